@@ -0,0 +1,967 @@
+use crate::codec::Codec;
+use crate::connection::{self, ConnectionReader, ConnectionWriter};
+use crate::endpoint::Endpoint;
+use crate::error::AquariusErr;
+use crate::event::AquariusEvent;
+use crate::messages::Bib;
+use crate::messages::Heat;
+use crate::messages::RequestListOpenHeats;
+use crate::messages::RequestSetTime;
+use crate::messages::RequestStartList;
+use crate::messages::ResponseListOpenHeats;
+use crate::messages::ResponseSetTime;
+use crate::messages::ResponseStartList;
+use crate::outbox::{Delivery, Outbox, OutboxItem};
+use crate::recorder::{ConnectionRecorder, Recorder};
+use crate::status::{ConnectionState, ConnectionStats};
+use crate::utils;
+use ::chrono::Utc;
+use ::db::timekeeper::Timestamp;
+use ::futures::Stream;
+use ::futures::StreamExt;
+use ::futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use ::std::collections::VecDeque;
+use ::std::io::{self, ErrorKind};
+use ::std::mem;
+use ::std::pin::Pin;
+use ::std::sync::{Arc, Mutex as StdMutex};
+use ::std::task::{Context, Poll};
+use ::std::time::Duration;
+use ::tokio::net::{TcpStream, lookup_host};
+use ::tokio::select;
+use ::tokio::sync::{Mutex, oneshot};
+use ::tokio::task::JoinHandle;
+use ::tokio::time::{self, Instant};
+use ::tokio_util::sync::CancellationToken;
+use ::tracing::{debug, error, info, trace, warn};
+
+/// The maximum delay between two attempts to connect to Aquarius.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The time to wait for Aquarius to answer a `TIME` command.
+const SEND_TIME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An asynchronous client to connect to the Aquarius application. It runs on the tokio runtime and emits its events
+/// as an [`AquariusEventStream`].
+///
+/// The client uses a single connection to Aquarius. A task reads all lines from the connection: lines starting with
+/// `!` are unsolicited events, all other lines belong to the reply of the oldest request still waiting for its reply.
+pub struct AsyncAquariusClient {
+    /// The connection to the Aquarius application, if connected.
+    connection: Arc<Mutex<Option<Session>>>,
+
+    /// The state and statistics of the connection to the Aquarius application.
+    status: Arc<StdMutex<Status>>,
+
+    /// A token to indicate if the Aquarius client should shut down.
+    shutdown: CancellationToken,
+
+    /// The text encoding of the link to Aquarius.
+    codec: Arc<StdMutex<Codec>>,
+
+    /// Records the lines sent to and received from Aquarius, if set.
+    recorder: Arc<StdMutex<Option<Recorder>>>,
+
+    /// Queues the times that could not be sent to Aquarius, if set. It stays locked while queued times are sent, so
+    /// new times are sent after the queued ones.
+    outbox: Arc<Mutex<Option<Outbox>>>,
+
+    /// A copy of the times waiting in the outbox, so they can be read while the outbox is locked to send them.
+    queued: Arc<StdMutex<Vec<OutboxItem>>>,
+
+    /// Emits the events of the client, e.g. the rejection of a time sent from the outbox.
+    sender: UnboundedSender<AquariusEvent>,
+}
+
+impl AsyncAquariusClient {
+    /// Creates a new `AsyncAquariusClient` and connects it to the Aquarius application. Must be called within a tokio
+    /// runtime.
+    /// # Arguments
+    /// * `host` - The host to connect to.
+    /// * `port` - The port to connect to.
+    /// * `timeout` - The timeout in milliseconds to connect to Aquarius.
+    /// # Returns
+    /// A client to communicate with Aquarius application and the stream of events emitted by the client.
+    /// # Errors
+    /// If the client could not be created.
+    pub async fn new(host: &str, port: u16, timeout: u16) -> Result<(Self, AquariusEventStream), AquariusErr> {
+        Self::with_endpoints(vec![Endpoint::new(host, port)], timeout).await
+    }
+
+    /// Creates a new `AsyncAquariusClient` that connects to the first reachable of the given endpoints. The host names
+    /// are resolved on each attempt to connect. If an endpoint is unreachable, the client fails over to the next one
+    /// and only backs off after all endpoints failed. Must be called within a tokio runtime.
+    /// # Arguments
+    /// * `endpoints` - The endpoints to connect to, starting with the primary one.
+    /// * `timeout` - The timeout in milliseconds to connect to Aquarius.
+    /// # Returns
+    /// A client to communicate with Aquarius application and the stream of events emitted by the client.
+    /// # Errors
+    /// If no endpoint is given.
+    pub async fn with_endpoints(
+        endpoints: Vec<Endpoint>,
+        timeout: u16,
+    ) -> Result<(Self, AquariusEventStream), AquariusErr> {
+        if endpoints.is_empty() {
+            return Err(AquariusErr::NoEndpointError());
+        }
+        let (sender, receiver) = mpsc::unbounded();
+        let client = AsyncAquariusClient {
+            connection: Arc::new(Mutex::new(None)),
+            status: Arc::new(StdMutex::new(Status::default())),
+            shutdown: CancellationToken::new(),
+            codec: Arc::new(StdMutex::new(Codec::default())),
+            recorder: Arc::new(StdMutex::new(None)),
+            outbox: Arc::new(Mutex::new(None)),
+            queued: Arc::new(StdMutex::new(Vec::new())),
+            sender: sender.clone(),
+        };
+        client.start_watch_dog(endpoints, timeout, sender);
+        Ok((client, AquariusEventStream { receiver }))
+    }
+
+    /// Reads the open heats from Aquarius.
+    /// # Returns
+    /// A vector of open heats or an error if the heats could not be read. The heats contain the boats that are in the heats.
+    /// # Errors
+    /// If the open heats could not be read from Aquarius.
+    pub async fn read_open_heats(&self) -> Result<Vec<Heat>, AquariusErr> {
+        let start = Instant::now();
+        let response = request(&self.connection, &RequestListOpenHeats::default().to_string()).await?;
+        if let Ok(mut status) = self.status.lock() {
+            status.stats.open_heats_latency = Some(start.elapsed());
+        }
+        let mut heats = response.parse::<ResponseListOpenHeats>()?;
+        for heat in heats.heats.iter_mut() {
+            read_start_list(&self.connection, heat).await?;
+        }
+        Ok(heats.heats)
+    }
+
+    /// Sends a time stamp to Aquarius and waits for Aquarius to accept it. If an outbox is set and the client is not
+    /// connected or Aquarius did not answer in time, the time is queued and sent as soon as Aquarius is connected
+    /// again.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to send to Aquarius.
+    /// * `bib` - The bib number of the boat to send the time stamp to.
+    /// # Returns
+    /// Whether the time was accepted by Aquarius or queued in the outbox.
+    /// # Errors
+    /// If Aquarius rejected the time, or if there is no outbox and the client is not connected or Aquarius did not
+    /// answer in time.
+    pub async fn send_time(&self, timestamp: &Timestamp, bib: Option<Bib>) -> Result<Delivery, AquariusErr> {
+        let cmd = RequestSetTime {
+            time: timestamp.time.into(),
+            split: timestamp.split().clone(),
+            heat_nr: timestamp.heat_nr().unwrap_or_default(),
+            bib,
+        }
+        .to_string();
+        let mut outbox = self.outbox.lock().await;
+        let Some(outbox) = outbox.as_mut() else {
+            send_time_command(&self.connection, &cmd).await?;
+            return Ok(Delivery::Accepted);
+        };
+        // Times are only sent directly if no older times are waiting, to keep them in order
+        if outbox.is_empty() {
+            match send_time_command(&self.connection, &cmd).await {
+                // a time that was not answered in time is sent again, the connection is dropped anyway
+                Err(AquariusErr::NotConnectedError())
+                | Err(AquariusErr::IoError(_))
+                | Err(AquariusErr::TimeoutError(_)) => {}
+                result => return result.map(|_| Delivery::Accepted),
+            }
+        }
+        let item = outbox.push(&cmd);
+        update_queued(&self.queued, outbox);
+        let item = item?;
+        info!(id = item.id, command = item.command, "Queued time in outbox:");
+        Ok(Delivery::Queued { id: item.id })
+    }
+
+    /// Sets the outbox to queue the times that could not be sent to Aquarius. Times already waiting in the outbox are
+    /// sent if the client is connected.
+    /// # Arguments
+    /// * `outbox` - The outbox to use, or `None` to fail sending times while not connected.
+    pub async fn set_outbox(&self, outbox: Option<Outbox>) {
+        let mut current = self.outbox.lock().await;
+        *current = outbox;
+        if let Ok(mut queued) = self.queued.lock() {
+            *queued = current.as_ref().map(Outbox::items).unwrap_or_default();
+        }
+        drop(current);
+        if self.state().is_connected() {
+            tokio::spawn(flush_outbox(
+                self.connection.clone(),
+                self.outbox.clone(),
+                self.queued.clone(),
+                self.sender.clone(),
+            ));
+        }
+    }
+
+    /// Returns the times waiting in the outbox, in the order they will be sent. Does not wait for the queued times
+    /// being sent.
+    pub fn outbox_items(&self) -> Vec<OutboxItem> {
+        self.queued.lock().map(|queued| queued.clone()).unwrap_or_default()
+    }
+
+    /// Discards a time waiting in the outbox, so it is never sent to Aquarius.
+    /// # Arguments
+    /// * `id` - The identifier of the item to discard.
+    /// # Returns
+    /// The discarded item, or `None` if there is no such item.
+    /// # Errors
+    /// If the outbox could not be stored.
+    pub async fn discard_outbox_item(&self, id: u64) -> Result<Option<OutboxItem>, AquariusErr> {
+        match self.outbox.lock().await.as_mut() {
+            Some(outbox) => {
+                let item = outbox.discard(id);
+                update_queued(&self.queued, outbox);
+                item
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the current state of the connection to Aquarius.
+    pub fn state(&self) -> ConnectionState {
+        self.status
+            .lock()
+            .map(|status| status.state.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns a snapshot of the statistics of the connection to Aquarius.
+    pub fn stats(&self) -> ConnectionStats {
+        self.status
+            .lock()
+            .map(|status| status.stats.clone())
+            .unwrap_or_default()
+    }
+
+    /// Sets the text encoding of the link to Aquarius, Windows-1252 by default. Takes effect immediately, also for an
+    /// established connection.
+    /// # Arguments
+    /// * `codec` - The text encoding used by Aquarius.
+    pub fn set_codec(&self, codec: Codec) {
+        if let Ok(mut current) = self.codec.lock() {
+            *current = codec;
+        }
+    }
+
+    /// Sets the recorder for the lines sent to and received from Aquarius. Takes effect immediately, also for an
+    /// established connection.
+    /// # Arguments
+    /// * `recorder` - The recorder to use, or `None` to stop recording.
+    pub fn set_recorder(&self, recorder: Option<Recorder>) {
+        if let Ok(mut current) = self.recorder.lock() {
+            *current = recorder;
+        }
+    }
+
+    /// Closes the connection to Aquarius and stops the watch dog task.
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+        if let Some(session) = self.connection.lock().await.as_mut() {
+            session.writer.disconnect().await;
+        }
+    }
+
+    /// Starts a task to watch the connection to Aquarius. The task connects to Aquarius, reads from the connection until
+    /// it is lost and then reconnects. Failed attempts to connect are retried with an exponential backoff.
+    /// # Returns
+    /// A handle to the task that watches the connection to Aquarius.
+    fn start_watch_dog(
+        &self,
+        endpoints: Vec<Endpoint>,
+        timeout: u16,
+        sender: UnboundedSender<AquariusEvent>,
+    ) -> JoinHandle<()> {
+        let connection_mutex = self.connection.clone();
+        let status = self.status.clone();
+        let shutdown = self.shutdown.clone();
+        let codec = self.codec.clone();
+        let recorder = self.recorder.clone();
+        let outbox = self.outbox.clone();
+        let queued = self.queued.clone();
+
+        tokio::spawn(async move {
+            // The delay before the first retry to connect to Aquarius in case of a failure
+            let initial_backoff = Duration::from_millis(timeout as u64);
+            let mut backoff = initial_backoff;
+            // The identifier of the last established connection, used to tell connections apart in recordings
+            let mut connection_id = 0;
+            // The index of the endpoint to connect to next
+            let mut index = 0;
+            // The number of endpoints that failed since the last established connection or backoff
+            let mut failures = 0;
+
+            // Loop until the client is shut down
+            while !shutdown.is_cancelled() {
+                update_stats(&status, |stats| stats.connect_attempts += 1);
+                set_state(&status, &sender, ConnectionState::Connecting);
+                // create a new connection to Aquarius
+                let endpoint = &endpoints[index];
+                let reason = match connect(endpoint, timeout).await {
+                    Ok(stream) => {
+                        connection_id += 1;
+                        let (reader, writer) = connection::split(
+                            stream,
+                            codec.clone(),
+                            ConnectionRecorder::new(recorder.clone(), connection_id),
+                        );
+                        let pending = Arc::new(StdMutex::new(PendingReplies::default()));
+                        let reset = CancellationToken::new();
+                        *connection_mutex.lock().await = Some(Session {
+                            writer,
+                            pending: pending.clone(),
+                            reset: reset.clone(),
+                        });
+                        // only a connection following an established one is a reconnect, not a retry after failures
+                        if connection_id > 1 {
+                            update_stats(&status, |stats| stats.reconnects += 1);
+                        }
+                        info!(%endpoint, "Connected to Aquarius");
+                        update_stats(&status, |stats| stats.active_endpoint = Some(endpoint.clone()));
+                        set_state(&status, &sender, ConnectionState::Connected);
+                        backoff = initial_backoff;
+                        failures = 0;
+                        tokio::spawn(flush_outbox(
+                            connection_mutex.clone(),
+                            outbox.clone(),
+                            queued.clone(),
+                            sender.clone(),
+                        ));
+
+                        // Spawn a task to handle the events, since handling an event may require further requests
+                        let (event_sender, event_receiver) = mpsc::unbounded();
+                        let event_task =
+                            tokio::spawn(handle_events(event_receiver, connection_mutex.clone(), sender.clone()));
+                        // Read from the connection until it is lost or the client is shut down
+                        let reason =
+                            receive_lines(reader, pending, event_sender, status.clone(), shutdown.clone(), reset).await;
+
+                        if let Some(mut session) = connection_mutex.lock().await.take() {
+                            session.writer.disconnect().await;
+                        }
+                        // Wait until all received events are handled before the connection loss is reported
+                        let _ = event_task.await;
+                        info!(reason, "Disconnected from Aquarius:");
+                        update_stats(&status, |stats| stats.active_endpoint = None);
+                        reason
+                    }
+                    Err(err) => {
+                        let reason = err.to_string();
+                        // Only log the first of repeated errors as warning, to avoid flooding the log
+                        if status
+                            .lock()
+                            .is_ok_and(|status| status.stats.last_error.as_ref() != Some(&reason))
+                        {
+                            warn!(%endpoint, %err, "Error connecting to Aquarius:");
+                        } else {
+                            debug!(%endpoint, %err, "Error connecting to Aquarius:");
+                        }
+                        update_stats(&status, |stats| stats.last_error = Some(reason.clone()));
+                        // fail over to the next endpoint
+                        failures += 1;
+                        index = (index + 1) % endpoints.len();
+                        reason
+                    }
+                };
+                set_state(&status, &sender, ConnectionState::Disconnected { reason });
+                if shutdown.is_cancelled() {
+                    break;
+                }
+                // try the remaining endpoints before backing off
+                if failures > 0 && failures < endpoints.len() {
+                    continue;
+                }
+                failures = 0;
+
+                let next_attempt = Utc::now() + backoff;
+                set_state(&status, &sender, ConnectionState::Backoff { next_attempt });
+                select! {
+                    _ = shutdown.cancelled() => {},
+                    _ = time::sleep(backoff) => {},
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            } // end while
+            debug!("Stopped watch dog task.");
+        })
+    }
+}
+
+impl Drop for AsyncAquariusClient {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// A stream of the events emitted by an [`AsyncAquariusClient`]. The stream ends when the client is shut down.
+pub struct AquariusEventStream {
+    receiver: UnboundedReceiver<AquariusEvent>,
+}
+
+impl Stream for AquariusEventStream {
+    type Item = AquariusEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// The state and statistics of the connection, shared between the client and its tasks.
+#[derive(Default)]
+struct Status {
+    /// The current state of the connection.
+    state: ConnectionState,
+
+    /// The statistics of the connection.
+    stats: ConnectionStats,
+}
+
+/// An established connection to Aquarius that is used to write requests. The replies are received by the task reading
+/// from the connection.
+struct Session {
+    /// The writing half of the connection.
+    writer: ConnectionWriter,
+
+    /// The requests waiting for their replies.
+    pending: Arc<StdMutex<PendingReplies>>,
+
+    /// A token to drop the connection, e.g. if a reply is missing and the following replies can't be assigned to
+    /// their requests anymore.
+    reset: CancellationToken,
+}
+
+/// The requests written to Aquarius that are still waiting for their replies. Aquarius answers requests in the order
+/// they were received, so the next reply always belongs to the oldest pending request.
+#[derive(Default)]
+struct PendingReplies {
+    /// The senders to complete the pending requests, in the order the requests were written.
+    senders: VecDeque<oneshot::Sender<String>>,
+
+    /// Whether the connection is closed and no more replies will be received.
+    closed: bool,
+}
+
+/// Writes a request to Aquarius and waits for its reply.
+/// # Arguments
+/// * `connection` - The connection to write the request to.
+/// * `cmd` - The request to write.
+/// # Returns
+/// The lines of the reply, separated by a newline.
+/// # Errors
+/// If the client is not connected, the request could not be written or the connection was lost before the reply was
+/// received.
+async fn request(connection: &Mutex<Option<Session>>, cmd: &str) -> Result<String, AquariusErr> {
+    let reply = {
+        // Keep the connection locked while writing, so the requests are written in the order of the pending replies.
+        let mut guard = connection.lock().await;
+        let session = guard.as_mut().ok_or(AquariusErr::NotConnectedError())?;
+        let (sender, reply) = oneshot::channel();
+        {
+            let mut pending = session.pending.lock().map_err(|_| AquariusErr::MutexPoisonError())?;
+            if pending.closed {
+                return Err(AquariusErr::NotConnectedError());
+            }
+            pending.senders.push_back(sender);
+        }
+        if let Err(err) = session.writer.write(cmd).await {
+            // No reply will be received for a request that was not written
+            if let Ok(mut pending) = session.pending.lock() {
+                pending.senders.pop_back();
+            }
+            return Err(err.into());
+        }
+        reply
+    };
+    reply.await.map_err(|_| AquariusErr::NotConnectedError())
+}
+
+/// Sends a `TIME` command to Aquarius and waits for Aquarius to accept it.
+/// # Errors
+/// If the client is not connected, Aquarius rejected the time or did not answer in time.
+async fn send_time_command(connection: &Mutex<Option<Session>>, cmd: &str) -> Result<(), AquariusErr> {
+    let response = match time::timeout(SEND_TIME_TIMEOUT, request(connection, cmd)).await {
+        Ok(response) => response?,
+        Err(_) => {
+            // The missing reply would be taken for the reply of the next request, so the connection is dropped
+            if let Some(session) = connection.lock().await.as_ref() {
+                session.reset.cancel();
+            }
+            return Err(AquariusErr::TimeoutError(SEND_TIME_TIMEOUT));
+        }
+    };
+    match response.parse::<ResponseSetTime>()? {
+        ResponseSetTime::Accepted => Ok(()),
+        ResponseSetTime::Rejected { reason } => Err(AquariusErr::Rejected { reason }),
+    }
+}
+
+/// Sends the times waiting in the outbox to Aquarius, in the order they were queued. Stops at the first time that
+/// could not be sent. Times rejected by Aquarius, or answered with a reply that can't be read, are discarded and
+/// reported as [`AquariusEvent::QueuedTimeRejected`], so they don't block the outbox.
+async fn flush_outbox(
+    connection: Arc<Mutex<Option<Session>>>,
+    outbox: Arc<Mutex<Option<Outbox>>>,
+    queued: Arc<StdMutex<Vec<OutboxItem>>>,
+    sender: UnboundedSender<AquariusEvent>,
+) {
+    let mut outbox = outbox.lock().await;
+    let Some(outbox) = outbox.as_mut() else {
+        return;
+    };
+    while let Some(item) = outbox.front().cloned() {
+        match send_time_command(&connection, &format!("{}\n", item.command)).await {
+            Ok(()) => info!(id = item.id, command = item.command, "Sent queued time:"),
+            Err(AquariusErr::Rejected { reason }) | Err(AquariusErr::InvalidMessage(reason)) => {
+                warn!(
+                    id = item.id,
+                    command = item.command,
+                    reason,
+                    "Discarding queued time rejected by Aquarius:"
+                );
+                let event = AquariusEvent::QueuedTimeRejected {
+                    item: item.clone(),
+                    reason,
+                };
+                if let Err(err) = sender.unbounded_send(event) {
+                    warn!(%err, "Error sending rejection of queued time:");
+                }
+            }
+            Err(err) => {
+                warn!(%err, id = item.id, "Error sending queued time:");
+                break;
+            }
+        }
+        let discarded = outbox.discard(item.id);
+        update_queued(&queued, outbox);
+        if let Err(err) = discarded {
+            error!(%err, id = item.id, "Error removing time from outbox:");
+            break;
+        }
+    }
+}
+
+/// Updates the copy of the times waiting in the outbox.
+fn update_queued(queued: &StdMutex<Vec<OutboxItem>>, outbox: &Outbox) {
+    if let Ok(mut queued) = queued.lock() {
+        *queued = outbox.items();
+    }
+}
+
+async fn connect(endpoint: &Endpoint, timeout: u16) -> io::Result<TcpStream> {
+    trace!(%endpoint, timeout, "Connecting to:");
+    let timeout = Duration::from_millis(timeout as u64);
+    // resolve the host name on each attempt, since the address of the Aquarius PC may have changed
+    let addresses = time::timeout(timeout, lookup_host((endpoint.host.as_str(), endpoint.port))).await??;
+    let mut last_err = io::Error::new(ErrorKind::NotFound, format!("Could not resolve {endpoint}"));
+    for addr in addresses {
+        match time::timeout(timeout, TcpStream::connect(addr)).await {
+            Ok(Ok(stream)) => {
+                stream.set_nodelay(true)?;
+                trace!(%addr, "Connected to:");
+                return Ok(stream);
+            }
+            Ok(Err(err)) => last_err = err,
+            Err(err) => last_err = err.into(),
+        }
+    }
+    Err(last_err)
+}
+
+async fn read_start_list(connection: &Mutex<Option<Session>>, heat: &mut Heat) -> Result<(), AquariusErr> {
+    let response = request(connection, &RequestStartList::new(heat.id).to_string()).await?;
+    let start_list = response.parse::<ResponseStartList>()?;
+    heat.boats = Some(start_list.boats);
+    Ok(())
+}
+
+/// Sets the state of the connection and sends it to the application.
+fn set_state(status: &StdMutex<Status>, sender: &UnboundedSender<AquariusEvent>, state: ConnectionState) {
+    trace!(%state, "Connection state changed:");
+    if let Ok(mut status) = status.lock() {
+        status.state = state.clone();
+    }
+    if let Err(err) = sender.unbounded_send(AquariusEvent::Client(state)) {
+        error!(%err, "Error sending message to application:");
+    }
+}
+
+/// Helper function to update the statistics of the connection.
+fn update_stats<T: Default>(status: &StdMutex<Status>, func: impl FnOnce(&mut ConnectionStats) -> T) -> T {
+    status
+        .lock()
+        .map(|mut status| func(&mut status.stats))
+        .unwrap_or_default()
+}
+
+/// Reads all lines from the connection until it is lost or the client is shut down. Events are passed to the event
+/// task, all other lines are collected to replies and passed to the pending requests.
+/// # Returns
+/// The reason why reading from the connection stopped.
+async fn receive_lines(
+    mut reader: ConnectionReader,
+    pending: Arc<StdMutex<PendingReplies>>,
+    events: UnboundedSender<String>,
+    status: Arc<StdMutex<Status>>,
+    shutdown: CancellationToken,
+    reset: CancellationToken,
+) -> String {
+    debug!("Starting to receive lines from Aquarius");
+    // The lines of the reply that is currently received
+    let mut reply = String::new();
+    let reason = loop {
+        // Read a line from the server and wait until a line is received or the client is shut down.
+        let received = select! {
+            _ = shutdown.cancelled() => break "Client shut down".to_owned(),
+            _ = reset.cancelled() => break "Missing reply from Aquarius".to_owned(),
+            received = reader.receive_line() => received,
+        };
+        if received.is_ok() {
+            update_stats(&status, |stats| stats.last_message_received = Some(Utc::now()));
+        }
+        match received {
+            // an unsolicited event
+            Ok(received) if received.starts_with('!') => {
+                if let Err(err) = events.unbounded_send(received) {
+                    error!(%err, "Error passing event to event task:");
+                }
+            }
+            // an empty line terminates a reply
+            Ok(received) if received.is_empty() => {
+                let sender = pending.lock().ok().and_then(|mut pending| pending.senders.pop_front());
+                match sender {
+                    Some(sender) => {
+                        // the request may have been given up in the meantime
+                        let _ = sender.send(mem::take(&mut reply));
+                    }
+                    None => debug!("Ignoring reply without pending request"),
+                }
+            }
+            // a line of a reply
+            Ok(received) => {
+                if !reply.is_empty() {
+                    reply.push('\n');
+                }
+                reply.push_str(&received);
+            }
+            // an error occurred while receiving a line
+            Err(err) => {
+                warn!(%err);
+                let reason = err.to_string();
+                update_stats(&status, |stats| stats.last_error = Some(reason.clone()));
+                break reason;
+            }
+        }
+    };
+    // Fail all requests still waiting for a reply
+    if let Ok(mut pending) = pending.lock() {
+        pending.closed = true;
+        pending.senders.clear();
+    }
+    debug!("Stopped receiving lines from Aquarius");
+    reason
+}
+
+/// Handles the events received from Aquarius until the connection is lost. Opened heats are completed with their start
+/// lists before they are passed to the application, unknown events are passed on as [`AquariusEvent::Unknown`].
+async fn handle_events(
+    mut events: UnboundedReceiver<String>,
+    connection: Arc<Mutex<Option<Session>>>,
+    sender: UnboundedSender<AquariusEvent>,
+) {
+    while let Some(received) = events.next().await {
+        // Parse the received line and handle the event
+        let event = match received.parse::<AquariusEvent>() {
+            Ok(AquariusEvent::HeatListChanged(mut event)) => {
+                if event.opened
+                    && let Err(err) = read_start_list(&connection, &mut event.heat).await
+                {
+                    warn!(%err, "Error reading start list:");
+                }
+                AquariusEvent::HeatListChanged(event)
+            }
+            Ok(AquariusEvent::Unknown(line)) => {
+                debug!(line = utils::print_whitespaces(&line), "Unknown event:");
+                AquariusEvent::Unknown(line)
+            }
+            Ok(event) => event,
+            Err(err) => {
+                warn!(%err);
+                continue;
+            }
+        };
+        if let Err(err) = sender.unbounded_send(event) {
+            error!(%err, "Error sending message to application:");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::db::timekeeper::Split;
+    use ::std::net::SocketAddr;
+    use ::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use ::tokio::net::TcpListener;
+    use ::tracing::Level;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+    const OPEN_REPLY: &str = "3 2766 4\r\n\r\n";
+
+    async fn init_client(events: &'static [&'static str]) -> (AsyncAquariusClient, AquariusEventStream) {
+        let _ = tracing_subscriber::fmt()
+            .with_max_level(Level::TRACE)
+            .with_test_writer()
+            .try_init();
+        let addr = start_test_server(events, OPEN_REPLY).await;
+        AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap()
+    }
+
+    /// Starts a server that answers `?OPEN` and `?STARTLIST` requests like Aquarius does. The given events are sent on
+    /// the first accepted connection, `?OPEN` is answered with the given reply.
+    async fn start_test_server(events: &'static [&'static str], open_reply: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut events = Some(events);
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, events.take().unwrap_or_default(), open_reply));
+            }
+        });
+        addr
+    }
+
+    async fn serve(stream: TcpStream, events: &[&str], open_reply: &str) {
+        let (reader, mut writer) = stream.into_split();
+        for event in events {
+            writer.write_all(format!("{event}\r\n").as_bytes()).await.unwrap();
+        }
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let response = match line.as_str() {
+                "?OPEN" => open_reply,
+                "?STARTLIST id=2766" => "1 1 0 'RV Neptun Konstanz'\r\n2 2 0 'RG Heidelberg'\r\n\r\n",
+                _ => continue,
+            };
+            writer.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    async fn next_event(events: &mut AquariusEventStream) -> AquariusEvent {
+        time::timeout(TEST_TIMEOUT, events.next()).await.unwrap().unwrap()
+    }
+
+    async fn wait_connected(events: &mut AquariusEventStream) {
+        while !matches!(
+            next_event(events).await,
+            AquariusEvent::Client(ConnectionState::Connected)
+        ) {}
+    }
+
+    #[tokio::test]
+    async fn test_client_connection() {
+        let (client, mut events) = init_client(&[]).await;
+        assert!(matches!(
+            next_event(&mut events).await,
+            AquariusEvent::Client(ConnectionState::Connecting)
+        ));
+        assert!(matches!(
+            next_event(&mut events).await,
+            AquariusEvent::Client(ConnectionState::Connected)
+        ));
+        assert!(client.connection.lock().await.is_some());
+        assert!(client.state().is_connected());
+        assert_eq!(client.stats().connect_attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_read_open_heats() {
+        let (client, mut events) = init_client(&[]).await;
+        wait_connected(&mut events).await;
+
+        let heats = client.read_open_heats().await.unwrap();
+        assert!(client.stats().open_heats_latency.is_some());
+        assert!(client.stats().last_message_received.is_some());
+        assert_eq!(heats.len(), 1);
+        assert_eq!(heats[0].id, 2766);
+        assert_eq!(heats[0].number, 3);
+        let boats = heats[0].boats.as_ref().unwrap();
+        assert_eq!(boats.len(), 2);
+        assert_eq!(boats[1].club, "RG Heidelberg");
+    }
+
+    #[tokio::test]
+    async fn test_heat_list_changed_event() {
+        let (_client, mut events) = init_client(&["!OPEN+ 3 2766 4"]).await;
+
+        let event = loop {
+            if let AquariusEvent::HeatListChanged(event) = next_event(&mut events).await {
+                break event;
+            }
+        };
+        assert!(event.opened);
+        assert_eq!(event.heat.id, 2766);
+        assert_eq!(event.heat.boats.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_not_connected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let (client, _events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+        let result = client.read_open_heats().await;
+        assert!(matches!(result, Err(AquariusErr::NotConnectedError())));
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_backup() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let unreachable = listener.local_addr().unwrap();
+        drop(listener);
+        let backup = start_test_server(&[], OPEN_REPLY).await;
+        let endpoints = vec![
+            Endpoint::new("localhost", unreachable.port()),
+            Endpoint::new(&backup.ip().to_string(), backup.port()),
+        ];
+
+        let (client, mut events) = AsyncAquariusClient::with_endpoints(endpoints.clone(), 100)
+            .await
+            .unwrap();
+        wait_connected(&mut events).await;
+        let stats = client.stats();
+        assert_eq!(stats.connect_attempts, 2);
+        assert_eq!(stats.reconnects, 0);
+        assert_eq!(stats.active_endpoint, Some(endpoints[1].clone()));
+        assert_eq!(client.read_open_heats().await.unwrap().len(), 1);
+
+        assert!(matches!(
+            AsyncAquariusClient::with_endpoints(Vec::new(), 100).await,
+            Err(AquariusErr::NoEndpointError())
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_ends_event_stream() {
+        let (client, mut events) = init_client(&[]).await;
+        wait_connected(&mut events).await;
+
+        client.shutdown().await;
+        drop(client);
+        let remaining = time::timeout(TEST_TIMEOUT, events.collect::<Vec<_>>()).await.unwrap();
+        assert!(matches!(
+            remaining.last(),
+            Some(AquariusEvent::Client(ConnectionState::Disconnected { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_event_within_reply() {
+        let addr = start_test_server(&[], "3 2766 4\r\n!OPEN- 50 2767 4\r\n\r\n").await;
+        let (client, mut events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+        wait_connected(&mut events).await;
+
+        let heats = client.read_open_heats().await.unwrap();
+        assert_eq!(heats.len(), 1);
+        assert_eq!(heats[0].id, 2766);
+        assert_eq!(heats[0].boats.as_ref().unwrap().len(), 2);
+
+        match next_event(&mut events).await {
+            AquariusEvent::HeatListChanged(event) => {
+                assert!(!event.opened);
+                assert_eq!(event.heat.id, 2767);
+            }
+            event => panic!("Unexpected event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_lost_during_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // read the request and close the connection without replying
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut lines = BufReader::new(stream).lines();
+            let _ = lines.next_line().await;
+        });
+
+        let (client, mut events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+        wait_connected(&mut events).await;
+
+        let result = time::timeout(TEST_TIMEOUT, client.read_open_heats()).await.unwrap();
+        assert!(matches!(result, Err(AquariusErr::NotConnectedError())));
+        assert!(matches!(
+            next_event(&mut events).await,
+            AquariusEvent::Client(ConnectionState::Disconnected { .. })
+        ));
+        assert!(matches!(
+            next_event(&mut events).await,
+            AquariusEvent::Client(ConnectionState::Backoff { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_time_timeout() {
+        // the test server never answers TIME commands
+        let (client, mut events) = init_client(&[]).await;
+        wait_connected(&mut events).await;
+
+        let timestamp = Timestamp::new(Utc::now(), Split::Finish, Some(3));
+        let result = client.send_time(&timestamp, None).await;
+        assert!(matches!(result, Err(AquariusErr::TimeoutError(_))));
+        match next_event(&mut events).await {
+            AquariusEvent::Client(ConnectionState::Disconnected { reason }) => {
+                assert_eq!(reason, "Missing reply from Aquarius")
+            }
+            event => panic!("Unexpected event: {event:?}"),
+        }
+        // the client reconnects and the replies are assigned to their requests again
+        wait_connected(&mut events).await;
+        assert_eq!(client.stats().reconnects, 1);
+        assert_eq!(client.read_open_heats().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_backoff_and_stats() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let (client, mut events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 50)
+            .await
+            .unwrap();
+        let mut delays = Vec::new();
+        while delays.len() < 3 {
+            match next_event(&mut events).await {
+                AquariusEvent::Client(ConnectionState::Disconnected { reason }) => assert!(!reason.is_empty()),
+                AquariusEvent::Client(ConnectionState::Backoff { next_attempt }) => {
+                    delays.push(next_attempt - Utc::now())
+                }
+                _ => {}
+            }
+        }
+        // the delay doubles with each failed attempt: 50ms, 100ms, 200ms
+        assert!(delays[2] > delays[1] && delays[1] > delays[0]);
+
+        let stats = client.stats();
+        assert!(stats.connect_attempts >= 3);
+        assert_eq!(stats.reconnects, 0);
+        assert!(stats.last_error.is_some());
+        assert!(stats.last_message_received.is_none());
+    }
+}
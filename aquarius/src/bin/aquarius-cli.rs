@@ -0,0 +1,212 @@
+use ::aquarius::client::AquariusClient;
+use ::aquarius::codec::Codec;
+use ::aquarius::endpoint::Endpoint;
+use ::aquarius::error::AquariusErr;
+use ::aquarius::event::AquariusEvent;
+use ::aquarius::messages::Heat;
+use ::aquarius::outbox::Delivery;
+use ::aquarius::status::ConnectionState;
+use ::chrono::{Local, NaiveTime, Utc};
+use ::clap::{Parser, Subcommand};
+use ::db::timekeeper::{Split, Timestamp};
+use ::std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use ::std::time::{Duration, Instant};
+use ::tracing_subscriber::EnvFilter;
+
+/// Talks to the Aquarius application from the command line, e.g. to check the link or to fix times.
+#[derive(Parser)]
+#[command(about, long_about = None)]
+struct Args {
+    /// The hosts to connect to, separated by commas and starting with the primary one, e.g.
+    /// `aquarius,aquarius-backup:2049`. Hosts without a port use the port given by `--port`
+    #[arg(long, default_value = "aquarius")]
+    host: String,
+
+    /// The default port to connect to
+    #[arg(long, default_value = "2048")]
+    port: u16,
+
+    /// The connection timeout in milliseconds
+    #[arg(long, default_value = "500")]
+    timeout: u16,
+
+    /// The time in seconds to wait for the connection to Aquarius
+    #[arg(long, default_value = "10")]
+    wait: u64,
+
+    /// The text encoding of the link to Aquarius
+    #[arg(long, value_enum, default_value_t = Codec::Windows1252)]
+    codec: Codec,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the open heats with their start lists
+    OpenHeats {
+        /// Print the heats as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Prints the events emitted by Aquarius until interrupted
+    Tail {
+        /// Print each event as a line of JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Sends a time to Aquarius
+    SendTime {
+        /// The number of the heat
+        #[arg(long)]
+        heat: i16,
+
+        /// The split: `start`, `finish` or the number of an intermediate split
+        #[arg(long, value_parser = parse_split)]
+        split: Split,
+
+        /// The bib of the boat the time belongs to
+        #[arg(long)]
+        bib: Option<u8>,
+
+        /// The local time of day, e.g. `10:15:30.123`. Defaults to now
+        #[arg(long, value_parser = parse_time)]
+        time: Option<NaiveTime>,
+    },
+}
+
+fn main() -> Result<(), AquariusErr> {
+    // log to stderr, so the output can be piped into other tools
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = Args::parse();
+    let (sender, receiver) = mpsc::channel();
+    let client = AquariusClient::with_endpoints(Endpoint::parse_list(&args.host, args.port)?, args.timeout, sender)?;
+    client.set_codec(args.codec);
+
+    match args.command {
+        Command::OpenHeats { json } => {
+            wait_connected(&receiver, Duration::from_secs(args.wait))?;
+            let heats = client.read_open_heats()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&heats)?);
+            } else {
+                print_heats(&heats);
+            }
+        }
+        Command::Tail { json } => {
+            for event in receiver {
+                if json {
+                    println!("{}", serde_json::to_string(&event)?);
+                } else {
+                    println!("{} {}", Local::now().format("%H:%M:%S%.3f"), describe(&event));
+                }
+            }
+        }
+        Command::SendTime { heat, split, bib, time } => {
+            wait_connected(&receiver, Duration::from_secs(args.wait))?;
+            let time = match time {
+                Some(time) => Local::now()
+                    .with_time(time)
+                    .single()
+                    .ok_or_else(|| AquariusErr::InvalidMessage(format!("Invalid local time {time}")))?
+                    .with_timezone(&Utc),
+                None => Utc::now(),
+            };
+            let timestamp = Timestamp::new(time, split, Some(heat));
+            match client.send_time(&timestamp, bib)? {
+                Delivery::Accepted => println!("Accepted by Aquarius"),
+                Delivery::Queued { id } => println!("Queued as {id}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Waits until the client is connected to Aquarius.
+/// # Errors
+/// If the client is not connected within the given time.
+fn wait_connected(receiver: &Receiver<AquariusEvent>, wait: Duration) -> Result<(), AquariusErr> {
+    let deadline = Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(AquariusEvent::Client(ConnectionState::Connected)) => return Ok(()),
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                return Err(AquariusErr::NotConnectedError());
+            }
+        }
+    }
+}
+
+fn print_heats(heats: &[Heat]) {
+    println!("{:>5} {:>6}  State", "Heat", "Id");
+    for heat in heats {
+        println!("{:>5} {:>6}  {}", heat.number, heat.id, heat.state);
+        for boat in heat.boats.iter().flatten() {
+            println!(
+                "{:>12} lane {} bib {:>3}  {} ({})",
+                "", boat.lane, boat.bib, boat.club, boat.state
+            );
+        }
+    }
+}
+
+fn describe(event: &AquariusEvent) -> String {
+    match event {
+        AquariusEvent::HeatListChanged(event) => {
+            let action = if event.opened { "opened" } else { "closed" };
+            format!("Heat {} (id {}) {action}", event.heat.number, event.heat.id)
+        }
+        AquariusEvent::TimeConfirmed(event) => {
+            let bib = event.bib.map(|bib| format!(" bib {bib}")).unwrap_or_default();
+            format!(
+                "Time {} confirmed for heat {} at {}{bib}",
+                event.time, event.heat_nr, event.split
+            )
+        }
+        AquariusEvent::HeatStateChanged(event) => {
+            format!(
+                "Heat {} (id {}) is {}",
+                event.heat.number, event.heat.id, event.heat.state
+            )
+        }
+        AquariusEvent::ResultUpdated(event) => {
+            let rank = event
+                .rank
+                .map(|rank| rank.to_string())
+                .unwrap_or_else(|| "-".to_owned());
+            format!(
+                "Result of bib {} in heat id {}: {rank}. {}",
+                event.bib, event.heat_id, event.result
+            )
+        }
+        AquariusEvent::Unknown(line) => format!("Unknown: {line}"),
+        AquariusEvent::Client(state) => format!("Client: {state}"),
+        AquariusEvent::QueuedTimeRejected { item, reason } => {
+            format!("Queued time rejected: {} ({reason})", item.command)
+        }
+    }
+}
+
+fn parse_split(s: &str) -> Result<Split, String> {
+    match s.to_lowercase().as_str() {
+        "start" => Ok(Split::Start),
+        "finish" => Ok(Split::Finish),
+        split => match split.parse::<u8>() {
+            Ok(split_nr) if split_nr <= 64 => Ok(Split::from(split_nr)),
+            _ => Err(format!("Invalid split: {s}")),
+        },
+    }
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S%.f").map_err(|err| format!("Invalid time {s}: {err}"))
+}
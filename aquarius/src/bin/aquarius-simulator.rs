@@ -0,0 +1,45 @@
+use ::aquarius::codec::Codec;
+use ::aquarius::error::AquariusErr;
+use ::aquarius::simulator::{Scenario, Simulator};
+use ::clap::Parser;
+use ::std::path::PathBuf;
+use ::tracing_subscriber::EnvFilter;
+
+/// Simulates the Aquarius application for tests and training of timekeepers.
+#[derive(Parser)]
+#[command(about, long_about = None)]
+struct Args {
+    /// The address to listen on
+    #[arg(long, default_value = "0.0.0.0")]
+    bind: String,
+
+    /// The port to listen on
+    #[arg(long, default_value = "2048")]
+    port: u16,
+
+    /// The scenario file to play, see `aquarius/scenarios/example.scenario`
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+
+    /// The text encoding to use
+    #[arg(long, value_enum, default_value_t = Codec::Windows1252)]
+    codec: Codec,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), AquariusErr> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let args = Args::parse();
+    let scenario = match args.scenario {
+        Some(path) => Scenario::load(path)?,
+        None => Scenario::default(),
+    };
+
+    let simulator = Simulator::start_with_codec((args.bind.as_str(), args.port), scenario, args.codec).await?;
+    tokio::signal::ctrl_c().await?;
+    simulator.shutdown();
+    Ok(())
+}
@@ -1,34 +1,30 @@
-use crate::connection::Connection;
+use crate::async_client::AsyncAquariusClient;
+use crate::codec::Codec;
+use crate::endpoint::Endpoint;
 use crate::error::AquariusErr;
 use crate::event::AquariusEvent;
 use crate::messages::Bib;
-use crate::messages::EventHeatChanged;
 use crate::messages::Heat;
-use crate::messages::RequestListOpenHeats;
-use crate::messages::RequestSetTime;
-use crate::messages::RequestStartList;
-use crate::messages::ResponseListOpenHeats;
-use crate::messages::ResponseStartList;
-use crate::utils;
+use crate::outbox::{Delivery, Outbox, OutboxItem};
+use crate::recorder::Recorder;
+use crate::status::{ConnectionState, ConnectionStats};
 use ::db::timekeeper::Timestamp;
+use ::futures::StreamExt;
+use ::futures::executor;
+use ::std::future::Future;
 use ::std::io;
-use ::std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
-use ::std::sync::{
-    Arc, Mutex,
-    atomic::{AtomicBool, Ordering::Relaxed},
-    mpsc::Sender,
-};
-use ::std::thread::{self, JoinHandle};
-use ::std::time::{Duration, Instant};
-use ::tracing::{debug, error, info, trace, warn};
+use ::std::sync::{Arc, mpsc::Sender};
+use ::tokio::runtime::{Builder, Runtime};
+use ::tracing::error;
 
-/// A client to connect to the Aquarius application.
+/// A blocking client to connect to the Aquarius application. It runs an [`AsyncAquariusClient`] on a runtime of its
+/// own and forwards the events of the client to the application, so it can be used without an async context.
 pub struct AquariusClient {
-    /// The connection to the Aquarius application.
-    connection: Arc<Mutex<Option<Connection>>>,
+    /// The asynchronous client communicating with the Aquarius application.
+    client: Arc<AsyncAquariusClient>,
 
-    /// A flag to indicate if the Aquarius client should shut down.
-    shutdown: Arc<AtomicBool>,
+    /// The runtime the asynchronous client runs on. It is only taken when the client is dropped.
+    runtime: Option<Runtime>,
 }
 
 impl AquariusClient {
@@ -43,16 +39,46 @@ impl AquariusClient {
     /// # Errors
     /// If the client could not be created.
     pub fn new(host: &str, port: u16, timeout: u16, sender: Sender<AquariusEvent>) -> Result<Self, AquariusErr> {
-        let mut addrs_iter = format!("{host}:{port}").to_socket_addrs()?;
-        let address = addrs_iter
-            .next()
-            .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port));
-        let client = AquariusClient {
-            connection: Arc::new(Mutex::new(None)),
-            shutdown: Arc::new(AtomicBool::new(false)),
-        };
-        client.start_watch_dog(address, timeout, sender);
-        Ok(client)
+        Self::with_endpoints(vec![Endpoint::new(host, port)], timeout, sender)
+    }
+
+    /// Creates a new `AquariusClient` that connects to the first reachable of the given endpoints and fails over to
+    /// the next one if the current endpoint is unreachable.
+    /// # Arguments
+    /// * `endpoints` - The endpoints to connect to, starting with the primary one.
+    /// * `timeout` - The timeout in milliseconds to connect to Aquarius.
+    /// * `sender` - The sender to send events to the application.
+    /// # Returns
+    /// A client to communicate with Aquarius application.
+    /// # Errors
+    /// If the client could not be created or no endpoint is given.
+    pub fn with_endpoints(
+        endpoints: Vec<Endpoint>,
+        timeout: u16,
+        sender: Sender<AquariusEvent>,
+    ) -> Result<Self, AquariusErr> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("aquarius-client")
+            .enable_all()
+            .build()?;
+        let (client, mut events) = block_on(&runtime, async move {
+            AsyncAquariusClient::with_endpoints(endpoints, timeout).await
+        })?;
+
+        // Forward the events of the asynchronous client to the application
+        runtime.spawn(async move {
+            while let Some(event) = events.next().await {
+                if let Err(err) = sender.send(event) {
+                    error!(%err, "Error sending message to application:");
+                }
+            }
+        });
+
+        Ok(AquariusClient {
+            client: Arc::new(client),
+            runtime: Some(runtime),
+        })
     }
 
     /// Reads the open heats from Aquarius.
@@ -61,271 +87,206 @@ impl AquariusClient {
     /// # Errors
     /// If the open heats could not be read from Aquarius.
     pub fn read_open_heats(&self) -> Result<Vec<Heat>, AquariusErr> {
-        self.with_connection(|connection| {
-            connection.write(&RequestListOpenHeats::default().to_string())?;
-            let response = connection.receive_all()?;
-            let mut heats = response.parse::<ResponseListOpenHeats>()?;
-            for heat in heats.heats.iter_mut() {
-                AquariusClient::read_start_list(connection, heat)?;
-            }
-            Ok(heats.heats)
-        })
+        let client = self.client.clone();
+        self.block_on(async move { client.read_open_heats().await })
     }
 
-    /// Sends a time stamp to Aquarius.
+    /// Sends a time stamp to Aquarius and waits for Aquarius to accept it. If an outbox is set and the client is not
+    /// connected, the time is queued and sent as soon as Aquarius is connected again.
     /// # Arguments
     /// * `timestamp` - The time stamp to send to Aquarius.
     /// * `bib` - The bib number of the boat to send the time stamp to.
-    pub fn send_time(&self, timestamp: &Timestamp, bib: Option<Bib>) -> Result<(), AquariusErr> {
-        self.with_connection(|connection| {
-            let request = RequestSetTime {
-                time: timestamp.time.into(),
-                split: timestamp.split().clone(),
-                heat_nr: timestamp.heat_nr().unwrap_or_default(),
-                bib,
-            };
-            connection.write(&request.to_string())?;
+    /// # Returns
+    /// Whether the time was accepted by Aquarius or queued in the outbox.
+    /// # Errors
+    /// If the client is not connected and there is no outbox, Aquarius rejected the time or did not answer in time.
+    pub fn send_time(&self, timestamp: &Timestamp, bib: Option<Bib>) -> Result<Delivery, AquariusErr> {
+        let client = self.client.clone();
+        let timestamp = timestamp.clone();
+        self.block_on(async move { client.send_time(&timestamp, bib).await })
+    }
+
+    /// Sets the outbox to queue the times that could not be sent to Aquarius.
+    /// # Arguments
+    /// * `outbox` - The outbox to use, or `None` to fail sending times while not connected.
+    pub fn set_outbox(&self, outbox: Option<Outbox>) {
+        let client = self.client.clone();
+        self.block_on(async move {
+            client.set_outbox(outbox).await;
             Ok(())
         })
+        .ok();
+    }
+
+    /// Returns the times waiting in the outbox, in the order they will be sent.
+    pub fn outbox_items(&self) -> Vec<OutboxItem> {
+        self.client.outbox_items()
     }
 
-    /// Starts a thread to watch the thread that receives events from Aquarius.
+    /// Discards a time waiting in the outbox, so it is never sent to Aquarius.
+    /// # Arguments
+    /// * `id` - The identifier of the item to discard.
     /// # Returns
-    /// A handle to the thread that watches the thread that receives events from Aquarius.
-    fn start_watch_dog(&self, address: SocketAddr, timeout: u16, sender: Sender<AquariusEvent>) -> JoinHandle<()> {
-        let connection_mutex = self.connection.clone();
-        let shutdown = self.shutdown.clone();
+    /// The discarded item, or `None` if there is no such item.
+    /// # Errors
+    /// If the outbox could not be stored.
+    pub fn discard_outbox_item(&self, id: u64) -> Result<Option<OutboxItem>, AquariusErr> {
+        let client = self.client.clone();
+        self.block_on(async move { client.discard_outbox_item(id).await })
+    }
 
-        // Spawn a thread to watch the thread that receives events from Aquarius
-        let watch_dog: JoinHandle<()> = thread::spawn(move || {
-            // The interval to retry connecting to Aquarius in case of a failure
-            let repeat_interval = Duration::from_millis(timeout as u64);
+    /// Returns the current state of the connection to Aquarius.
+    pub fn state(&self) -> ConnectionState {
+        self.client.state()
+    }
 
-            // Loop until the client is shut down
-            while !shutdown.load(Relaxed) {
-                let start = Instant::now();
-                // create a new connection to Aquarius
-                match connect(&address, timeout) {
-                    Ok(connection) => {
-                        // Spawn a thread to receive events from Aquarius
-                        let event_thread_handle = spawn_event_thread(shutdown.clone(), connection, sender.clone());
-                        match connect(&address, timeout) {
-                            Ok(connection) => {
-                                *connection_mutex.lock().unwrap() = Some(connection);
-                                send_connection_status(&sender, true);
-                                // Wait for the thread to finish
-                                let _ = event_thread_handle.join().is_ok();
-                            }
-                            Err(err) => warn!(%err, "Error connecting to Aquarius:"),
-                        }
-                    }
-                    Err(err) => trace!(%err, "Error connecting to Aquarius:"),
-                }
-                let mut previous_connection = connection_mutex.lock().unwrap();
-                if previous_connection.is_some() {
-                    *previous_connection = None;
-                    send_connection_status(&sender, false);
-                    info!("Disconnected from Aquarius");
-                }
+    /// Returns a snapshot of the statistics of the connection to Aquarius.
+    pub fn stats(&self) -> ConnectionStats {
+        self.client.stats()
+    }
 
-                let elapsed = start.elapsed();
-                if elapsed < repeat_interval {
-                    thread::sleep(repeat_interval - elapsed);
-                }
-            } // end while
-            debug!("Stopped watch dog thread.");
-        });
+    /// Sets the text encoding of the link to Aquarius, Windows-1252 by default.
+    /// # Arguments
+    /// * `codec` - The text encoding used by Aquarius.
+    pub fn set_codec(&self, codec: Codec) {
+        self.client.set_codec(codec);
+    }
 
-        watch_dog
+    /// Sets the recorder for the lines sent to and received from Aquarius.
+    /// # Arguments
+    /// * `recorder` - The recorder to use, or `None` to stop recording.
+    pub fn set_recorder(&self, recorder: Option<Recorder>) {
+        self.client.set_recorder(recorder);
     }
 
-    /// Closes the connection to Aquarius and stops the watch dog thread.
+    /// Closes the connection to Aquarius and stops the watch dog task.
     pub fn shutdown(&self) {
-        self.shutdown.store(true, Relaxed);
-        self.with_connection(|connection| {
-            connection.disconnect();
+        let client = self.client.clone();
+        self.block_on(async move {
+            client.shutdown().await;
             Ok(())
         })
         .ok();
     }
 
-    fn read_start_list(comm: &mut Connection, heat: &mut Heat) -> Result<(), AquariusErr> {
-        comm.write(&RequestStartList::new(heat.id).to_string())?;
-        let response = comm.receive_all()?;
-        let start_list = response.parse::<ResponseStartList>()?;
-        heat.boats = Some(start_list.boats);
-        Ok(())
-    }
-
-    /// Helper method to execute a function with the connection to Aquarius. If the connection is not available, an error is returned.
-    fn with_connection<F, T>(&self, func: F) -> Result<T, AquariusErr>
+    /// Helper method to run a future on the runtime of the client and to block until it is completed.
+    fn block_on<F, T>(&self, future: F) -> Result<T, AquariusErr>
     where
-        F: Fn(&mut Connection) -> Result<T, AquariusErr>,
+        F: Future<Output = Result<T, AquariusErr>> + Send + 'static,
+        T: Send + 'static,
     {
-        match self.connection.lock() {
-            Ok(mut guard) => match guard.as_mut() {
-                Some(connection) => func(connection),
-                None => Err(AquariusErr::NotConnectedError()),
-            },
-            Err(_) => Err(AquariusErr::MutexPoisonError()),
+        match &self.runtime {
+            Some(runtime) => block_on(runtime, future),
+            None => Err(AquariusErr::NotConnectedError()),
         }
     }
 }
 
-fn connect(addr: &SocketAddr, timeout: u16) -> io::Result<Connection> {
-    trace!(%addr, timeout, "Connecting to:");
-    let stream = TcpStream::connect_timeout(addr, Duration::from_millis(timeout as u64))?;
-    stream.set_nodelay(true)?;
-    trace!(%addr, "Connected to:");
-    Connection::new(stream)
-}
-
-fn send_connection_status(sender: &Sender<AquariusEvent>, status: bool) {
-    // Send a message to the application that the client is connected
-    if let Err(err) = sender.send(AquariusEvent::Client(status)) {
-        error!(%err, "Error sending message to application:");
-    }
-}
-
-fn spawn_event_thread(
-    stop_watch_dog: Arc<AtomicBool>,
-    mut connection: Connection,
-    sender: Sender<AquariusEvent>,
-) -> JoinHandle<()> {
-    debug!("Starting thread to receive Aquarius events");
-    thread::spawn(move || {
-        while !stop_watch_dog.load(Relaxed) {
-            // Read a line from the server and blocks until a line is received.
-            match connection.receive_line() {
-                // successfully received a line
-                Ok(received) => {
-                    if !received.is_empty() && received.starts_with("!OPEN") {
-                        // Parse the received line and handle the event
-                        match received.parse::<EventHeatChanged>() {
-                            Ok(mut event) => {
-                                if event.opened {
-                                    AquariusClient::read_start_list(&mut connection, &mut event.heat).unwrap();
-                                }
-                                sender.send(AquariusEvent::HeatListChanged(event)).unwrap();
-                            }
-                            Err(err) => warn!(%err),
-                        }
-                    } else {
-                        debug!(line = utils::print_whitespaces(&received), "Ignoring:");
-                    }
-                }
-                // an error occurred while receiving a line
-                Err(err) => {
-                    warn!(%err);
-                    break;
-                }
-            }
-        }
-        debug!("Stopped thread to receive Aquarius events");
-    })
-}
-
 impl Drop for AquariusClient {
     fn drop(&mut self) {
         self.shutdown();
+        if let Some(runtime) = self.runtime.take() {
+            // don't wait for the tasks to finish, the client may be dropped within an async context
+            runtime.shutdown_background();
+        }
     }
 }
 
+/// Runs a future on the given runtime and blocks the current thread until it is completed. Unlike
+/// [`Runtime::block_on`], this can also be called from within another runtime.
+fn block_on<F, T>(runtime: &Runtime, future: F) -> Result<T, AquariusErr>
+where
+    F: Future<Output = Result<T, AquariusErr>> + Send + 'static,
+    T: Send + 'static,
+{
+    executor::block_on(runtime.spawn(future)).map_err(io::Error::from)?
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{
-        io::{BufRead, BufReader, Write},
-        net::{SocketAddr, TcpListener},
-        sync::mpsc::{self, Receiver},
-        thread,
-    };
-    use tracing::Level;
-    const TEST_MESSAGE: &str = "Hello World!";
-    const EXIT_COMMAND: &str = "exit";
-    const MESSAGE_END: &str = "\r\n";
+    use crate::simulator::{Scenario, Simulator};
+    use ::std::sync::mpsc::{self, Receiver};
+    use ::std::time::Duration;
+    use ::tracing::Level;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+    const SCENARIO: &str = "
+        HEAT 3 2766 4
+        BOAT 2766 1 1 0 'RV Neptun Konstanz'
+        BOAT 2766 2 2 0 'RG Heidelberg'
+        OPEN 2766
+    ";
 
-    fn init_client() -> (AquariusClient, Receiver<AquariusEvent>) {
+    fn init_client() -> (AquariusClient, Receiver<AquariusEvent>, Simulator, Runtime) {
         let _ = tracing_subscriber::fmt()
             .with_max_level(Level::TRACE)
             .with_test_writer()
             .try_init();
+        let runtime = Runtime::new().unwrap();
+        let simulator = runtime
+            .block_on(Simulator::start("127.0.0.1:0", SCENARIO.parse::<Scenario>().unwrap()))
+            .unwrap();
         let (sender, receiver) = mpsc::channel();
-        let addr = start_test_server();
-        let client = AquariusClient::new(&addr.ip().to_string(), addr.port(), 1, sender).unwrap();
-        (client, receiver)
+        let addr = simulator.local_addr();
+        let client = AquariusClient::new(&addr.ip().to_string(), addr.port(), 100, sender).unwrap();
+        (client, receiver, simulator, runtime)
     }
 
-    fn start_test_server() -> SocketAddr {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
-
-        thread::spawn(move || {
-            for incoming in listener.incoming() {
-                let mut stream = incoming.unwrap();
-                let mut reader = BufReader::new(stream.try_clone().unwrap());
-                let mut buffer = String::new();
-                while reader.read_line(&mut buffer).unwrap() > 0 {
-                    if buffer.trim() == EXIT_COMMAND {
-                        break;
-                    }
-                    stream.write_all(buffer.as_bytes()).unwrap();
-                    buffer.clear();
-                }
-            }
-            info!("Exiting test server.");
-        });
-        addr
+    fn wait_connected(receiver: &Receiver<AquariusEvent>) {
+        while !matches!(
+            receiver.recv_timeout(TEST_TIMEOUT).unwrap(),
+            AquariusEvent::Client(ConnectionState::Connected)
+        ) {}
     }
 
     #[test]
     fn test_client_connection() {
-        let (client, receiver) = init_client();
-        receiver.recv().unwrap(); // wait until connected
-        assert!(client.connection.lock().unwrap().is_some());
+        let (client, receiver, _simulator, _runtime) = init_client();
+        wait_connected(&receiver);
+        assert_eq!(client.state(), ConnectionState::Connected);
+        assert_eq!(client.stats().connect_attempts, 1);
     }
 
     #[test]
-    fn test_client_write() {
-        let (client, receiver) = init_client();
-        receiver.recv().unwrap(); // wait until connected
-        let mut binding = client.connection.lock().unwrap();
-        let connection = binding.as_mut().unwrap();
-        let result = connection.write(TEST_MESSAGE);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), TEST_MESSAGE.len());
+    fn test_client_read_open_heats() {
+        let (client, receiver, _simulator, _runtime) = init_client();
+        // wait until the scenario has opened the heat
+        loop {
+            if let AquariusEvent::HeatListChanged(event) = receiver.recv_timeout(TEST_TIMEOUT).unwrap() {
+                assert!(event.opened);
+                assert_eq!(event.heat.boats.unwrap().len(), 2);
+                break;
+            }
+        }
+
+        let heats = client.read_open_heats().unwrap();
+        assert_eq!(heats.len(), 1);
+        assert_eq!(heats[0].id, 2766);
+        assert_eq!(heats[0].boats.as_ref().unwrap()[1].club, "RG Heidelberg");
     }
 
     #[test]
-    #[ignore]
-    fn test_client_receive_line() {
-        let (client, receiver) = init_client();
-        receiver.recv().unwrap(); // wait until connected
-        let mut binding = client.connection.lock().unwrap();
-        let connection = binding.as_mut().unwrap();
-        connection.write(TEST_MESSAGE).unwrap();
-        connection.write(MESSAGE_END).unwrap();
-        let response = connection.receive_line();
-        assert!(response.is_ok());
-        assert_eq!(response.unwrap(), TEST_MESSAGE);
+    fn test_client_shutdown() {
+        let (client, receiver, _simulator, _runtime) = init_client();
+        wait_connected(&receiver);
 
-        connection.write(EXIT_COMMAND).unwrap();
+        client.shutdown();
+        let disconnected = receiver
+            .iter()
+            .any(|event| matches!(event, AquariusEvent::Client(ConnectionState::Disconnected { .. })));
+        assert!(disconnected);
     }
 
-    #[test]
-    #[ignore]
-    fn test_client_receive_all() {
-        let (client, receiver) = init_client();
-        receiver.recv().unwrap(); // wait until connected
-        let mut binding = client.connection.lock().unwrap();
-        let comm = binding.as_mut().unwrap();
-        comm.write("Hello World!\n").unwrap();
-        comm.write("This is a test.\n").unwrap();
-        comm.write(MESSAGE_END).unwrap();
-        let response = comm.receive_all();
-        assert!(response.is_ok());
-        assert_eq!(response.unwrap(), "Hello World!\nThis is a test.");
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_client_within_runtime() {
+        let simulator = Simulator::start("127.0.0.1:0", Scenario::default()).await.unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let addr = simulator.local_addr();
+        let client = AquariusClient::new(&addr.ip().to_string(), addr.port(), 100, sender).unwrap();
+        wait_connected(&receiver);
 
-        comm.write(EXIT_COMMAND).unwrap();
+        assert!(client.read_open_heats().unwrap().is_empty());
+        drop(client);
     }
 }
@@ -0,0 +1,88 @@
+use ::encoding_rs::{UTF_8, WINDOWS_1252};
+use ::std::borrow::Cow;
+use ::std::str::FromStr;
+
+/// The text encoding used on the link to Aquarius. Aquarius uses Windows-1252, newer installations may be configured
+/// to use UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(::clap::ValueEnum))]
+pub enum Codec {
+    /// The Windows-1252 encoding used by Aquarius by default.
+    #[default]
+    Windows1252,
+
+    /// The UTF-8 encoding.
+    Utf8,
+}
+
+impl Codec {
+    /// Decodes bytes received from Aquarius. Invalid sequences are replaced by the replacement character.
+    /// # Arguments
+    /// * `bytes` - The bytes to decode.
+    /// # Returns
+    /// The decoded text.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        match self {
+            Codec::Windows1252 => WINDOWS_1252.decode(bytes).0,
+            Codec::Utf8 => UTF_8.decode(bytes).0,
+        }
+    }
+
+    /// Encodes text to send it to Aquarius. Characters that can't be encoded are replaced by numeric character
+    /// references.
+    /// # Arguments
+    /// * `text` - The text to encode.
+    /// # Returns
+    /// The encoded bytes.
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        match self {
+            Codec::Windows1252 => WINDOWS_1252.encode(text).0,
+            Codec::Utf8 => Cow::Borrowed(text.as_bytes()),
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    /// Parses a codec from its name, e.g. `windows1252` or `utf8`. Case is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "windows1252" => Ok(Codec::Windows1252),
+            "utf8" => Ok(Codec::Utf8),
+            _ => Err(format!("invalid codec: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_round_trip() {
+        let club = "Ruderclub Lübeck";
+        let encoded = Codec::Windows1252.encode(club);
+        assert_eq!(encoded.len(), club.chars().count());
+        assert!(encoded.contains(&0xFC));
+        assert_eq!(Codec::Windows1252.decode(&encoded), club);
+
+        let encoded = Codec::Utf8.encode(club);
+        assert_eq!(encoded.as_ref(), club.as_bytes());
+        assert_eq!(Codec::Utf8.decode(&encoded), club);
+    }
+
+    #[test]
+    fn test_codec_from_str() {
+        assert_eq!("windows1252".parse::<Codec>().unwrap(), Codec::Windows1252);
+        assert_eq!("UTF8".parse::<Codec>().unwrap(), Codec::Utf8);
+        assert!("latin1".parse::<Codec>().is_err());
+    }
+
+    #[test]
+    fn test_codec_mismatch() {
+        // UTF-8 decoded as Windows-1252 yields mojibake, but never fails
+        assert_eq!(Codec::Windows1252.decode("ü".as_bytes()), "Ã¼");
+        assert_eq!(Codec::Utf8.decode(&[0xFC]), "\u{FFFD}");
+    }
+}
@@ -1,311 +1,279 @@
+use crate::codec::Codec;
+use crate::recorder::{ConnectionRecorder, Direction};
 use crate::utils;
-use ::encoding_rs::WINDOWS_1252;
 use ::std::io;
-use ::std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
-use ::std::net::Shutdown;
-use ::std::net::TcpStream;
+use ::std::io::ErrorKind;
+use ::std::sync::{Arc, Mutex as StdMutex};
+use ::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use ::tokio::net::TcpStream;
+use ::tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use ::tracing::trace;
 
-/// A struct to handle a connection to the Aquarius application.
-pub(super) struct Connection {
+/// Splits a TCP stream to Aquarius into a reading and a writing half. Both halves can be used independently, e.g. to
+/// receive events while a request is written.
+/// # Arguments
+/// * `stream` - The TCP stream to communicate with Aquarius.
+/// * `codec` - The text encoding of the connection. It can be changed while the connection is used.
+/// * `recorder` - Records the lines sent and received on the connection.
+/// # Returns
+/// The reading and writing half of the connection.
+pub(super) fn split(
+    stream: TcpStream,
+    codec: Arc<StdMutex<Codec>>,
+    recorder: ConnectionRecorder,
+) -> (ConnectionReader, ConnectionWriter) {
+    let (read_half, write_half) = stream.into_split();
+    let reader = ConnectionReader {
+        reader: BufReader::new(read_half),
+        codec: codec.clone(),
+        recorder: recorder.clone(),
+    };
+    let writer = ConnectionWriter {
+        writer: BufWriter::new(write_half),
+        codec,
+        recorder,
+    };
+    (reader, writer)
+}
+
+/// The reading half of a connection to the Aquarius application.
+pub(super) struct ConnectionReader {
     /// A buffered reader to read from the Aquarius application.
-    reader: BufReader<TcpStream>,
+    reader: BufReader<OwnedReadHalf>,
 
-    /// A buffered writer to write to the Aquarius application.
-    writer: BufWriter<TcpStream>,
+    /// The text encoding of the connection.
+    codec: Arc<StdMutex<Codec>>,
 
-    /// The underlying TCP stream for the connection.
-    stream: TcpStream,
+    /// Records the received lines.
+    recorder: ConnectionRecorder,
 }
 
-impl Connection {
-    /// Create a new `Connection` struct.
-    /// # Arguments
-    /// * `stream` - The TCP stream to communicate with Aquarius.
+impl ConnectionReader {
+    /// Receive a single line from Aquarius.
     /// # Returns
-    /// A new `Connection` struct.
+    /// The line received from Aquarius without trailing whitespaces. An empty line terminates a reply of Aquarius.
     /// # Errors
-    /// An error if the stream cannot be cloned.
-    pub(super) fn new(stream: TcpStream) -> io::Result<Self> {
-        let reader = BufReader::new(stream.try_clone()?);
-        let writer = BufWriter::new(stream.try_clone()?);
-        Ok(Connection { reader, writer, stream })
-    }
+    /// An error if the connection is closed or an error occurs while reading.
+    pub(super) async fn receive_line(&mut self) -> io::Result<String> {
+        let mut buf = Vec::new();
 
-    /// Closes the connection to Aquarius.
-    /// # Returns
-    /// An empty result or an error if the connection could not be closed.
-    pub(super) fn disconnect(&mut self) {
-        trace!("Disconnecting from Aquarius");
-        // ignore any error from flushing, since we want to close the connection anyway.
-        let _ = self.writer.flush();
-        // ignore any error from shutting down, since we want to close the connection anyway.
-        let _ = self.stream.shutdown(Shutdown::Both);
+        // Read until a newline character is found and wait until data is available.
+        let count = self.reader.read_until(b'\n', &mut buf).await?;
+        // If no data is read, the connection is closed.
+        if count == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Connection closed"));
+        }
+        // Decode the buffer to a string with the encoding used by Aquarius.
+        let line = codec(&self.codec).decode(&buf);
+        trace!(line = utils::print_whitespaces(&line), count, "Received line:");
+        self.recorder.record(Direction::In, &line);
+        Ok(line.trim_end().to_string())
     }
+}
+
+/// The writing half of a connection to the Aquarius application.
+pub(super) struct ConnectionWriter {
+    /// A buffered writer to write to the Aquarius application.
+    writer: BufWriter<OwnedWriteHalf>,
+
+    /// The text encoding of the connection.
+    codec: Arc<StdMutex<Codec>>,
+
+    /// Records the written commands.
+    recorder: ConnectionRecorder,
+}
 
+impl ConnectionWriter {
     /// Write a command to Aquarius.
     /// # Arguments
     /// * `cmd` - The command to write.
     /// # Returns
     /// The number of bytes written or an error if the command could not be written.
-    pub(super) fn write(&mut self, cmd: &str) -> io::Result<usize> {
+    pub(super) async fn write(&mut self, cmd: &str) -> io::Result<usize> {
         trace!(cmd = utils::print_whitespaces(cmd), "Writing command:");
-        let count = self.writer.write(cmd.as_bytes())?;
-        self.writer.flush()?;
+        self.writer.write_all(&codec(&self.codec).encode(cmd)).await?;
+        self.writer.flush().await?;
+        self.recorder.record(Direction::Out, cmd);
+        let count = cmd.len();
         trace!(count, "Written bytes:");
         Ok(count)
     }
 
-    /// Receive a single line from Aquarius.
-    /// # Returns
-    /// The line received from Aquarius or an error if the line could not be read.
-    /// # Errors
-    /// An error if the connection is closed or an error occurs while reading.
-    pub(super) fn receive_line(&mut self) -> io::Result<String> {
-        let mut line = String::new();
-
-        // Read a line from Aquarius and blocks until data is available.
-        match self.reader.read_line(&mut line) {
-            Ok(count) => {
-                // If no data is read, the connection is closed.
-                if count == 0 {
-                    Err(io::Error::new(ErrorKind::UnexpectedEof, "Connection closed"))
-                } else {
-                    trace!(line = utils::print_whitespaces(&line), count, "Received line:");
-                    Ok(line.trim_end().to_string())
-                }
-            }
-            Err(err) => Err(err),
-        }
-    }
-
-    /// Receive all data from Aquarius until a newline character is found.
-    /// # Returns
-    /// The data received from Aquarius or an error if the data could not be read.
-    /// # Errors
-    /// An error if the connection is closed or an error occurs while reading.
-    pub(super) fn receive_all(&mut self) -> io::Result<String> {
-        let mut result = String::new();
-        let mut buf = Vec::new();
-        loop {
-            // Read until a newline character is found and blocks until data is available.
-            let count = self.reader.read_until(b'\n', &mut buf)?;
-            if count == 0 {
-                // If no data is read, the connection is closed.
-                return Err(io::Error::new(ErrorKind::UnexpectedEof, "Connection closed"));
-            } else {
-                // Decode the buffer to a string. Aquarius uses Windows-1252 encoding.
-                let line = WINDOWS_1252.decode(&buf).0;
-                trace!(line = utils::print_whitespaces(&line), count, "Received line:");
-                // If the line is empty, break the loop. Aquarius sends \r\n at the end of the message.
-                if count <= 2 {
-                    break;
-                }
-                // Append the line to the result string.
-                result.push_str(&line);
-                buf.clear();
-            }
-        }
-        trace!(
-            msg = utils::print_whitespaces(&result),
-            len = result.len(),
-            "Received message:",
-        );
-        Ok(result.trim_end().to_string())
+    /// Closes the connection to Aquarius.
+    pub(super) async fn disconnect(&mut self) {
+        trace!("Disconnecting from Aquarius");
+        // ignore any error from flushing, since we want to close the connection anyway.
+        let _ = self.writer.flush().await;
+        // ignore any error from shutting down, since we want to close the connection anyway.
+        let _ = self.writer.shutdown().await;
     }
 }
 
-impl Drop for Connection {
-    fn drop(&mut self) {
-        self.disconnect();
-    }
+/// Returns the current text encoding of a connection.
+fn codec(codec: &StdMutex<Codec>) -> Codec {
+    codec.lock().map(|codec| *codec).unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::{Read, Write};
-    use std::net::TcpListener;
+    use ::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use ::tokio::net::TcpListener;
 
-    /// Helper: start a TcpListener on a random port and return it together with
-    /// a `Connection` that is connected to it.
-    fn setup() -> (TcpListener, Connection) {
-        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-        let addr = listener.local_addr().unwrap();
-        let stream = TcpStream::connect(addr).unwrap();
-        let conn = Connection::new(stream).unwrap();
-        (listener, conn)
+    /// Helper: start a TcpListener on a random port and return the accepted server stream together with
+    /// both halves of a connection that is connected to it.
+    async fn setup() -> (TcpStream, ConnectionReader, ConnectionWriter) {
+        setup_with_codec(Codec::default()).await
     }
 
-    // ── new ──────────────────────────────────────────────────────────────
-
-    #[test]
-    fn new_creates_connection() {
-        let (listener, _conn) = setup();
-        // Accept the client to prove the connection was established.
-        let (_server_stream, _addr) = listener.accept().unwrap();
+    async fn setup_with_codec(codec: Codec) -> (TcpStream, ConnectionReader, ConnectionWriter) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let (reader, writer) = split(stream, Arc::new(StdMutex::new(codec)), ConnectionRecorder::default());
+        (server, reader, writer)
     }
 
     // ── write ────────────────────────────────────────────────────────────
 
-    #[test]
-    fn write_sends_bytes_to_server() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn write_sends_bytes_to_server() {
+        let (mut server, _reader, mut writer) = setup().await;
 
         let msg = "HELLO\r\n";
-        let count = conn.write(msg).unwrap();
+        let count = writer.write(msg).await.unwrap();
         assert_eq!(count, msg.len());
 
         let mut buf = vec![0u8; 64];
-        let n = server.read(&mut buf).unwrap();
+        let n = server.read(&mut buf).await.unwrap();
         assert_eq!(&buf[..n], msg.as_bytes());
     }
 
-    #[test]
-    fn write_returns_correct_byte_count() {
-        let (listener, mut conn) = setup();
-        let (_server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn write_returns_correct_byte_count() {
+        let (_server, _reader, mut writer) = setup().await;
 
-        let msg = "CMD";
-        let count = conn.write(msg).unwrap();
+        let count = writer.write("CMD").await.unwrap();
         assert_eq!(count, 3);
     }
 
     // ── receive_line ─────────────────────────────────────────────────────
 
-    #[test]
-    fn receive_line_reads_single_line() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn receive_line_reads_single_line() {
+        let (mut server, mut reader, _writer) = setup().await;
 
-        server.write_all(b"Hello World\n").unwrap();
-        server.flush().unwrap();
+        server.write_all(b"Hello World\n").await.unwrap();
 
-        let line = conn.receive_line().unwrap();
+        let line = reader.receive_line().await.unwrap();
         assert_eq!(line, "Hello World");
     }
 
-    #[test]
-    fn receive_line_trims_crlf() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn receive_line_trims_crlf() {
+        let (mut server, mut reader, _writer) = setup().await;
 
-        server.write_all(b"data\r\n").unwrap();
-        server.flush().unwrap();
+        server.write_all(b"data\r\n").await.unwrap();
 
-        let line = conn.receive_line().unwrap();
+        let line = reader.receive_line().await.unwrap();
         assert_eq!(line, "data");
     }
 
-    #[test]
-    fn receive_line_returns_error_on_closed_connection() {
-        let (listener, mut conn) = setup();
-        let (server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn receive_line_returns_empty_line_for_terminator() {
+        let (mut server, mut reader, _writer) = setup().await;
 
-        // Close the server side so the client sees EOF.
-        drop(server);
+        // Simulate Aquarius protocol: content lines followed by an empty \r\n terminator.
+        server.write_all(b"line1\r\nline2\r\n\r\n").await.unwrap();
 
-        let err = conn.receive_line().unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(reader.receive_line().await.unwrap(), "line1");
+        assert_eq!(reader.receive_line().await.unwrap(), "line2");
+        assert_eq!(reader.receive_line().await.unwrap(), "");
     }
 
-    // ── receive_all ──────────────────────────────────────────────────────
+    #[tokio::test]
+    async fn receive_line_decodes_windows_1252() {
+        let (mut server, mut reader, _writer) = setup().await;
 
-    #[test]
-    fn receive_all_reads_multi_line_message() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+        // ä in Windows-1252 is 0xE4, ö is 0xF6, ü is 0xFC
+        server.write_all(&[0xE4, 0xF6, 0xFC, b'\r', b'\n']).await.unwrap();
 
-        // Simulate Aquarius protocol: content lines followed by an empty \r\n terminator.
-        server.write_all(b"line1\r\n").unwrap();
-        server.write_all(b"line2\r\n").unwrap();
-        server.write_all(b"\r\n").unwrap(); // terminator (<=2 bytes after \n split)
-        server.flush().unwrap();
-
-        let result = conn.receive_all().unwrap();
-        // Both content lines are concatenated (with their \r\n), then trim_end is applied.
-        assert!(result.contains("line1"));
-        assert!(result.contains("line2"));
+        let line = reader.receive_line().await.unwrap();
+        assert_eq!(line, "äöü");
     }
 
-    #[test]
-    fn receive_all_returns_error_on_closed_connection() {
-        let (listener, mut conn) = setup();
-        let (server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn receive_line_decodes_utf_8() {
+        let (mut server, mut reader, _writer) = setup_with_codec(Codec::Utf8).await;
 
-        // Close server so client gets EOF immediately.
-        drop(server);
+        server
+            .write_all("1 5 0 'Ruderclub Lübeck'\r\n".as_bytes())
+            .await
+            .unwrap();
 
-        let err = conn.receive_all().unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let line = reader.receive_line().await.unwrap();
+        assert_eq!(line, "1 5 0 'Ruderclub Lübeck'");
     }
 
-    #[test]
-    fn receive_all_handles_single_line_before_terminator() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
-
-        server.write_all(b"only-line\r\n").unwrap();
-        server.write_all(b"\r\n").unwrap();
-        server.flush().unwrap();
+    #[tokio::test]
+    async fn write_encodes_with_codec() {
+        let (mut server, _reader, mut writer) = setup().await;
+        writer.write("ü\n").await.unwrap();
+        let mut buf = vec![0u8; 64];
+        let n = server.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], &[0xFC, b'\n']);
 
-        let result = conn.receive_all().unwrap();
-        assert_eq!(result, "only-line");
+        let (mut server, _reader, mut writer) = setup_with_codec(Codec::Utf8).await;
+        writer.write("ü\n").await.unwrap();
+        let n = server.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], "ü\n".as_bytes());
     }
 
-    #[test]
-    fn receive_all_decodes_windows_1252() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn receive_line_returns_error_on_closed_connection() {
+        let (server, mut reader, _writer) = setup().await;
 
-        // ä in Windows-1252 is 0xE4, ö is 0xF6, ü is 0xFC
-        let mut payload: Vec<u8> = vec![0xE4, 0xF6, 0xFC]; // äöü
-        payload.extend_from_slice(b"\r\n");
-        server.write_all(&payload).unwrap();
-        server.write_all(b"\r\n").unwrap();
-        server.flush().unwrap();
-
-        let result = conn.receive_all().unwrap();
-        assert_eq!(result, "äöü");
+        // Close the server side so the client sees EOF.
+        drop(server);
+
+        let err = reader.receive_line().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
     }
 
     // ── disconnect ───────────────────────────────────────────────────────
 
-    #[test]
-    fn disconnect_shuts_down_connection() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn disconnect_shuts_down_connection() {
+        let (mut server, _reader, mut writer) = setup().await;
 
-        conn.disconnect();
+        writer.disconnect().await;
 
         // After disconnect the server should see EOF.
         let mut buf = vec![0u8; 64];
-        let n = server.read(&mut buf).unwrap();
+        let n = server.read(&mut buf).await.unwrap();
         assert_eq!(n, 0);
     }
 
     // ── round-trip ───────────────────────────────────────────────────────
 
-    #[test]
-    fn write_then_receive_line_round_trip() {
-        let (listener, mut conn) = setup();
-        let (mut server, _) = listener.accept().unwrap();
+    #[tokio::test]
+    async fn write_then_receive_line_round_trip() {
+        let (mut server, mut reader, mut writer) = setup().await;
 
         // Client writes a command.
-        conn.write("REQUEST\r\n").unwrap();
+        writer.write("REQUEST\r\n").await.unwrap();
 
         // Server reads the command.
         let mut cmd_buf = vec![0u8; 64];
-        let n = server.read(&mut cmd_buf).unwrap();
+        let n = server.read(&mut cmd_buf).await.unwrap();
         assert_eq!(&cmd_buf[..n], b"REQUEST\r\n");
 
         // Server sends a response.
-        server.write_all(b"RESPONSE\r\n").unwrap();
-        server.flush().unwrap();
+        server.write_all(b"RESPONSE\r\n").await.unwrap();
 
         // Client reads the response.
-        let response = conn.receive_line().unwrap();
+        let response = reader.receive_line().await.unwrap();
         assert_eq!(response, "RESPONSE");
     }
 }
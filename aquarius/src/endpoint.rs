@@ -0,0 +1,102 @@
+use crate::error::AquariusErr;
+use ::serde::Serialize;
+use ::std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The address of an Aquarius application, e.g. the primary or the backup Aquarius PC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Endpoint {
+    /// The host name or IP address. The host name is resolved on each attempt to connect.
+    pub host: String,
+
+    /// The port Aquarius is listening on.
+    pub port: u16,
+}
+
+impl Endpoint {
+    /// Creates a new endpoint.
+    /// # Arguments
+    /// * `host` - The host name or IP address.
+    /// * `port` - The port Aquarius is listening on.
+    pub fn new(host: &str, port: u16) -> Self {
+        Endpoint {
+            host: host.to_owned(),
+            port,
+        }
+    }
+
+    /// Parses a comma separated list of endpoints, e.g. `aquarius,aquarius-backup:2049`. Endpoints without a port use
+    /// the given default port, IPv6 addresses with a port have to be enclosed in brackets, e.g. `[::1]:2048`.
+    /// # Arguments
+    /// * `list` - The endpoints to parse.
+    /// * `default_port` - The port of endpoints without a port.
+    /// # Returns
+    /// The endpoints in the given order, the first one is the primary endpoint.
+    /// # Errors
+    /// If a port is invalid or the list contains no endpoint.
+    pub fn parse_list(list: &str, default_port: u16) -> Result<Vec<Self>, AquariusErr> {
+        let endpoints = list
+            .split(',')
+            .map(str::trim)
+            .filter(|endpoint| !endpoint.is_empty())
+            .map(|endpoint| Self::parse(endpoint, default_port))
+            .collect::<Result<Vec<_>, _>>()?;
+        if endpoints.is_empty() {
+            return Err(AquariusErr::NoEndpointError());
+        }
+        Ok(endpoints)
+    }
+
+    fn parse(endpoint: &str, default_port: u16) -> Result<Self, AquariusErr> {
+        match endpoint.rsplit_once(':') {
+            // a plain IPv6 address without port contains several colons
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => Ok(Endpoint::new(
+                host.trim_start_matches('[').trim_end_matches(']'),
+                port.parse()?,
+            )),
+            _ => Ok(Endpoint::new(endpoint, default_port)),
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let endpoints = Endpoint::parse_list("aquarius, aquarius-backup:2049,[::1]:2050,::1", 2048).unwrap();
+        assert_eq!(
+            endpoints,
+            vec![
+                Endpoint::new("aquarius", 2048),
+                Endpoint::new("aquarius-backup", 2049),
+                Endpoint::new("::1", 2050),
+                Endpoint::new("::1", 2048),
+            ]
+        );
+        assert_eq!(endpoints[1].to_string(), "aquarius-backup:2049");
+        assert_eq!(endpoints[2].to_string(), "[::1]:2050");
+    }
+
+    #[test]
+    fn test_parse_list_errors() {
+        assert!(matches!(
+            Endpoint::parse_list(" , ", 2048),
+            Err(AquariusErr::NoEndpointError())
+        ));
+        assert!(matches!(
+            Endpoint::parse_list("aquarius:port", 2048),
+            Err(AquariusErr::ParseError(_))
+        ));
+    }
+}
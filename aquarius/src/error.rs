@@ -1,5 +1,6 @@
 use crate::event::AquariusEvent;
 use ::std::io;
+use ::std::time::Duration;
 use ::std::{
     num::ParseIntError,
     sync::mpsc::{RecvError, SendError},
@@ -17,6 +18,10 @@ pub enum AquariusErr {
     #[error("I/O error: {0}")]
     IoError(#[from] io::Error),
 
+    /// Error when serializing or deserializing JSON fails.
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     /// Error when the message is invalid.
     #[error("Invalid message: {0}")]
     InvalidMessage(String),
@@ -36,28 +41,52 @@ pub enum AquariusErr {
     /// Error when not connected to the server.
     #[error("Not connected to Aquarius server")]
     NotConnectedError(),
+
+    /// Error when no endpoint of Aquarius is configured.
+    #[error("No Aquarius endpoint configured")]
+    NoEndpointError(),
+
+    /// Error when Aquarius rejected a command.
+    #[error("Rejected by Aquarius: {reason}")]
+    Rejected {
+        /// The reason given by Aquarius.
+        reason: String,
+    },
+
+    /// Error when Aquarius did not answer a request in time.
+    #[error("No answer from Aquarius within {0:?}")]
+    TimeoutError(Duration),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::status::ConnectionState;
 
     #[test]
     fn test_message_err() {
         let parse_error = AquariusErr::ParseError("error".parse::<i32>().unwrap_err());
         let io_error = AquariusErr::IoError(io::Error::other("error"));
+        let json_error = AquariusErr::from(serde_json::from_str::<u8>("error").unwrap_err());
         let invalid_message = AquariusErr::InvalidMessage("error".to_string());
-        let send_error = AquariusErr::SendError(SendError(AquariusEvent::Client(true)));
+        let send_error = AquariusErr::SendError(SendError(AquariusEvent::Client(ConnectionState::Connected)));
         let recv_error = AquariusErr::ReceiveError(RecvError);
         let mutex_poison_error = AquariusErr::MutexPoisonError();
         let not_connected_error = AquariusErr::NotConnectedError();
+        let rejected = AquariusErr::Rejected {
+            reason: "Unknown heat".to_owned(),
+        };
+        let timeout_error = AquariusErr::TimeoutError(Duration::from_secs(1));
 
         assert!(matches!(parse_error, AquariusErr::ParseError(_)));
         assert!(matches!(io_error, AquariusErr::IoError(_)));
+        assert!(matches!(json_error, AquariusErr::JsonError(_)));
         assert!(matches!(invalid_message, AquariusErr::InvalidMessage { .. }));
         assert!(matches!(send_error, AquariusErr::SendError(_)));
         assert!(matches!(recv_error, AquariusErr::ReceiveError(_)));
         assert!(matches!(mutex_poison_error, AquariusErr::MutexPoisonError()));
         assert!(matches!(not_connected_error, AquariusErr::NotConnectedError()));
+        assert_eq!(rejected.to_string(), "Rejected by Aquarius: Unknown heat");
+        assert!(matches!(timeout_error, AquariusErr::TimeoutError(_)));
     }
 }
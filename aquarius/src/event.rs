@@ -1,11 +1,108 @@
-use crate::messages::EventHeatChanged;
+use crate::error::AquariusErr;
+use crate::messages::{EventHeatChanged, EventHeatStateChanged, EventResultUpdated, EventTimeConfirmed};
+use crate::outbox::OutboxItem;
+use crate::status::ConnectionState;
+use ::serde::Serialize;
+use ::std::str::FromStr;
 
 /// Events emitted by the Aquarius client.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum AquariusEvent {
     /// An event indicating that the list of heats has changed
     HeatListChanged(EventHeatChanged),
 
-    /// An event from the client, e.g. connected or connection lost
-    Client(bool),
+    /// An event indicating that Aquarius has confirmed a time
+    TimeConfirmed(EventTimeConfirmed),
+
+    /// An event indicating that the state of a heat has changed
+    HeatStateChanged(EventHeatStateChanged),
+
+    /// An event indicating that the result of a boat has changed
+    ResultUpdated(EventResultUpdated),
+
+    /// An unsolicited message from Aquarius the client does not know
+    Unknown(String),
+
+    /// An event from the client indicating that the state of the connection to Aquarius has changed
+    Client(ConnectionState),
+
+    /// An event from the client indicating that Aquarius rejected a time sent from the outbox, e.g. because the heat
+    /// was closed in the meantime. The time is missing in Aquarius and has to be entered manually.
+    QueuedTimeRejected {
+        /// The rejected time, which was removed from the outbox.
+        item: OutboxItem,
+        /// The reason given by Aquarius.
+        reason: String,
+    },
+}
+
+impl FromStr for AquariusEvent {
+    type Err = AquariusErr;
+
+    /// Parses an unsolicited message received from Aquarius. Messages with an unknown keyword are returned as
+    /// [`AquariusEvent::Unknown`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keyword = s.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "!OPEN+" | "!OPEN-" => Ok(AquariusEvent::HeatListChanged(s.parse()?)),
+            "!TIME" => Ok(AquariusEvent::TimeConfirmed(s.parse()?)),
+            "!STATE" => Ok(AquariusEvent::HeatStateChanged(s.parse()?)),
+            "!RESULT" => Ok(AquariusEvent::ResultUpdated(s.parse()?)),
+            _ => Ok(AquariusEvent::Unknown(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::chrono::NaiveTime;
+    use ::db::aquarius::model::HeatState;
+    use ::db::timekeeper::Split;
+
+    #[test]
+    fn test_parse_events() {
+        match "!OPEN+ 3 2766 4".parse::<AquariusEvent>().unwrap() {
+            AquariusEvent::HeatListChanged(event) => assert!(event.opened && event.heat.id == 2766),
+            event => panic!("Unexpected event: {event:?}"),
+        }
+        match "!TIME time=10:15:30.123 comp=17 split=64 bib=3"
+            .parse::<AquariusEvent>()
+            .unwrap()
+        {
+            AquariusEvent::TimeConfirmed(event) => {
+                assert_eq!(event.time, NaiveTime::from_hms_milli_opt(10, 15, 30, 123).unwrap());
+                assert_eq!(event.heat_nr, 17);
+                assert_eq!(event.split, Split::Finish);
+                assert_eq!(event.bib, Some(3));
+            }
+            event => panic!("Unexpected event: {event:?}"),
+        }
+        match "!STATE 3 2766 4".parse::<AquariusEvent>().unwrap() {
+            AquariusEvent::HeatStateChanged(event) => {
+                assert_eq!(event.heat.id, 2766);
+                assert_eq!(event.heat.state, HeatState::Official);
+            }
+            event => panic!("Unexpected event: {event:?}"),
+        }
+        match "!RESULT 2766 3 0 DNF".parse::<AquariusEvent>().unwrap() {
+            AquariusEvent::ResultUpdated(event) => {
+                assert_eq!((event.heat_id, event.bib, event.rank), (2766, 3, None));
+                assert_eq!(event.result, "DNF");
+            }
+            event => panic!("Unexpected event: {event:?}"),
+        }
+        match "!PING 42".parse::<AquariusEvent>().unwrap() {
+            AquariusEvent::Unknown(line) => assert_eq!(line, "!PING 42"),
+            event => panic!("Unexpected event: {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_events() {
+        assert!("!TIME comp=17 split=64".parse::<AquariusEvent>().is_err());
+        assert!("!TIME time=10:15 comp=17 split=64".parse::<AquariusEvent>().is_err());
+        assert!("!STATE 3 2766".parse::<AquariusEvent>().is_err());
+        assert!("!RESULT 2766 3 1".parse::<AquariusEvent>().is_err());
+    }
 }
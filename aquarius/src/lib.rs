@@ -1,6 +1,13 @@
+pub mod async_client;
 pub mod client;
+pub mod codec;
 mod connection;
+pub mod endpoint;
 pub mod error;
 pub mod event;
 pub mod messages;
+pub mod outbox;
+pub mod recorder;
+pub mod simulator;
+pub mod status;
 mod utils;
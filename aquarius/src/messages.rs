@@ -1,6 +1,7 @@
 use crate::error::AquariusErr;
 use crate::utils;
-use ::chrono::{DateTime, Local};
+use ::chrono::{DateTime, Local, NaiveTime};
+use ::db::aquarius::model::{BoatState, HeatState};
 use ::db::timekeeper::Split;
 use ::serde::Serialize;
 use ::std::fmt::{Display, Formatter, Result as FmtResult};
@@ -100,8 +101,39 @@ impl Display for RequestSetTime {
     }
 }
 
+/// A message to respond to a `TIME` command. Aquarius answers with `OK` if it accepted the time, or with `ERR`
+/// followed by the reason if it rejected the time, e.g. because the heat does not exist or is already closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseSetTime {
+    /// The time was accepted by Aquarius.
+    Accepted,
+
+    /// The time was rejected by Aquarius.
+    Rejected {
+        /// The reason given by Aquarius.
+        reason: String,
+    },
+}
+
+impl FromStr for ResponseSetTime {
+    type Err = AquariusErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = s.lines().next().unwrap_or_default().trim();
+        if line == "OK" {
+            Ok(ResponseSetTime::Accepted)
+        } else if let Some(reason) = line.strip_prefix("ERR") {
+            Ok(ResponseSetTime::Rejected {
+                reason: reason.trim().to_owned(),
+            })
+        } else {
+            Err(AquariusErr::InvalidMessage(s.to_owned()))
+        }
+    }
+}
+
 /// An event that a heat has changed. This event is sent when a heat is opened or closed
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct EventHeatChanged {
     /// The heat that has changed.
     pub heat: Heat,
@@ -121,11 +153,11 @@ impl FromStr for EventHeatChanged {
         let action = parts[0];
         let number = parts[1].parse()?;
         let id = parts[2].parse()?;
-        let status = parts[3].parse()?;
+        let state = HeatState::from(parts[3].parse::<u8>()?);
 
         match action {
-            "!OPEN+" => Ok(EventHeatChanged::new(Heat::new(id, number, status), true)),
-            "!OPEN-" => Ok(EventHeatChanged::new(Heat::new(id, number, status), false)),
+            "!OPEN+" => Ok(EventHeatChanged::new(Heat::new(id, number, state), true)),
+            "!OPEN-" => Ok(EventHeatChanged::new(Heat::new(id, number, state), false)),
             _ => Err(AquariusErr::InvalidMessage(s.to_owned())),
         }
     }
@@ -143,6 +175,103 @@ impl EventHeatChanged {
     }
 }
 
+/// An event that Aquarius has confirmed a time, e.g. a time sent by this or another timekeeping station. The event
+/// repeats the arguments of the `TIME` command, e.g. `!TIME time=10:15:30.123 comp=17 split=64 bib=3`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTimeConfirmed {
+    /// The confirmed time of day.
+    pub time: NaiveTime,
+    /// The number of the heat the time belongs to.
+    pub heat_nr: HeatNr,
+    /// The split the time was taken at.
+    pub split: Split,
+    /// The bib of the boat the time was assigned to, if any.
+    pub bib: Option<Bib>,
+}
+
+impl FromStr for EventTimeConfirmed {
+    type Err = AquariusErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AquariusErr::InvalidMessage(s.to_owned());
+        let args = s.strip_prefix("!TIME ").ok_or_else(invalid)?;
+        let (mut time, mut heat_nr, mut split, mut bib) = (None, None, None, None);
+        for arg in args.split_whitespace() {
+            match arg.split_once('=').ok_or_else(invalid)? {
+                ("time", value) => {
+                    time = Some(NaiveTime::parse_from_str(value, "%H:%M:%S%.3f").map_err(|_| invalid())?)
+                }
+                ("comp", value) => heat_nr = Some(value.parse()?),
+                ("split", value) => split = Some(Split::from(value.parse::<u8>()?)),
+                ("bib", value) => bib = Some(value.parse()?),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(EventTimeConfirmed {
+            time: time.ok_or_else(invalid)?,
+            heat_nr: heat_nr.ok_or_else(invalid)?,
+            split: split.ok_or_else(invalid)?,
+            bib,
+        })
+    }
+}
+
+/// An event that the state of a heat has changed, e.g. from started to finished, e.g. `!STATE 3 2766 4`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventHeatStateChanged {
+    /// The heat with its new state. The start list is not included.
+    pub heat: Heat,
+}
+
+impl FromStr for EventHeatStateChanged {
+    type Err = AquariusErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let heat = s
+            .strip_prefix("!STATE ")
+            .ok_or_else(|| AquariusErr::InvalidMessage(s.to_owned()))?
+            .parse()?;
+        Ok(EventHeatStateChanged { heat })
+    }
+}
+
+/// An event that the result of a boat in a heat has changed, e.g. `!RESULT 2766 3 1 7:12.34`. A boat without rank,
+/// e.g. one that did not finish, has rank `0`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventResultUpdated {
+    /// The identifier of the heat.
+    pub heat_id: u16,
+    /// The bib of the boat.
+    pub bib: Bib,
+    /// The rank of the boat in the heat, if the boat is ranked.
+    pub rank: Option<u8>,
+    /// The result as displayed by Aquarius, e.g. the finish time or `DNF`.
+    pub result: String,
+}
+
+impl FromStr for EventResultUpdated {
+    type Err = AquariusErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = s
+            .strip_prefix("!RESULT ")
+            .ok_or_else(|| AquariusErr::InvalidMessage(s.to_owned()))?;
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        if parts.len() != 4 {
+            return Err(AquariusErr::InvalidMessage(s.to_owned()));
+        }
+        let rank = parts[2].parse()?;
+        Ok(EventResultUpdated {
+            heat_id: parts[0].parse()?,
+            bib: parts[1].parse()?,
+            rank: (rank > 0).then_some(rank),
+            result: parts[3].to_owned(),
+        })
+    }
+}
+
 /// A heat in a competition.
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Heat {
@@ -151,7 +280,10 @@ pub struct Heat {
     // The heat number.
     pub number: HeatNr,
     // The heat state.
-    state: u8,
+    pub state: HeatState,
+    // A stable key of the heat state.
+    #[serde(rename = "stateKey")]
+    state_key: &'static str,
     // The boats in the heat.
     pub boats: Option<Vec<Boat>>,
 }
@@ -164,14 +296,23 @@ impl Heat {
     /// * `state` - The heat state.
     /// # Returns
     /// A new heat with the given id, number, and state.
-    fn new(id: u16, number: i16, state: u8) -> Self {
+    fn new(id: u16, number: i16, state: HeatState) -> Self {
         Heat {
             id,
             number,
             state,
+            state_key: state.key(),
             boats: None,
         }
     }
+
+    /// Sets the state of the heat.
+    /// # Arguments
+    /// * `state` - The new state of the heat.
+    pub fn set_state(&mut self, state: HeatState) {
+        self.state = state;
+        self.state_key = state.key();
+    }
 }
 
 impl FromStr for Heat {
@@ -184,14 +325,20 @@ impl FromStr for Heat {
         }
         let number = parts[0].parse()?;
         let id = parts[1].parse()?;
-        let status = parts[2].parse()?;
-        Ok(Heat::new(id, number, status))
+        let state = HeatState::from(parts[2].parse::<u8>()?);
+        Ok(Heat::new(id, number, state))
     }
 }
 
 impl Display for Heat {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        writeln!(f, "Heat: id={}, number={}, state={}", self.id, self.number, self.state)
+        writeln!(
+            f,
+            "Heat: id={}, number={}, state={}",
+            self.id,
+            self.number,
+            self.state.value()
+        )
     }
 }
 
@@ -205,7 +352,10 @@ pub struct Boat {
     /// The club name of the boat.
     pub club: String,
     /// The state of the boat.
-    pub state: u8,
+    pub state: BoatState,
+    /// A stable key of the state of the boat.
+    #[serde(rename = "stateKey")]
+    state_key: &'static str,
 }
 
 impl Boat {
@@ -216,12 +366,13 @@ impl Boat {
     /// * `club` - The club name.
     /// # Returns
     /// A new boat.
-    fn new(lane: Lane, bib: Bib, club: String, state: u8) -> Self {
+    fn new(lane: Lane, bib: Bib, club: String, state: BoatState) -> Self {
         Boat {
             bib,
             lane,
             club: utils::unquote(&club).to_owned(),
             state,
+            state_key: state.key(),
         }
     }
 }
@@ -234,7 +385,7 @@ impl FromStr for Boat {
         if parts.len() == 4 {
             let lane = parts[0].parse()?;
             let bib: u8 = parts[1].parse()?;
-            let state: u8 = parts[2].parse()?;
+            let state = BoatState::from(parts[2].parse::<u8>()?);
             let club = parts[3].to_owned();
             Ok(Boat::new(lane, bib, club, state))
         } else {
@@ -252,6 +403,7 @@ impl Display for Boat {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ::chrono::TimeZone;
     use tracing::Level;
 
     fn init() {
@@ -267,6 +419,32 @@ mod tests {
         assert_eq!(request.to_string(), "?OPEN\n");
     }
 
+    #[test]
+    fn test_request_set_time_intermediate() {
+        let request = RequestSetTime {
+            time: Local.with_ymd_and_hms(2025, 6, 1, 10, 15, 30).unwrap(),
+            split: Split::Intermediate(2),
+            heat_nr: 17,
+            bib: Some(3),
+        };
+        assert_eq!(request.to_string(), "TIME time=10:15:30.000 comp=17 split=2 bib=3\n");
+    }
+
+    #[test]
+    fn test_response_set_time() {
+        assert_eq!("OK".parse::<ResponseSetTime>().unwrap(), ResponseSetTime::Accepted);
+        assert_eq!(
+            "ERR Heat 17 is closed".parse::<ResponseSetTime>().unwrap(),
+            ResponseSetTime::Rejected {
+                reason: "Heat 17 is closed".to_owned()
+            }
+        );
+        assert!(matches!(
+            "".parse::<ResponseSetTime>(),
+            Err(AquariusErr::InvalidMessage(_))
+        ));
+    }
+
     #[test]
     fn test_response_list_open_heats() {
         let message = "3 2766 4\n50 2767 4\n71 2786 4";
@@ -276,13 +454,13 @@ mod tests {
         assert_eq!(response.heats.len(), 3);
         assert_eq!(response.heats[0].id, 2766);
         assert_eq!(response.heats[0].number, 3);
-        assert_eq!(response.heats[0].state, 4);
+        assert_eq!(response.heats[0].state, HeatState::Official);
         assert_eq!(response.heats[1].id, 2767);
         assert_eq!(response.heats[1].number, 50);
-        assert_eq!(response.heats[1].state, 4);
+        assert_eq!(response.heats[1].state, HeatState::Official);
         assert_eq!(response.heats[2].id, 2786);
         assert_eq!(response.heats[2].number, 71);
-        assert_eq!(response.heats[2].state, 4);
+        assert_eq!(response.heats[2].state, HeatState::Official);
 
         let message = "3 2766 4\n50 2767 4\n71 2786 4f";
         assert!(message.parse::<ResponseListOpenHeats>().is_err());
@@ -290,10 +468,10 @@ mod tests {
 
     #[test]
     fn test_heat_new() {
-        let heat = Heat::new(1234, 50, 4);
+        let heat = Heat::new(1234, 50, HeatState::Official);
         assert_eq!(heat.id, 1234);
         assert_eq!(heat.number, 50);
-        assert_eq!(heat.state, 4);
+        assert_eq!(heat.state, HeatState::Official);
     }
 
     #[test]
@@ -308,10 +486,26 @@ mod tests {
 
     #[test]
     fn test_display_heat() {
-        let heat = Heat::new(2766, 1, 4);
+        let heat = Heat::new(2766, 1, HeatState::Official);
         assert_eq!(heat.to_string(), "Heat: id=2766, number=1, state=4\n");
     }
 
+    #[test]
+    fn test_serialize_heat() {
+        let mut heat = Heat::new(2766, 1, HeatState::Started);
+        heat.boats = Some(vec![Boat::new(
+            1,
+            12,
+            "RV Neptun Konstanz".to_owned(),
+            BoatState::DidNotStart,
+        )]);
+        let json = serde_json::to_value(&heat).unwrap();
+        assert_eq!(json["state"], 2);
+        assert_eq!(json["stateKey"], "started");
+        assert_eq!(json["boats"][0]["state"], 1);
+        assert_eq!(json["boats"][0]["stateKey"], "didNotStart");
+    }
+
     #[test]
     fn test_request_start_list() {
         let request = RequestStartList::new(1);
@@ -340,7 +534,7 @@ mod tests {
 
     #[test]
     fn test_boat_new() {
-        let boat = Boat::new(1, 12, "RV Neptun Konstanz".to_owned(), 0);
+        let boat = Boat::new(1, 12, "RV Neptun Konstanz".to_owned(), BoatState::Entered);
         assert_eq!(boat.lane, 1);
         assert_eq!(boat.bib, 12);
         assert_eq!(boat.club, "RV Neptun Konstanz");
@@ -354,24 +548,24 @@ mod tests {
         assert_eq!(boat.lane, 1);
         assert_eq!(boat.bib, 12);
         assert_eq!(boat.club, "RV Neptun Konstanz");
-        assert_eq!(boat.state, 0);
+        assert_eq!(boat.state, BoatState::Entered);
 
         assert!("1 12".parse::<Boat>().is_err());
     }
 
     #[test]
     fn test_display_boat() {
-        let boat = Boat::new(1, 12, "RV Neptun Konstanz".to_owned(), 0);
+        let boat = Boat::new(1, 12, "RV Neptun Konstanz".to_owned(), BoatState::Entered);
         assert_eq!(boat.to_string(), "Boat: lane=1, bib=12, club=RV Neptun Konstanz\n");
     }
 
     #[test]
     fn test_event_heat_changed_new() {
-        let heat = Heat::new(1234, 50, 4);
+        let heat = Heat::new(1234, 50, HeatState::Official);
         let event = EventHeatChanged::new(heat.clone(), true);
         assert_eq!(event.heat.id, 1234);
         assert_eq!(event.heat.number, 50);
-        assert_eq!(event.heat.state, 4);
+        assert_eq!(event.heat.state, HeatState::Official);
         assert!(event.opened);
 
         let event = EventHeatChanged::new(heat, false);
@@ -387,7 +581,7 @@ mod tests {
         let event = event.unwrap();
         assert_eq!(event.heat.id, 1234);
         assert_eq!(event.heat.number, 50);
-        assert_eq!(event.heat.state, 4);
+        assert_eq!(event.heat.state, HeatState::Official);
         assert!(event.opened);
 
         let event = "!OPEN- 50 1234 4".parse::<EventHeatChanged>();
@@ -395,7 +589,7 @@ mod tests {
         let event = event.unwrap();
         assert_eq!(event.heat.id, 1234);
         assert_eq!(event.heat.number, 50);
-        assert_eq!(event.heat.state, 4);
+        assert_eq!(event.heat.state, HeatState::Official);
         assert!(!event.opened);
 
         let event = "!OPEN+ 50 1234".parse::<EventHeatChanged>();
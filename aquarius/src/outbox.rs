@@ -0,0 +1,260 @@
+use crate::error::AquariusErr;
+use ::chrono::{DateTime, Utc};
+use ::serde::{Deserialize, Serialize};
+use ::std::collections::VecDeque;
+use ::std::fs::{self, File};
+use ::std::io::{ErrorKind, Write};
+use ::std::path::{Path, PathBuf};
+use ::tracing::warn;
+
+/// The result of sending a time to Aquarius.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// The time was accepted by Aquarius.
+    Accepted,
+
+    /// The time could not be sent and was queued in the outbox. It is sent as soon as Aquarius is connected again.
+    Queued {
+        /// The identifier of the item in the outbox.
+        id: u64,
+    },
+}
+
+/// A `TIME` command waiting in the outbox to be sent to Aquarius.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxItem {
+    /// The identifier of the item, unique within the outbox. Identifiers are never reused, not even after the outbox
+    /// was drained.
+    pub id: u64,
+
+    /// The time the command was queued.
+    pub queued_at: DateTime<Utc>,
+
+    /// The `TIME` command without its line terminator.
+    pub command: String,
+}
+
+/// A persistent queue of `TIME` commands that could not be sent to Aquarius. The items are kept in the order they were
+/// queued and stored as JSON file, which is rewritten on each change. So no time is lost if the application is
+/// restarted before Aquarius is connected again.
+pub struct Outbox {
+    /// The file the items are stored in.
+    path: PathBuf,
+
+    /// The items in the order they were queued.
+    items: VecDeque<OutboxItem>,
+
+    /// The identifier of the next queued item.
+    next_id: u64,
+}
+
+/// The content of the outbox file.
+#[derive(Serialize, Deserialize)]
+struct OutboxFile {
+    /// The identifier of the next queued item.
+    next_id: u64,
+
+    /// The items in the order they were queued.
+    items: VecDeque<OutboxItem>,
+}
+
+impl Default for OutboxFile {
+    fn default() -> Self {
+        OutboxFile {
+            next_id: 1,
+            items: VecDeque::new(),
+        }
+    }
+}
+
+impl Outbox {
+    /// Opens an outbox stored in the given file. The file is created with the first queued item. A file that can't be
+    /// parsed, e.g. truncated by a power loss, is moved aside to `<path>.corrupt` and the outbox starts empty.
+    /// # Arguments
+    /// * `path` - The path of the file to store the items in.
+    /// # Returns
+    /// The outbox containing the items stored in the file.
+    /// # Errors
+    /// If the file exists but could not be read or moved aside.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AquariusErr> {
+        let path = path.as_ref().to_path_buf();
+        let file = match fs::read_to_string(&path) {
+            Ok(content) if content.trim().is_empty() => OutboxFile::default(),
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(file) => file,
+                Err(err) => {
+                    let corrupt_path = path.with_extension("corrupt");
+                    warn!(%err, path = %corrupt_path.display(), "Moving aside unreadable outbox:");
+                    fs::rename(&path, &corrupt_path)?;
+                    OutboxFile::default()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => OutboxFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Outbox {
+            path,
+            items: file.items,
+            next_id: file.next_id,
+        })
+    }
+
+    /// Returns the pending items in the order they were queued.
+    pub fn items(&self) -> Vec<OutboxItem> {
+        self.items.iter().cloned().collect()
+    }
+
+    /// Returns the number of pending items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if there are no pending items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Appends a command to the outbox.
+    /// # Arguments
+    /// * `command` - The `TIME` command to queue.
+    /// # Returns
+    /// The queued item.
+    /// # Errors
+    /// If the outbox could not be stored. The command is not queued then.
+    pub fn push(&mut self, command: &str) -> Result<OutboxItem, AquariusErr> {
+        let item = OutboxItem {
+            id: self.next_id,
+            queued_at: Utc::now(),
+            command: command.trim_end().to_owned(),
+        };
+        self.items.push_back(item.clone());
+        self.next_id += 1;
+        if let Err(err) = self.store() {
+            // the caller is told the time was not queued, so it must not be sent later
+            self.items.pop_back();
+            self.next_id -= 1;
+            return Err(err);
+        }
+        Ok(item)
+    }
+
+    /// Returns the oldest pending item.
+    pub fn front(&self) -> Option<&OutboxItem> {
+        self.items.front()
+    }
+
+    /// Discards a pending item without sending it.
+    /// # Arguments
+    /// * `id` - The identifier of the item to discard.
+    /// # Returns
+    /// The discarded item, or `None` if there is no item with the given identifier.
+    /// # Errors
+    /// If the outbox could not be stored.
+    pub fn discard(&mut self, id: u64) -> Result<Option<OutboxItem>, AquariusErr> {
+        let Some(index) = self.items.iter().position(|item| item.id == id) else {
+            return Ok(None);
+        };
+        let item = self.items.remove(index);
+        self.store()?;
+        Ok(item)
+    }
+
+    /// Writes the items to the file. The items are written to a temporary file first, which is flushed to disk and
+    /// then replaces the file, so the file is never left half written, not even by a power loss.
+    fn store(&self) -> Result<(), AquariusErr> {
+        let json = serde_json::to_string_pretty(&OutboxFile {
+            next_id: self.next_id,
+            items: self.items.clone(),
+        })?;
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(json.as_bytes())?;
+        tmp_file.sync_data()?;
+        fs::rename(&tmp_path, &self.path)?;
+        // persist the rename, directories can't be synced on all platforms
+        if let Some(dir) = self.path.parent()
+            && let Ok(dir) = File::open(if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            })
+        {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+
+    #[test]
+    fn test_outbox_persistence() {
+        let path = env::temp_dir().join(format!("aquarius-outbox-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut outbox = Outbox::open(&path).unwrap();
+        assert!(outbox.is_empty());
+        let first = outbox.push("TIME time=10:00:00.000 comp=3 split=0\n").unwrap();
+        let second = outbox.push("TIME time=10:07:12.345 comp=3 split=64").unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(first.command, "TIME time=10:00:00.000 comp=3 split=0");
+
+        // the items survive a restart
+        let mut outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.items(), vec![first.clone(), second.clone()]);
+
+        assert_eq!(outbox.discard(first.id).unwrap(), Some(first));
+        assert_eq!(outbox.discard(42).unwrap(), None);
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(outbox.front(), Some(&second));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_outbox_ids_not_reused() {
+        let path = env::temp_dir().join(format!("aquarius-outbox-ids-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut outbox = Outbox::open(&path).unwrap();
+        let first = outbox.push("TIME time=10:00:00.000 comp=3 split=0").unwrap();
+        outbox.discard(first.id).unwrap();
+        assert!(outbox.is_empty());
+
+        // the drained outbox keeps counting, also after a restart
+        let mut outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.push("TIME time=10:07:12.345 comp=3 split=64").unwrap().id, 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_outbox_corrupt() {
+        let path = env::temp_dir().join(format!("aquarius-outbox-corrupt-{}.json", std::process::id()));
+        fs::write(&path, "{\"next_id\": 3, \"items\": [").unwrap();
+
+        // a truncated file does not keep the outbox from being used
+        let mut outbox = Outbox::open(&path).unwrap();
+        assert!(outbox.is_empty());
+        assert!(path.with_extension("corrupt").exists());
+        assert_eq!(outbox.push("TIME time=10:00:00.000 comp=3 split=0").unwrap().id, 1);
+        assert_eq!(Outbox::open(&path).unwrap().len(), 1);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("corrupt")).unwrap();
+    }
+
+    #[test]
+    fn test_outbox_push_not_stored() {
+        let dir = env::temp_dir().join(format!("aquarius-outbox-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut outbox = Outbox::open(dir.join("outbox.json")).unwrap();
+        fs::remove_dir(&dir).unwrap();
+
+        // a time that could not be stored is not sent later
+        assert!(outbox.push("TIME time=10:00:00.000 comp=3 split=0").is_err());
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.next_id, 1);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::error::AquariusErr;
+use crate::event::AquariusEvent;
+use crate::messages::{Boat, EventHeatChanged, Heat, ResponseListOpenHeats, ResponseSetTime, ResponseStartList};
+use crate::status::ConnectionState;
+use ::chrono::{DateTime, Utc};
+use ::serde::{Deserialize, Serialize};
+use ::std::collections::{HashMap, VecDeque};
+use ::std::fs::{File, OpenOptions};
+use ::std::io::{BufRead, BufReader, Write};
+use ::std::mem;
+use ::std::path::Path;
+use ::std::sync::{Arc, Mutex as StdMutex};
+use ::tracing::warn;
+
+/// The direction of a recorded line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// The line was received from Aquarius.
+    In,
+
+    /// The line was sent to Aquarius.
+    Out,
+}
+
+/// A single line sent to or received from Aquarius, as written to a recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// The time the line was sent or received.
+    pub timestamp: DateTime<Utc>,
+
+    /// The identifier of the connection the line was sent or received on. Each connection of a client gets a new one.
+    pub connection: u32,
+
+    /// Whether the line was sent or received.
+    pub direction: Direction,
+
+    /// The raw line including its line terminator.
+    pub line: String,
+}
+
+/// Records all lines sent to and received from Aquarius to a file. Each line is appended as a JSON object on a line of
+/// its own (JSONL), so a recording can be inspected with common tools and replayed with [`Replay`].
+#[derive(Clone)]
+pub struct Recorder {
+    /// The file the records are appended to.
+    file: Arc<StdMutex<File>>,
+}
+
+impl Recorder {
+    /// Creates a new recorder that appends to the given file. The file is created if it does not exist.
+    /// # Arguments
+    /// * `path` - The path of the file to append the records to.
+    /// # Returns
+    /// A recorder appending to the given file.
+    /// # Errors
+    /// If the file could not be opened.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, AquariusErr> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            file: Arc::new(StdMutex::new(file)),
+        })
+    }
+
+    /// Appends a line to the recording.
+    /// # Arguments
+    /// * `connection` - The identifier of the connection the line was sent or received on.
+    /// * `direction` - Whether the line was sent or received.
+    /// * `line` - The raw line.
+    /// # Errors
+    /// If the record could not be written.
+    pub fn record(&self, connection: u32, direction: Direction, line: &str) -> Result<(), AquariusErr> {
+        let record = Record {
+            timestamp: Utc::now(),
+            connection,
+            direction,
+            line: line.to_owned(),
+        };
+        let json = serde_json::to_string(&record)?;
+        let mut file = self.file.lock().map_err(|_| AquariusErr::MutexPoisonError())?;
+        // write the record with a single call, so a record is never split
+        file.write_all(format!("{json}\n").as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Records the lines of a single connection to Aquarius, if a recorder is set for the client.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectionRecorder {
+    /// The recorder of the client. It can be set or removed while the client is connected.
+    recorder: Arc<StdMutex<Option<Recorder>>>,
+
+    /// The identifier of the connection.
+    connection: u32,
+}
+
+impl ConnectionRecorder {
+    /// Creates a recorder for a connection of a client.
+    /// # Arguments
+    /// * `recorder` - The recorder of the client.
+    /// * `connection` - The identifier of the connection.
+    pub(crate) fn new(recorder: Arc<StdMutex<Option<Recorder>>>, connection: u32) -> Self {
+        ConnectionRecorder { recorder, connection }
+    }
+
+    /// Records a line if a recorder is set. Errors are only logged, since recording must never disturb the connection.
+    pub(crate) fn record(&self, direction: Direction, line: &str) {
+        let recorder = self.recorder.lock().ok().and_then(|recorder| recorder.clone());
+        if let Some(recorder) = recorder
+            && let Err(err) = recorder.record(self.connection, direction, line)
+        {
+            warn!(%err, "Error recording line:");
+        }
+    }
+}
+
+/// A message reconstructed from a recording.
+#[derive(Debug)]
+pub enum Replayed {
+    /// An event the client would have emitted.
+    Event(AquariusEvent),
+
+    /// The reply to a request for the open heats. The heats do not contain their boats.
+    OpenHeats(Vec<Heat>),
+
+    /// The reply to a request for the start list of a heat.
+    StartList {
+        /// The identifier of the heat.
+        heat_id: u16,
+
+        /// The boats of the heat.
+        boats: Vec<Boat>,
+    },
+
+    /// The answer of Aquarius to a `TIME` command.
+    Time {
+        /// The `TIME` command that was sent.
+        command: String,
+
+        /// The answer of Aquarius.
+        response: ResponseSetTime,
+    },
+}
+
+/// A recording loaded from a file, to feed the recorded lines through the message parsers again. This allows to
+/// reproduce parsing failures offline and to check which times were sent to Aquarius.
+pub struct Replay {
+    /// The records in the order they were recorded.
+    records: Vec<Record>,
+}
+
+impl Replay {
+    /// Loads a recording written by a [`Recorder`].
+    /// # Arguments
+    /// * `path` - The path of the recording.
+    /// # Returns
+    /// The loaded recording.
+    /// # Errors
+    /// If the file could not be read or contains an invalid record.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AquariusErr> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Replay { records })
+    }
+
+    /// Returns all records of the recording.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Returns the records of the times that were sent to Aquarius.
+    pub fn sent_times(&self) -> impl Iterator<Item = &Record> {
+        self.records
+            .iter()
+            .filter(|record| record.direction == Direction::Out && record.line.starts_with("TIME"))
+    }
+
+    /// Feeds the recorded lines through the message parsers, the same way the client processes them. Requests are
+    /// matched with their replies per connection, opened heats are completed with the start list that was requested for
+    /// them.
+    /// # Returns
+    /// The reconstructed messages or the errors parsing them, together with the record that completed the message.
+    pub fn messages(&self) -> Vec<(&Record, Result<Replayed, AquariusErr>)> {
+        let mut connections: HashMap<u32, ReplayConnection> = HashMap::new();
+        let mut messages = Vec::new();
+        for record in &self.records {
+            let connection = connections.entry(record.connection).or_insert_with(|| {
+                messages.push((
+                    record,
+                    Ok(Replayed::Event(AquariusEvent::Client(ConnectionState::Connected))),
+                ));
+                ReplayConnection::default()
+            });
+            let line = record.line.trim_end();
+            match record.direction {
+                Direction::Out => {
+                    if line.starts_with('?') || line.starts_with("TIME") {
+                        connection.requests.push_back(line.to_owned());
+                    }
+                }
+                Direction::In if line.starts_with('!') => match line.parse::<AquariusEvent>() {
+                    Ok(AquariusEvent::HeatListChanged(event)) if event.opened => connection.opened.push(event),
+                    Ok(event) => messages.push((record, Ok(Replayed::Event(event)))),
+                    Err(err) => messages.push((record, Err(err))),
+                },
+                Direction::In if line.is_empty() => {
+                    let reply = mem::take(&mut connection.reply);
+                    if let Some(request) = connection.requests.pop_front()
+                        && let Some(replayed) = connection.complete(&request, &reply)
+                    {
+                        messages.push((record, replayed));
+                    }
+                }
+                Direction::In => {
+                    if !connection.reply.is_empty() {
+                        connection.reply.push('\n');
+                    }
+                    connection.reply.push_str(line);
+                }
+            }
+        }
+        // Opened heats without a start list are passed on without boats, like the client does
+        if let Some(last) = self.records.last() {
+            for connection in connections.into_values() {
+                for event in connection.opened {
+                    messages.push((last, Ok(Replayed::Event(AquariusEvent::HeatListChanged(event)))));
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// The state of a connection while it is replayed.
+#[derive(Default)]
+struct ReplayConnection {
+    /// The requests waiting for their replies, in the order they were sent.
+    requests: VecDeque<String>,
+
+    /// The lines of the reply that is currently received.
+    reply: String,
+
+    /// The opened heats waiting for their start lists.
+    opened: Vec<EventHeatChanged>,
+}
+
+impl ReplayConnection {
+    /// Parses the reply to a request.
+    /// # Returns
+    /// The reconstructed message, or `None` if the request is unknown.
+    fn complete(&mut self, request: &str, reply: &str) -> Option<Result<Replayed, AquariusErr>> {
+        if request == "?OPEN" {
+            return Some(
+                reply
+                    .parse::<ResponseListOpenHeats>()
+                    .map(|response| Replayed::OpenHeats(response.heats)),
+            );
+        }
+        if request.starts_with("TIME") {
+            return Some(reply.parse::<ResponseSetTime>().map(|response| Replayed::Time {
+                command: request.to_owned(),
+                response,
+            }));
+        }
+        let heat_id = request.strip_prefix("?STARTLIST id=")?;
+        let heat_id = match heat_id.parse::<u16>() {
+            Ok(heat_id) => heat_id,
+            Err(err) => return Some(Err(err.into())),
+        };
+        let boats = match reply.parse::<ResponseStartList>() {
+            Ok(response) => response.boats,
+            Err(err) => return Some(Err(err)),
+        };
+        match self.opened.iter().position(|event| event.heat.id == heat_id) {
+            Some(index) => {
+                let mut event = self.opened.remove(index);
+                event.heat.boats = Some(boats);
+                Some(Ok(Replayed::Event(AquariusEvent::HeatListChanged(event))))
+            }
+            None => Some(Ok(Replayed::StartList { heat_id, boats })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+    use ::std::fs;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("aquarius-{name}-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = temp_path("record");
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(1, Direction::Out, "?OPEN\n").unwrap();
+        recorder.record(1, Direction::In, "!OPEN+ 3 2766 1\r\n").unwrap();
+        recorder.record(1, Direction::In, "3 2766 1\r\n").unwrap();
+        recorder.record(1, Direction::In, "\r\n").unwrap();
+        recorder.record(1, Direction::Out, "?STARTLIST id=2766\n").unwrap();
+        recorder
+            .record(1, Direction::In, "1 1 0 'RV Neptun Konstanz'\r\n")
+            .unwrap();
+        recorder.record(1, Direction::In, "\r\n").unwrap();
+        recorder
+            .record(1, Direction::Out, "TIME time=10:00:00.000 comp=3 split=0\n")
+            .unwrap();
+        recorder.record(1, Direction::In, "OK\r\n").unwrap();
+        recorder.record(1, Direction::In, "\r\n").unwrap();
+        recorder.record(2, Direction::In, "!OPEN- 3 2766 4\r\n").unwrap();
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(replay.records().len(), 11);
+        assert_eq!(replay.records()[1].line, "!OPEN+ 3 2766 1\r\n");
+        assert_eq!(replay.sent_times().count(), 1);
+
+        let messages = replay.messages();
+        assert_eq!(messages.len(), 6);
+        assert!(matches!(
+            messages[0].1,
+            Ok(Replayed::Event(AquariusEvent::Client(ConnectionState::Connected)))
+        ));
+        assert!(matches!(&messages[1].1, Ok(Replayed::OpenHeats(heats)) if heats[0].id == 2766));
+        match &messages[2].1 {
+            Ok(Replayed::Event(AquariusEvent::HeatListChanged(event))) => {
+                assert!(event.opened);
+                assert_eq!(event.heat.boats.as_ref().unwrap()[0].club, "RV Neptun Konstanz");
+            }
+            other => panic!("Unexpected message: {other:?}"),
+        }
+        assert!(matches!(
+            &messages[3].1,
+            Ok(Replayed::Time {
+                response: ResponseSetTime::Accepted,
+                ..
+            })
+        ));
+        assert_eq!(messages[4].0.connection, 2);
+        assert!(matches!(
+            &messages[5].1,
+            Ok(Replayed::Event(AquariusEvent::HeatListChanged(event))) if !event.opened
+        ));
+    }
+
+    #[test]
+    fn test_replay_parse_error() {
+        let path = temp_path("error");
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(1, Direction::Out, "?OPEN\n").unwrap();
+        recorder.record(1, Direction::In, "3 x 1\r\n").unwrap();
+        recorder.record(1, Direction::In, "\r\n").unwrap();
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let messages = replay.messages();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[1].1, Err(AquariusErr::ParseError(_))));
+        assert_eq!(messages[1].0.line, "\r\n");
+    }
+}
@@ -0,0 +1,632 @@
+mod scenario;
+
+use crate::codec::Codec;
+use crate::error::AquariusErr;
+use crate::messages::Heat;
+use ::db::aquarius::model::HeatState;
+use ::std::io;
+use ::std::net::SocketAddr;
+use ::std::sync::{Arc, Mutex};
+use ::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use ::tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use ::tokio::select;
+use ::tokio::sync::Notify;
+use ::tokio::sync::broadcast::{self, Sender, error::RecvError};
+use ::tokio::time;
+use ::tokio_util::sync::CancellationToken;
+use ::tracing::{debug, info, warn};
+
+pub use scenario::Scenario;
+pub use scenario::Step;
+
+/// The number of events buffered for each connected client.
+const EVENT_CAPACITY: usize = 64;
+
+/// A simulator that speaks the Aquarius protocol as server. It answers `?OPEN` and `?STARTLIST id=` requests,
+/// acknowledges `TIME` commands for open heats and confirms them with a `!TIME` event. It emits `!OPEN+`, `!OPEN-`,
+/// `!STATE` and `!RESULT` events to all connected clients as scripted in a [`Scenario`]. The scenario starts playing as soon as the first client has connected.
+pub struct Simulator {
+    /// The address the simulator is listening on.
+    address: SocketAddr,
+
+    /// The state of the simulated Aquarius application.
+    state: Arc<Mutex<State>>,
+
+    /// A token to indicate if the simulator should shut down.
+    shutdown: CancellationToken,
+}
+
+impl Simulator {
+    /// Starts a new simulator. Must be called within a tokio runtime.
+    /// # Arguments
+    /// * `address` - The address to listen on.
+    /// * `scenario` - The scenario to play.
+    /// # Returns
+    /// The running simulator.
+    /// # Errors
+    /// If the simulator could not listen on the given address.
+    pub async fn start(address: impl ToSocketAddrs, scenario: Scenario) -> Result<Self, AquariusErr> {
+        Self::start_with_codec(address, scenario, Codec::default()).await
+    }
+
+    /// Starts a new simulator that uses the given text encoding. Must be called within a tokio runtime.
+    /// # Arguments
+    /// * `address` - The address to listen on.
+    /// * `scenario` - The scenario to play.
+    /// * `codec` - The text encoding to use, Aquarius uses Windows-1252 by default.
+    /// # Returns
+    /// The running simulator.
+    /// # Errors
+    /// If the simulator could not listen on the given address.
+    pub async fn start_with_codec(
+        address: impl ToSocketAddrs,
+        scenario: Scenario,
+        codec: Codec,
+    ) -> Result<Self, AquariusErr> {
+        let listener = TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            heats: scenario.heats,
+            open_heats: Vec::new(),
+            received_times: Vec::new(),
+        }));
+        let shutdown = CancellationToken::new();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let connected = Arc::new(Notify::new());
+
+        tokio::spawn(accept_connections(
+            listener,
+            codec,
+            state.clone(),
+            events.clone(),
+            connected.clone(),
+            shutdown.clone(),
+        ));
+        tokio::spawn(play_scenario(
+            scenario.steps,
+            state.clone(),
+            events,
+            connected,
+            shutdown.clone(),
+        ));
+        info!(%address, "Aquarius simulator listening on:");
+
+        Ok(Simulator {
+            address,
+            state,
+            shutdown,
+        })
+    }
+
+    /// Returns the address the simulator is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the heats that are currently open.
+    pub fn open_heats(&self) -> Vec<Heat> {
+        self.state.lock().unwrap().open_heats()
+    }
+
+    /// Returns all `TIME` commands received by the simulator in the order they were received.
+    pub fn received_times(&self) -> Vec<String> {
+        self.state.lock().unwrap().received_times.clone()
+    }
+
+    /// Stops the simulator and closes all client connections.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// The state of the simulated Aquarius application.
+struct State {
+    /// All heats known to the simulator.
+    heats: Vec<Heat>,
+    /// The identifiers of the open heats in the order they were opened.
+    open_heats: Vec<u16>,
+    /// The received `TIME` commands.
+    received_times: Vec<String>,
+}
+
+impl State {
+    fn open_heats(&self) -> Vec<Heat> {
+        self.open_heats
+            .iter()
+            .filter_map(|id| self.heats.iter().find(|heat| heat.id == *id))
+            .cloned()
+            .collect()
+    }
+
+    /// Handles a command sent by a client.
+    /// # Returns
+    /// The reply to send to the client, if any, and the event to emit to all clients, if any.
+    fn handle_command(&mut self, command: &str) -> (Option<String>, Option<String>) {
+        if command == "?OPEN" {
+            let lines = self
+                .open_heats()
+                .iter()
+                .map(|heat| format!("{} {} {}", heat.number, heat.id, heat.state.value()))
+                .collect();
+            (Some(reply(lines)), None)
+        } else if let Some(id) = command.strip_prefix("?STARTLIST id=") {
+            let boats = id
+                .parse::<u16>()
+                .ok()
+                .and_then(|id| self.heats.iter().find(|heat| heat.id == id))
+                .and_then(|heat| heat.boats.clone())
+                .unwrap_or_default();
+            let lines = boats
+                .iter()
+                .map(|boat| format!("{} {} {} '{}'", boat.lane, boat.bib, boat.state.value(), boat.club))
+                .collect();
+            (Some(reply(lines)), None)
+        } else if command.starts_with("TIME ") {
+            info!(command, "Received time:");
+            self.received_times.push(command.to_owned());
+            let response = self.accept_time(command);
+            // accepted times are confirmed to all timekeeping stations
+            let event = (response == "OK").then(|| format!("!{command}"));
+            (Some(reply(vec![response])), event)
+        } else {
+            debug!(command, "Ignoring:");
+            (None, None)
+        }
+    }
+
+    /// Checks whether a `TIME` command would be accepted by Aquarius. Times are only accepted for open heats.
+    /// # Returns
+    /// `OK` if the time is accepted, otherwise `ERR` followed by the reason.
+    fn accept_time(&self, command: &str) -> String {
+        let heat_nr = command
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix("comp="))
+            .and_then(|number| number.parse::<i16>().ok());
+        let Some(heat_nr) = heat_nr else {
+            return format!("ERR Invalid command: {command}");
+        };
+        match self.heats.iter().find(|heat| heat.number == heat_nr) {
+            None => format!("ERR Unknown heat {heat_nr}"),
+            Some(heat) if !self.open_heats.contains(&heat.id) => format!("ERR Heat {heat_nr} is not open"),
+            Some(_) => "OK".to_owned(),
+        }
+    }
+
+    /// Opens or closes a heat.
+    /// # Returns
+    /// The event to emit, or `None` if the heat is unknown or already in the requested state.
+    fn change_heat(&mut self, id: u16, open: bool) -> Option<String> {
+        let Some(heat) = self.heats.iter().find(|heat| heat.id == id) else {
+            warn!(id, "Unknown heat in scenario:");
+            return None;
+        };
+        let action = if open { '+' } else { '-' };
+        let event = format!("!OPEN{action} {} {} {}", heat.number, heat.id, heat.state.value());
+        match (open, self.open_heats.contains(&id)) {
+            (true, false) => self.open_heats.push(id),
+            (false, true) => self.open_heats.retain(|open_id| *open_id != id),
+            _ => return None,
+        }
+        Some(event)
+    }
+
+    /// Changes the state of a heat.
+    /// # Returns
+    /// The event to emit, or `None` if the heat is unknown.
+    fn change_heat_state(&mut self, id: u16, state: u8) -> Option<String> {
+        let Some(heat) = self.heats.iter_mut().find(|heat| heat.id == id) else {
+            warn!(id, "Unknown heat in scenario:");
+            return None;
+        };
+        heat.set_state(HeatState::from(state));
+        Some(format!("!STATE {} {} {}", heat.number, heat.id, heat.state.value()))
+    }
+}
+
+/// Joins the given lines to a reply. Aquarius terminates each reply with an empty line.
+fn reply(lines: Vec<String>) -> String {
+    let mut reply: String = lines.iter().map(|line| format!("{line}\r\n")).collect();
+    reply.push_str("\r\n");
+    reply
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    codec: Codec,
+    state: Arc<Mutex<State>>,
+    events: Sender<String>,
+    connected: Arc<Notify>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let (stream, peer) = select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!(%err, "Error accepting connection:");
+                    continue;
+                }
+            },
+        };
+        info!(%peer, "Client connected:");
+        let session = serve(
+            stream,
+            codec,
+            state.clone(),
+            events.clone(),
+            events.subscribe(),
+            shutdown.clone(),
+        );
+        connected.notify_one();
+        tokio::spawn(async move {
+            if let Err(err) = session.await {
+                warn!(%err, %peer, "Error serving client:");
+            }
+            info!(%peer, "Client disconnected:");
+        });
+    }
+    debug!("Stopped accepting connections.");
+}
+
+async fn serve(
+    stream: TcpStream,
+    codec: Codec,
+    state: Arc<Mutex<State>>,
+    sender: Sender<String>,
+    mut events: broadcast::Receiver<String>,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        select! {
+            _ = shutdown.cancelled() => break,
+            count = reader.read_until(b'\n', &mut buf) => {
+                if count? == 0 {
+                    break;
+                }
+                let line = codec.decode(&buf).into_owned();
+                buf.clear();
+                let (reply, event) = state.lock().unwrap().handle_command(line.trim());
+                if let Some(reply) = reply {
+                    writer.write_all(&codec.encode(&reply)).await?;
+                }
+                if let Some(event) = event {
+                    // an error only means that no client is connected at the moment
+                    let _ = sender.send(event);
+                }
+            },
+            event = events.recv() => match event {
+                Ok(event) => writer.write_all(&codec.encode(&format!("{event}\r\n"))).await?,
+                Err(RecvError::Lagged(count)) => warn!(count, "Dropped events:"),
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+    writer.shutdown().await
+}
+
+async fn play_scenario(
+    steps: Vec<Step>,
+    state: Arc<Mutex<State>>,
+    events: Sender<String>,
+    connected: Arc<Notify>,
+    shutdown: CancellationToken,
+) {
+    // The scenario starts as soon as the first client has connected
+    select! {
+        _ = shutdown.cancelled() => return,
+        _ = connected.notified() => {},
+    }
+    info!(steps = steps.len(), "Playing scenario:");
+    for step in steps {
+        let event = match step {
+            Step::Open(id) => state.lock().unwrap().change_heat(id, true),
+            Step::Close(id) => state.lock().unwrap().change_heat(id, false),
+            Step::State(id, heat_state) => state.lock().unwrap().change_heat_state(id, heat_state),
+            Step::Result(result) => Some(format!(
+                "!RESULT {} {} {} {}",
+                result.heat_id,
+                result.bib,
+                result.rank.unwrap_or_default(),
+                result.result
+            )),
+            Step::Wait(duration) => {
+                select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = time::sleep(duration) => {},
+                }
+                None
+            }
+        };
+        if let Some(event) = event {
+            info!(event, "Emitting event:");
+            // an error only means that no client is connected at the moment
+            let _ = events.send(event);
+        }
+    }
+    info!("Finished scenario.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_client::AsyncAquariusClient;
+    use crate::event::AquariusEvent;
+    use crate::outbox::{Delivery, Outbox};
+    use ::chrono::Utc;
+    use ::db::timekeeper::{Split, Timestamp};
+    use ::futures::StreamExt;
+    use ::std::time::Duration;
+    use ::tokio::io::Lines;
+    use ::tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    const SCENARIO: &str = "
+        HEAT 3 2766 4
+        BOAT 2766 1 1 0 'RV Neptun Konstanz'
+        BOAT 2766 2 2 0 'Ruderclub Lübeck'
+        HEAT 50 2767 4
+        OPEN 2767
+        WAIT 100
+        OPEN 2766
+        WAIT 100
+        CLOSE 2767
+    ";
+
+    async fn start_simulator(scenario: &str) -> Simulator {
+        Simulator::start("127.0.0.1:0", scenario.parse().unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn connect(simulator: &Simulator) -> (Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf) {
+        let stream = TcpStream::connect(simulator.local_addr()).await.unwrap();
+        let (reader, writer) = stream.into_split();
+        (BufReader::new(reader).lines(), writer)
+    }
+
+    async fn next_line(lines: &mut Lines<BufReader<OwnedReadHalf>>) -> String {
+        time::timeout(TEST_TIMEOUT, lines.next_line())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_open_heats_and_start_list() {
+        let simulator = start_simulator("HEAT 3 2766 4\nBOAT 2766 1 1 0 'RG Heidelberg'\nOPEN 2766").await;
+        let (mut lines, mut writer) = connect(&simulator).await;
+        assert_eq!(next_line(&mut lines).await, "!OPEN+ 3 2766 4");
+
+        writer.write_all(b"?OPEN\r\n").await.unwrap();
+        assert_eq!(next_line(&mut lines).await, "3 2766 4");
+        assert_eq!(next_line(&mut lines).await, "");
+
+        writer.write_all(b"?STARTLIST id=2766\r\n").await.unwrap();
+        assert_eq!(next_line(&mut lines).await, "1 1 0 'RG Heidelberg'");
+        assert_eq!(next_line(&mut lines).await, "");
+
+        writer.write_all(b"?STARTLIST id=1\r\n").await.unwrap();
+        assert_eq!(next_line(&mut lines).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_received_times() {
+        let simulator = start_simulator("").await;
+        let (mut lines, mut writer) = connect(&simulator).await;
+
+        writer
+            .write_all(b"TIME time=10:00:00.000 comp=3 split=0\r\n")
+            .await
+            .unwrap();
+        assert_eq!(next_line(&mut lines).await, "ERR Unknown heat 3");
+        assert_eq!(next_line(&mut lines).await, "");
+        assert_eq!(
+            simulator.received_times(),
+            vec!["TIME time=10:00:00.000 comp=3 split=0".to_owned()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scenario_with_async_client() {
+        let simulator = start_simulator(SCENARIO).await;
+        let addr = simulator.local_addr();
+        let (client, events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+
+        let changes = events
+            .filter_map(|event| async move {
+                match event {
+                    AquariusEvent::HeatListChanged(event) => Some(event),
+                    _ => None,
+                }
+            })
+            .take(3)
+            .collect::<Vec<_>>();
+        let changes = time::timeout(TEST_TIMEOUT, changes).await.unwrap();
+        assert_eq!((changes[0].heat.id, changes[0].opened), (2767, true));
+        assert_eq!((changes[1].heat.id, changes[1].opened), (2766, true));
+        assert_eq!(changes[1].heat.boats.as_ref().unwrap()[1].club, "Ruderclub Lübeck");
+        assert_eq!((changes[2].heat.id, changes[2].opened), (2767, false));
+
+        let open_heats = simulator.open_heats();
+        assert_eq!(open_heats.len(), 1);
+        assert_eq!(open_heats[0].id, 2766);
+
+        // the events and the replies share the same connection
+        let heats = client.read_open_heats().await.unwrap();
+        assert_eq!(heats.len(), 1);
+        assert_eq!(heats[0].id, 2766);
+        assert_eq!(heats[0].boats.as_ref().unwrap().len(), 2);
+
+        // times are only accepted for open heats
+        let start = Timestamp::new(Utc::now(), Split::Start, Some(3));
+        client.send_time(&start, None).await.unwrap();
+        let start = Timestamp::new(Utc::now(), Split::Start, Some(50));
+        let err = client.send_time(&start, None).await.unwrap_err();
+        assert!(matches!(err, AquariusErr::Rejected { reason } if reason == "Heat 50 is not open"));
+        assert_eq!(simulator.received_times().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unsolicited_events() {
+        let simulator = start_simulator("HEAT 3 2766 2\nOPEN 2766\nSTATE 2766 5\nRESULT 2766 2 1 7:12.34").await;
+        let addr = simulator.local_addr();
+        let (client, mut events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+
+        let mut next_event = async || time::timeout(TEST_TIMEOUT, events.next()).await.unwrap().unwrap();
+        let state = loop {
+            if let AquariusEvent::HeatStateChanged(event) = next_event().await {
+                break event;
+            }
+        };
+        assert_eq!((state.heat.id, state.heat.state), (2766, HeatState::Finished));
+        let AquariusEvent::ResultUpdated(result) = next_event().await else {
+            panic!("Expected a result");
+        };
+        assert_eq!((result.heat_id, result.bib, result.rank), (2766, 2, Some(1)));
+        assert_eq!(result.result, "7:12.34");
+
+        // accepted times are confirmed
+        let finish = Timestamp::new(Utc::now(), Split::Finish, Some(3));
+        client.send_time(&finish, Some(2)).await.unwrap();
+        let AquariusEvent::TimeConfirmed(confirmed) = next_event().await else {
+            panic!("Expected a time confirmation");
+        };
+        assert_eq!(
+            (confirmed.heat_nr, confirmed.split, confirmed.bib),
+            (3, Split::Finish, Some(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_utf_8_codec() {
+        let simulator = Simulator::start_with_codec("127.0.0.1:0", SCENARIO.parse().unwrap(), Codec::Utf8)
+            .await
+            .unwrap();
+        let addr = simulator.local_addr();
+        let (client, events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 100)
+            .await
+            .unwrap();
+        client.set_codec(Codec::Utf8);
+
+        // the start list of the opened heat is read with the event
+        let mut opened = events
+            .filter_map(|event| async move {
+                match event {
+                    AquariusEvent::HeatListChanged(event) if event.heat.id == 2766 => Some(event),
+                    _ => None,
+                }
+            })
+            .boxed();
+        let opened = time::timeout(TEST_TIMEOUT, opened.next()).await.unwrap().unwrap();
+        assert_eq!(opened.heat.boats.as_ref().unwrap()[1].club, "Ruderclub Lübeck");
+
+        let heats = client.read_open_heats().await.unwrap();
+        let heat = heats.iter().find(|heat| heat.id == 2766).unwrap();
+        assert_eq!(heat.boats.as_ref().unwrap()[1].club, "Ruderclub Lübeck");
+    }
+
+    #[tokio::test]
+    async fn test_non_ascii_command() {
+        let simulator = start_simulator("").await;
+        let (mut lines, mut writer) = connect(&simulator).await;
+
+        // Windows-1252 encoded umlaut in an unknown command
+        writer
+            .write_all(b"TIME time=10:00:00.000 comp=3 split=0 x=\xFC\r\n")
+            .await
+            .unwrap();
+        assert_eq!(next_line(&mut lines).await, "ERR Unknown heat 3");
+        assert_eq!(
+            simulator.received_times()[0],
+            "TIME time=10:00:00.000 comp=3 split=0 x=ü"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_outbox_sent_after_connect() {
+        // reserve a port, Aquarius is not running yet
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let path = std::env::temp_dir().join(format!("aquarius-simulator-outbox-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (client, events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 50)
+            .await
+            .unwrap();
+        client.set_outbox(Some(Outbox::open(&path).unwrap())).await;
+        let finish = Timestamp::new(Utc::now(), Split::Finish, Some(3));
+        let delivery = client.send_time(&finish, None).await.unwrap();
+        assert_eq!(delivery, Delivery::Queued { id: 1 });
+        assert_eq!(client.outbox_items().len(), 1);
+
+        // the queued time is sent as soon as Aquarius is connected
+        let simulator = Simulator::start(addr, Scenario::default()).await.unwrap();
+        time::timeout(TEST_TIMEOUT, async {
+            while simulator.received_times().is_empty() || !client.outbox_items().is_empty() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(simulator.received_times()[0].ends_with("comp=3 split=64"));
+
+        // the heat is unknown to Aquarius, so the time is reported as missing there
+        let mut rejected = events
+            .filter_map(|event| async move {
+                match event {
+                    AquariusEvent::QueuedTimeRejected { item, reason } => Some((item, reason)),
+                    _ => None,
+                }
+            })
+            .boxed();
+        let (item, reason) = time::timeout(TEST_TIMEOUT, rejected.next()).await.unwrap().unwrap();
+        assert_eq!(item.id, 1);
+        assert_eq!(reason, "Unknown heat 3");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_time_queued() {
+        // accepts the connection, but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let silent = tokio::spawn(async move { listener.accept().await.unwrap() });
+        let path = std::env::temp_dir().join(format!("aquarius-silent-outbox-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (client, _events) = AsyncAquariusClient::new(&addr.ip().to_string(), addr.port(), 50)
+            .await
+            .unwrap();
+        client.set_outbox(Some(Outbox::open(&path).unwrap())).await;
+        time::timeout(TEST_TIMEOUT, async {
+            while !client.state().is_connected() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let finish = Timestamp::new(Utc::now(), Split::Finish, Some(3));
+        let delivery = client.send_time(&finish, None).await.unwrap();
+        assert_eq!(delivery, Delivery::Queued { id: 1 });
+        assert_eq!(client.outbox_items().len(), 1);
+        drop(silent);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
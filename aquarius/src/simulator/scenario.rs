@@ -0,0 +1,154 @@
+use crate::error::AquariusErr;
+use crate::messages::{Boat, EventResultUpdated, Heat};
+use ::std::fs;
+use ::std::path::Path;
+use ::std::str::FromStr;
+use ::std::time::Duration;
+
+/// A single step of a scenario that is played by the simulator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Opens the heat with the given identifier and emits an `!OPEN+` event.
+    Open(u16),
+    /// Closes the heat with the given identifier and emits an `!OPEN-` event.
+    Close(u16),
+    /// Changes the state of the heat with the given identifier and emits a `!STATE` event.
+    State(u16, u8),
+    /// Emits a `!RESULT` event.
+    Result(EventResultUpdated),
+    /// Pauses the scenario for the given duration.
+    Wait(Duration),
+}
+
+/// A scripted scenario for the simulator. A scenario declares the heats known to the simulator, their start lists and
+/// the steps that are played once the first client has connected.
+///
+/// A scenario file contains one instruction per line, empty lines and lines starting with `#` are ignored:
+/// * `HEAT <number> <id> <state>` - declares a heat
+/// * `BOAT <heat id> <lane> <bib> <state> '<club>'` - adds a boat to the start list of a declared heat
+/// * `OPEN <heat id>` - opens a heat
+/// * `CLOSE <heat id>` - closes a heat
+/// * `STATE <heat id> <state>` - changes the state of a heat
+/// * `RESULT <heat id> <bib> <rank> <result>` - updates the result of a boat, rank `0` for boats without rank
+/// * `WAIT <milliseconds>` - pauses the scenario
+#[derive(Debug, Default, Clone)]
+pub struct Scenario {
+    /// The heats known to the simulator, including their start lists.
+    pub(super) heats: Vec<Heat>,
+    /// The steps to play.
+    pub(super) steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Loads a scenario from a file.
+    /// # Arguments
+    /// * `path` - The path of the scenario file.
+    /// # Returns
+    /// The scenario read from the file.
+    /// # Errors
+    /// If the file could not be read or contains an invalid instruction.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AquariusErr> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Returns the steps of the scenario.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = AquariusErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scenario = Scenario::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (instruction, args) = line.split_once(' ').unwrap_or((line, ""));
+            let args = args.trim();
+            match instruction {
+                "HEAT" => scenario.heats.push(args.parse()?),
+                "BOAT" => {
+                    let (heat_id, boat) = args
+                        .split_once(' ')
+                        .ok_or_else(|| AquariusErr::InvalidMessage(line.to_owned()))?;
+                    let heat_id: u16 = heat_id.parse()?;
+                    let boat = boat.parse::<Boat>()?;
+                    let heat = scenario
+                        .heats
+                        .iter_mut()
+                        .find(|heat| heat.id == heat_id)
+                        .ok_or_else(|| AquariusErr::InvalidMessage(line.to_owned()))?;
+                    heat.boats.get_or_insert_with(Vec::new).push(boat);
+                }
+                "OPEN" => scenario.steps.push(Step::Open(args.parse()?)),
+                "CLOSE" => scenario.steps.push(Step::Close(args.parse()?)),
+                "STATE" => {
+                    let (heat_id, state) = args
+                        .split_once(' ')
+                        .ok_or_else(|| AquariusErr::InvalidMessage(line.to_owned()))?;
+                    scenario
+                        .steps
+                        .push(Step::State(heat_id.parse()?, state.trim().parse()?));
+                }
+                "RESULT" => scenario.steps.push(Step::Result(format!("!{line}").parse()?)),
+                "WAIT" => scenario.steps.push(Step::Wait(Duration::from_millis(args.parse()?))),
+                _ => return Err(AquariusErr::InvalidMessage(line.to_owned())),
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = "
+        # two heats
+        HEAT 3 2766 4
+        BOAT 2766 1 1 0 'RV Neptun Konstanz'
+        BOAT 2766 2 2 0 'RG Heidelberg'
+        HEAT 50 2767 4
+
+        OPEN 2766
+        WAIT 1500
+        STATE 2766 5
+        RESULT 2766 2 1 7:12.34
+        CLOSE 2766
+    ";
+
+    #[test]
+    fn test_scenario_from_str() {
+        let scenario = SCENARIO.parse::<Scenario>().unwrap();
+        assert_eq!(scenario.heats.len(), 2);
+        assert_eq!(scenario.heats[0].id, 2766);
+        assert_eq!(scenario.heats[0].number, 3);
+        let boats = scenario.heats[0].boats.as_ref().unwrap();
+        assert_eq!(boats.len(), 2);
+        assert_eq!(boats[1].club, "RG Heidelberg");
+        assert!(scenario.heats[1].boats.is_none());
+        assert_eq!(
+            scenario.steps(),
+            &[
+                Step::Open(2766),
+                Step::Wait(Duration::from_millis(1500)),
+                Step::State(2766, 5),
+                Step::Result("!RESULT 2766 2 1 7:12.34".parse().unwrap()),
+                Step::Close(2766)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scenario_from_str_invalid() {
+        assert!("JUMP 1".parse::<Scenario>().is_err());
+        assert!("HEAT 3 2766".parse::<Scenario>().is_err());
+        assert!("BOAT 2766 1 1 0 'RG Heidelberg'".parse::<Scenario>().is_err());
+        assert!("WAIT soon".parse::<Scenario>().is_err());
+        assert!("STATE 2766".parse::<Scenario>().is_err());
+        assert!("RESULT 2766 2 first".parse::<Scenario>().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::endpoint::Endpoint;
+use ::chrono::{DateTime, Utc};
+use ::serde::Serialize;
+use ::std::fmt::{Display, Formatter, Result as FmtResult};
+use ::std::time::Duration;
+
+/// The state of the connection to the Aquarius application.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub enum ConnectionState {
+    /// The client is trying to connect to Aquarius.
+    #[default]
+    Connecting,
+
+    /// The client is connected to Aquarius.
+    Connected,
+
+    /// The client is not connected to Aquarius.
+    Disconnected {
+        /// The reason why the connection failed or was lost.
+        reason: String,
+    },
+
+    /// The client waits before it tries to connect to Aquarius again.
+    Backoff {
+        /// The time of the next attempt to connect.
+        next_attempt: DateTime<Utc>,
+    },
+}
+
+impl ConnectionState {
+    /// Returns `true` if the client is connected to Aquarius.
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected)
+    }
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ConnectionState::Connecting => write!(f, "Connecting"),
+            ConnectionState::Connected => write!(f, "Connected"),
+            ConnectionState::Disconnected { reason } => write!(f, "Disconnected: {reason}"),
+            ConnectionState::Backoff { next_attempt } => {
+                write!(f, "Reconnecting at {}", next_attempt.format("%H:%M:%S"))
+            }
+        }
+    }
+}
+
+/// A snapshot of the statistics of the connection to the Aquarius application.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConnectionStats {
+    /// The number of attempts to connect to Aquarius.
+    pub connect_attempts: u32,
+
+    /// The number of successful connections after the first one.
+    pub reconnects: u32,
+
+    /// The last error that caused a connection to fail or to be lost.
+    pub last_error: Option<String>,
+
+    /// The time the last line was received from Aquarius.
+    pub last_message_received: Option<DateTime<Utc>>,
+
+    /// The round-trip latency of the last request for the open heats.
+    pub open_heats_latency: Option<Duration>,
+
+    /// The endpoint Aquarius is currently connected to, or `None` if not connected.
+    pub active_endpoint: Option<Endpoint>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_state_display() {
+        assert_eq!(ConnectionState::Connected.to_string(), "Connected");
+        let disconnected = ConnectionState::Disconnected {
+            reason: "Connection refused".to_owned(),
+        };
+        assert_eq!(disconnected.to_string(), "Disconnected: Connection refused");
+        assert!(!disconnected.is_connected());
+        assert!(ConnectionState::Connected.is_connected());
+    }
+}
@@ -0,0 +1,15 @@
+-- Adds a surrogate key to HRV_Timestamp, so time stamps taken in the same millisecond, e.g. by two stations, or in
+-- two regattas stored in the same database are told apart. Updates and deletes are keyed by id and eventId.
+IF COL_LENGTH('HRV_Timestamp', 'id') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD id INT IDENTITY(1, 1) NOT NULL;
+END
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = 'UX_HRV_Timestamp_id')
+    CREATE UNIQUE INDEX UX_HRV_Timestamp_id ON HRV_Timestamp (id);
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = 'IX_HRV_Timestamp_eventId_timestamp')
+    CREATE INDEX IX_HRV_Timestamp_eventId_timestamp ON HRV_Timestamp (eventId, timestamp DESC);
+GO
@@ -0,0 +1,25 @@
+-- Adds an audit trail of the changes to HRV_Timestamp, so jury disputes can be resolved by who changed which time
+-- stamp when. Rows are only ever inserted, and are written by the same statement that changes the time stamp.
+IF OBJECT_ID('HRV_TimestampAudit', 'U') IS NULL
+BEGIN
+    CREATE TABLE HRV_TimestampAudit (
+        id          INT IDENTITY(1, 1) NOT NULL PRIMARY KEY,
+        timestampId INT                NOT NULL,
+        eventId     INT                NOT NULL,
+        action      NVARCHAR(16)       NOT NULL,
+        changedBy   NVARCHAR(128)      NOT NULL,
+        changedAt   DATETIME2(3)       NOT NULL,
+        timestamp   DATETIME2(3)       NOT NULL,
+        splitNr     TINYINT            NOT NULL,
+        oldHeatNr   SMALLINT           NULL,
+        newHeatNr   SMALLINT           NULL,
+        oldBib      TINYINT            NULL,
+        newBib      TINYINT            NULL,
+        previousId  INT                NULL
+    );
+END
+GO
+
+IF NOT EXISTS (SELECT 1 FROM sys.indexes WHERE name = 'IX_HRV_TimestampAudit_eventId_timestampId')
+    CREATE INDEX IX_HRV_TimestampAudit_eventId_timestampId ON HRV_TimestampAudit (eventId, timestampId);
+GO
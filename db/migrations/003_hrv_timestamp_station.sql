@@ -0,0 +1,13 @@
+-- Records which station took a time stamp and the uncorrected time of its clock. The timestamp column holds the time
+-- corrected by the clock offset of the station, so start and finish towers give comparable net times.
+IF COL_LENGTH('HRV_Timestamp', 'station') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD station NVARCHAR(64) NULL;
+END
+GO
+
+IF COL_LENGTH('HRV_Timestamp', 'rawTimestamp') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD rawTimestamp DATETIME2(3) NULL;
+END
+GO
@@ -0,0 +1,32 @@
+-- Records how a time stamp was taken, a free-text note and whether it was voided, e.g. after a false start. Voided time
+-- stamps are kept, but not used to rank the heats. The audit trail records the values after each change.
+IF COL_LENGTH('HRV_Timestamp', 'source') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD source NVARCHAR(16) NULL;
+END
+GO
+
+IF COL_LENGTH('HRV_Timestamp', 'note') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD note NVARCHAR(256) NULL;
+END
+GO
+
+IF COL_LENGTH('HRV_Timestamp', 'voided') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD voided BIT NOT NULL CONSTRAINT DF_HRV_Timestamp_voided DEFAULT 0;
+END
+GO
+
+IF COL_LENGTH('HRV_Timestamp', 'voidReason') IS NULL
+BEGIN
+    ALTER TABLE HRV_Timestamp ADD voidReason NVARCHAR(256) NULL;
+END
+GO
+
+IF COL_LENGTH('HRV_TimestampAudit', 'source') IS NULL
+BEGIN
+    ALTER TABLE HRV_TimestampAudit ADD source NVARCHAR(16) NULL, note NVARCHAR(256) NULL, voided BIT NULL,
+        voidReason NVARCHAR(256) NULL;
+END
+GO
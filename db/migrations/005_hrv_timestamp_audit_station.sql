@@ -0,0 +1,7 @@
+-- Records the station and the uncorrected time of its clock in the audit trail, so a deleted time stamp is restored
+-- with them and other stations learn them when syncing.
+IF COL_LENGTH('HRV_TimestampAudit', 'station') IS NULL
+BEGIN
+    ALTER TABLE HRV_TimestampAudit ADD station NVARCHAR(64) NULL, rawTimestamp DATETIME2(3) NULL;
+END
+GO
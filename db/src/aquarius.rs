@@ -19,6 +19,8 @@ use crate::cache::CacheStats;
 use crate::cache::Caches;
 use crate::error::DbError;
 use crate::tiberius::TiberiusPool;
+use crate::timekeeper::TimekeepingProtocol;
+use ::chrono::NaiveDate;
 use ::futures::future::join3;
 use ::std::time::{Duration, Instant};
 use ::tracing::debug;
@@ -336,6 +338,22 @@ impl Aquarius {
         )
     }
 
+    /// Returns the timekeeping protocol of a regatta day, or `None` if there is no such regatta. It is never cached, as
+    /// it is signed by the officials.
+    pub async fn get_timekeeping_protocol(
+        &self,
+        regatta_id: i32,
+        day: NaiveDate,
+        user_pool: &TiberiusPool,
+    ) -> Result<Option<TimekeepingProtocol>, DbError> {
+        timed_query!(
+            "Query timekeeping protocol from DB:",
+            TimekeepingProtocol::query(regatta_id, day, &mut *user_pool.get().await?).await,
+            regatta_id,
+            day = day.to_string()
+        )
+    }
+
     pub async fn create_notification(
         &self,
         regatta_id: i32,
@@ -6,17 +6,20 @@ use super::Referee;
 use super::TryToEntity;
 use super::age_class::ID as AGE_CLASS_ID;
 use super::boat_class::ID as BOAT_CLASS_ID;
+use super::entry::CANCELLED as ENTRY_CANCELLED;
 use super::entry::ID as ENTRY_ID;
 use super::get_row;
 use super::get_rows;
 use super::race::ID as RACE_ID;
+use super::state::HeatState;
 use crate::{
     error::DbError,
-    tiberius::{RowColumn, TiberiusPool, TryRowColumn},
+    tiberius::{RowColumn, TiberiusClient, TiberiusPool, TryRowColumn},
 };
 use ::chrono::{DateTime, Utc};
 use ::futures::future::join;
 use ::serde::Serialize;
+use ::std::collections::BTreeMap;
 use ::tiberius::{Query, Row};
 use ::utoipa::ToSchema;
 
@@ -37,7 +40,7 @@ pub struct Heat {
     pub id: i32,
 
     /// The sequential number of the heat.
-    number: i16,
+    pub(crate) number: i16,
 
     /// The race the heat belongs to.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -53,12 +56,15 @@ pub struct Heat {
 
     group_value: i16,
 
-    /// The state of the heat. Known values are:
-    /// 0 - scheduled, 1 - seeded, 2 - started, 3 - ???, 4 - official, 5 - finished, 6 - photo finish
-    state: u8,
+    /// The state of the heat, e.g. 2 - started
+    #[schema(value_type = u8)]
+    state: HeatState,
+
+    /// A stable key of the state of the heat, e.g. "started"
+    state_key: &'static str,
 
     /// Indicates whether or not the heat has been canceled.
-    cancelled: bool,
+    pub(crate) cancelled: bool,
 
     /// The umpires of this heat.
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -87,9 +93,10 @@ impl Heat {
     pub async fn query_heats_ready_to_start(regatta_id: i32, pool: &TiberiusPool) -> Result<Vec<Self>, DbError> {
         let sql = format!(
             "SELECT {0} FROM Comp c
-            WHERE c.Comp_Event_ID_FK = @P1 AND c.{STATE} = 1
+            WHERE c.Comp_Event_ID_FK = @P1 AND c.{STATE} = {1}
             ORDER BY c.{NUMBER} ASC",
-            Heat::select_columns("c")
+            Heat::select_columns("c"),
+            HeatState::Seeded.value()
         );
 
         let mut query = Query::new(sql);
@@ -103,9 +110,10 @@ impl Heat {
     pub async fn query_heats_started(regatta_id: i32, pool: &TiberiusPool) -> Result<Vec<Self>, DbError> {
         let sql = format!(
             "SELECT {0} FROM Comp c
-            WHERE c.Comp_Event_ID_FK = @P1 AND c.{STATE} = 2
+            WHERE c.Comp_Event_ID_FK = @P1 AND c.{STATE} = {1}
             ORDER BY c.{NUMBER} ASC",
-            Heat::select_columns("c")
+            Heat::select_columns("c"),
+            HeatState::Started.value()
         );
 
         let mut query = Query::new(sql);
@@ -190,6 +198,40 @@ impl Heat {
         Ok(heats.into_iter().map(|row| Heat::from(&row)).collect())
     }
 
+    /// Counts the boats of the heats of a regatta that have not been withdrawn.
+    /// # Arguments
+    /// * `regatta_id` - The regatta identifier
+    /// * `heat_nr` - The number of the heat to count the boats of, or `None` to count the boats of all heats
+    /// * `client` - The DB client to use
+    /// # Returns
+    /// The number of boats by the number of the heat, heats without boats have a count of 0
+    pub(crate) async fn query_boats(
+        regatta_id: i32,
+        heat_nr: Option<i16>,
+        client: &mut TiberiusClient,
+    ) -> Result<BTreeMap<i16, u8>, DbError> {
+        let sql = format!(
+            "SELECT c.{NUMBER}, COUNT(e.{ENTRY_ID}) AS Boats FROM Comp c
+            LEFT JOIN CompEntries ce ON ce.CE_Comp_ID_FK = c.{ID}
+            LEFT JOIN Entry        e ON e.{ENTRY_ID}     = ce.CE_Entry_ID_FK AND e.{ENTRY_CANCELLED} = 0
+            WHERE c.Comp_Event_ID_FK = @P1 AND (@P2 IS NULL OR c.{NUMBER} = @P2)
+            GROUP BY c.{ID}, c.{NUMBER}"
+        );
+
+        let mut query = Query::new(sql);
+        query.bind(regatta_id);
+        query.bind(heat_nr);
+
+        let rows = get_rows(query.query(client).await?).await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let boats: i32 = row.get_column("Boats");
+                (row.get_column(NUMBER), u8::try_from(boats).unwrap_or(u8::MAX))
+            })
+            .collect())
+    }
+
     /// Query a single heat.
     /// # Arguments
     /// * `heat_id` - The heat identifier
@@ -228,6 +270,7 @@ impl Heat {
 
 impl From<&Row> for Heat {
     fn from(value: &Row) -> Self {
+        let state = HeatState::from(<Row as RowColumn<u8>>::get_column(value, STATE));
         Heat {
             id: value.get_column(ID),
             race: value.try_to_entity(),
@@ -235,7 +278,8 @@ impl From<&Row> for Heat {
             round_code: value.get_column(ROUND_CODE),
             label: value.try_get_column(LABEL),
             group_value: value.get_column(GROUP_VALUE),
-            state: value.get_column(STATE),
+            state,
+            state_key: state.key(),
             cancelled: value.get_column(CANCELLED),
             date_time: value.try_get_column(DATE_TIME),
             referees: vec![],
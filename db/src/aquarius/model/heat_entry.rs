@@ -13,6 +13,7 @@ use super::heat::ROUND as HEAT_ROUND;
 use super::heat_result::HeatResult;
 use super::race::ID as RACE_ID;
 use super::race::Race;
+use super::state::HeatState;
 use crate::{
     error::DbError,
     tiberius::{RowColumn, TiberiusPool},
@@ -60,6 +61,8 @@ impl HeatEntry {
     /// # Returns
     /// A list of entries of the heat
     pub(crate) async fn query_entries_of_heat(heat: &Heat, pool: &TiberiusPool) -> Result<Vec<Self>, DbError> {
+        let official = HeatState::Official.value();
+        let started = HeatState::Started.value();
         let sql = format!("SELECT DISTINCT ce.CE_ID, ce.CE_Lane, {0}, Label_Short, {NUM_ROWERS}, {1}, {2}, {3}
             FROM CompEntries ce
             JOIN Comp                  ON           CE_Comp_ID_FK = {HEAT_ID}
@@ -70,7 +73,7 @@ impl HeatEntry {
             FULL OUTER JOIN Label      ON          EL_Label_ID_FK = Label_ID
             FULL OUTER JOIN Result r   ON       r.Result_CE_ID_FK = ce.CE_ID
             JOIN Club c                ON             c.{CLUB_ID} = Entry_OwnerClub_ID_FK
-            WHERE CE_Comp_ID_FK = @P1 AND ((Result_SplitNr = 64 AND Comp_State >=4) OR (Result_SplitNr = 0 AND Comp_State < 3) OR (Comp_State < 2 AND Result_SplitNr IS NULL))
+            WHERE CE_Comp_ID_FK = @P1 AND ((Result_SplitNr = 64 AND Comp_State >= {official}) OR (Result_SplitNr = 0 AND Comp_State <= {started}) OR (Comp_State < {started} AND Result_SplitNr IS NULL))
             AND EL_RoundFrom <= {HEAT_ROUND} AND {HEAT_ROUND} <= EL_RoundTo
             ORDER BY CE_Lane ASC",
             Entry::select_columns("e"), Club::select_all_columns("c"), Race::select_columns("o"), HeatResult::select_columns("r"));
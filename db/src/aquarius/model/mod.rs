@@ -16,6 +16,7 @@ mod referee;
 mod regatta;
 mod schedule;
 mod score;
+mod state;
 mod statistics;
 
 use crate::error::DbError;
@@ -40,6 +41,7 @@ pub use referee::Referee;
 pub use regatta::Regatta;
 pub use schedule::{Schedule, ScheduleEntry};
 pub use score::Score;
+pub use state::{BoatState, HeatState};
 pub use statistics::Statistics;
 
 pub trait TryToEntity<T> {
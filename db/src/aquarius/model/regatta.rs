@@ -60,6 +60,11 @@ impl From<&Row> for Regatta {
 }
 
 impl Regatta {
+    /// Returns the title of the regatta.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
     pub async fn query_active_regatta(client: &mut TiberiusClient) -> Result<Regatta, DbError> {
         let stream = Query::new(format!(
             "SELECT TOP 1 {} FROM Event e ORDER BY e.{START_DATE} DESC, e.{ID} DESC",
@@ -15,6 +15,7 @@ use super::get_rows;
 use super::heat::ID as HEAT_ID;
 use super::heat::ROUND as HEAT_ROUND;
 use super::race::ID as RACE_ID;
+use super::state::HeatState;
 use crate::tiberius::TiberiusClient;
 use crate::{
     error::DbError,
@@ -43,6 +44,7 @@ impl From<&Row> for Score {
 
 impl Score {
     pub async fn calculate(regatta_id: i32, client: &mut TiberiusClient) -> Result<Vec<Self>, DbError> {
+        let official = HeatState::Official.value();
         let mut query = Query::new(format!(
             "SELECT {CLUB_ID}, SUM(Points_Crew) as points, {CLUB_LONG_NAME}, {CLUB_CITY}, {CLUB_SHORT_NAME}, {CLUB_ABBREVIATION}, {CLUB_EXTERN_ID} FROM
               (SELECT {CLUB_ID}, {CLUB_LONG_NAME}, {CLUB_CITY}, {CLUB_SHORT_NAME}, {CLUB_ABBREVIATION}, {CLUB_EXTERN_ID},
@@ -63,7 +65,7 @@ impl Score {
               JOIN Offer       ON       {RACE_ID} = Comp_Race_ID_FK
               JOIN BoatClass   ON {BOAT_CLASS_ID} = Offer_BoatClass_ID_FK
               JOIN RaceMode    ON     RaceMode_ID = Offer_RaceMode_ID_FK
-              WHERE Offer_Event_ID_FK = @P1 AND {CREW_IS_COX} = 0 AND Result_SplitNr = 64 AND {CREW_ROUND_TO} = 64 AND Result_Rank > 0 AND {HEAT_ROUND} = 64 AND Comp_State = 4
+              WHERE Offer_Event_ID_FK = @P1 AND {CREW_IS_COX} = 0 AND Result_SplitNr = 64 AND {CREW_ROUND_TO} = 64 AND Result_Rank > 0 AND {HEAT_ROUND} = 64 AND Comp_State = {official}
             ) as t
             GROUP BY {CLUB_ID}, {CLUB_CITY}, {CLUB_LONG_NAME}, {CLUB_SHORT_NAME}, {CLUB_ABBREVIATION}, {CLUB_EXTERN_ID}
             ORDER BY points DESC",
@@ -0,0 +1,264 @@
+use ::serde::{Serialize, Serializer};
+use ::std::fmt::{Display, Formatter, Result as FmtResult};
+
+const HEAT_SCHEDULED: u8 = 0;
+const HEAT_SEEDED: u8 = 1;
+const HEAT_STARTED: u8 = 2;
+const HEAT_OFFICIAL: u8 = 4;
+const HEAT_FINISHED: u8 = 5;
+const HEAT_PHOTO_FINISH: u8 = 6;
+
+const BOAT_ENTERED: u8 = 0;
+const BOAT_DID_NOT_START: u8 = 1;
+const BOAT_DID_NOT_FINISH: u8 = 2;
+const BOAT_DISQUALIFIED: u8 = 3;
+
+/// The state of a heat as stored by Aquarius in the database and sent in its protocol. It is serialized as its numeric
+/// value, structs containing a state serialize its [`key`](HeatState::key) next to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeatState {
+    /// The heat is scheduled, the boats are not assigned to lanes yet.
+    #[default]
+    Scheduled,
+
+    /// The boats are assigned to lanes, the heat is ready to start.
+    Seeded,
+
+    /// The heat has been started.
+    Started,
+
+    /// The results of the heat are official.
+    Official,
+
+    /// The heat is finished.
+    Finished,
+
+    /// The heat is finished and the results are taken from the photo finish.
+    PhotoFinish,
+
+    /// A state unknown to this application.
+    Other(u8),
+}
+
+impl HeatState {
+    /// Returns the numeric value of the state as stored by Aquarius.
+    pub const fn value(&self) -> u8 {
+        match self {
+            HeatState::Scheduled => HEAT_SCHEDULED,
+            HeatState::Seeded => HEAT_SEEDED,
+            HeatState::Started => HEAT_STARTED,
+            HeatState::Official => HEAT_OFFICIAL,
+            HeatState::Finished => HEAT_FINISHED,
+            HeatState::PhotoFinish => HEAT_PHOTO_FINISH,
+            HeatState::Other(value) => *value,
+        }
+    }
+
+    /// Returns a stable key of the state that can be used by clients, e.g. for translations.
+    pub const fn key(&self) -> &'static str {
+        match self {
+            HeatState::Scheduled => "scheduled",
+            HeatState::Seeded => "seeded",
+            HeatState::Started => "started",
+            HeatState::Official => "official",
+            HeatState::Finished => "finished",
+            HeatState::PhotoFinish => "photoFinish",
+            HeatState::Other(_) => "unknown",
+        }
+    }
+
+    /// Returns the German label of the state.
+    pub const fn label_de(&self) -> &'static str {
+        match self {
+            HeatState::Scheduled => "Angesetzt",
+            HeatState::Seeded => "Gesetzt",
+            HeatState::Started => "Gestartet",
+            HeatState::Official => "Offiziell",
+            HeatState::Finished => "Beendet",
+            HeatState::PhotoFinish => "Zielfoto",
+            HeatState::Other(_) => "Unbekannt",
+        }
+    }
+
+    /// Returns the English label of the state.
+    pub const fn label_en(&self) -> &'static str {
+        match self {
+            HeatState::Scheduled => "Scheduled",
+            HeatState::Seeded => "Seeded",
+            HeatState::Started => "Started",
+            HeatState::Official => "Official",
+            HeatState::Finished => "Finished",
+            HeatState::PhotoFinish => "Photo finish",
+            HeatState::Other(_) => "Unknown",
+        }
+    }
+}
+
+impl From<u8> for HeatState {
+    fn from(value: u8) -> Self {
+        match value {
+            HEAT_SCHEDULED => HeatState::Scheduled,
+            HEAT_SEEDED => HeatState::Seeded,
+            HEAT_STARTED => HeatState::Started,
+            HEAT_OFFICIAL => HeatState::Official,
+            HEAT_FINISHED => HeatState::Finished,
+            HEAT_PHOTO_FINISH => HeatState::PhotoFinish,
+            _ => HeatState::Other(value),
+        }
+    }
+}
+
+impl From<HeatState> for u8 {
+    fn from(state: HeatState) -> Self {
+        state.value()
+    }
+}
+
+impl Display for HeatState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.label_de())
+    }
+}
+
+impl Serialize for HeatState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.value())
+    }
+}
+
+/// The state of a boat in a heat as sent by Aquarius in its start lists. It is serialized as its numeric value,
+/// structs containing a state serialize its [`key`](BoatState::key) next to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoatState {
+    /// The boat is entered in the heat.
+    #[default]
+    Entered,
+
+    /// The boat did not start.
+    DidNotStart,
+
+    /// The boat did not finish.
+    DidNotFinish,
+
+    /// The boat has been disqualified.
+    Disqualified,
+
+    /// A state unknown to this application.
+    Other(u8),
+}
+
+impl BoatState {
+    /// Returns the numeric value of the state as sent by Aquarius.
+    pub const fn value(&self) -> u8 {
+        match self {
+            BoatState::Entered => BOAT_ENTERED,
+            BoatState::DidNotStart => BOAT_DID_NOT_START,
+            BoatState::DidNotFinish => BOAT_DID_NOT_FINISH,
+            BoatState::Disqualified => BOAT_DISQUALIFIED,
+            BoatState::Other(value) => *value,
+        }
+    }
+
+    /// Returns a stable key of the state that can be used by clients, e.g. for translations.
+    pub const fn key(&self) -> &'static str {
+        match self {
+            BoatState::Entered => "entered",
+            BoatState::DidNotStart => "didNotStart",
+            BoatState::DidNotFinish => "didNotFinish",
+            BoatState::Disqualified => "disqualified",
+            BoatState::Other(_) => "unknown",
+        }
+    }
+
+    /// Returns the German label of the state.
+    pub const fn label_de(&self) -> &'static str {
+        match self {
+            BoatState::Entered => "Gemeldet",
+            BoatState::DidNotStart => "Nicht am Start",
+            BoatState::DidNotFinish => "Nicht im Ziel",
+            BoatState::Disqualified => "Disqualifiziert",
+            BoatState::Other(_) => "Unbekannt",
+        }
+    }
+
+    /// Returns the English label of the state.
+    pub const fn label_en(&self) -> &'static str {
+        match self {
+            BoatState::Entered => "Entered",
+            BoatState::DidNotStart => "Did not start",
+            BoatState::DidNotFinish => "Did not finish",
+            BoatState::Disqualified => "Disqualified",
+            BoatState::Other(_) => "Unknown",
+        }
+    }
+}
+
+impl From<u8> for BoatState {
+    fn from(value: u8) -> Self {
+        match value {
+            BOAT_ENTERED => BoatState::Entered,
+            BOAT_DID_NOT_START => BoatState::DidNotStart,
+            BOAT_DID_NOT_FINISH => BoatState::DidNotFinish,
+            BOAT_DISQUALIFIED => BoatState::Disqualified,
+            _ => BoatState::Other(value),
+        }
+    }
+}
+
+impl From<BoatState> for u8 {
+    fn from(state: BoatState) -> Self {
+        state.value()
+    }
+}
+
+impl Display for BoatState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.label_de())
+    }
+}
+
+impl Serialize for BoatState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_state_values() {
+        for value in 0..=10 {
+            assert_eq!(HeatState::from(value).value(), value);
+        }
+        assert_eq!(HeatState::from(2), HeatState::Started);
+        assert_eq!(HeatState::from(3), HeatState::Other(3));
+        assert_eq!(u8::from(HeatState::PhotoFinish), 6);
+    }
+
+    #[test]
+    fn test_heat_state_labels() {
+        assert_eq!(HeatState::Seeded.label_de(), "Gesetzt");
+        assert_eq!(HeatState::Seeded.label_en(), "Seeded");
+        assert_eq!(HeatState::Official.to_string(), "Offiziell");
+    }
+
+    #[test]
+    fn test_state_serialization() {
+        assert_eq!(serde_json::to_string(&HeatState::Started).unwrap(), "2");
+        assert_eq!(serde_json::to_string(&HeatState::Other(3)).unwrap(), "3");
+        assert_eq!(serde_json::to_string(&BoatState::DidNotStart).unwrap(), "1");
+        assert_eq!(HeatState::Other(3).key(), "unknown");
+        assert_eq!(BoatState::DidNotStart.key(), "didNotStart");
+    }
+
+    #[test]
+    fn test_boat_state_values() {
+        for value in 0..=10 {
+            assert_eq!(BoatState::from(value).value(), value);
+        }
+        assert_eq!(BoatState::from(0), BoatState::Entered);
+        assert_eq!(BoatState::Disqualified.label_en(), "Disqualified");
+    }
+}
@@ -15,6 +15,7 @@ use super::heat::STATE as HEAT_STATE;
 use super::race::CANCELLED as RACE_CANCELLED;
 use super::race::DRIVEN as RACE_DRIVEN;
 use super::race::ID as RACE_ID;
+use super::state::HeatState;
 use super::try_get_row;
 use crate::{
     error::DbError,
@@ -129,6 +130,12 @@ struct Athletes {
 
 impl Statistics {
     pub async fn query(regatta_id: i32, pool: &TiberiusPool) -> Result<Self, DbError> {
+        let official = HeatState::Official.value();
+        let finished = HeatState::Finished.value();
+        let photo_finish = HeatState::PhotoFinish.value();
+        let started = HeatState::Started.value();
+        let seeded = HeatState::Seeded.value();
+        let scheduled = HeatState::Scheduled.value();
         let mut query = Query::new(
         format!("SELECT
           (SELECT COUNT(*) FROM Offer WHERE Offer_Event_ID_FK = @P1) AS races_all,
@@ -136,11 +143,11 @@ impl Statistics {
           (SELECT COUNT(*) FROM Offer WHERE Offer_Event_ID_FK = @P1 AND ({RACE_CANCELLED} > 0 OR {RACE_DRIVEN} = 0)) AS races_cancelled,
           (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1) AS heats_all,
           (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_CANCELLED} > 0 ) AS heats_cancelled,
-          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = 4 AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_official,
-          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND ({HEAT_STATE} = 5 OR {HEAT_STATE} = 6) AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_finished,
-          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = 2 AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_started,
-          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = 1 AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_seeded,
-          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = 0 AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_scheduled,
+          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = {official} AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_official,
+          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND ({HEAT_STATE} = {finished} OR {HEAT_STATE} = {photo_finish}) AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_finished,
+          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = {started} AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_started,
+          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = {seeded} AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_seeded,
+          (SELECT COUNT(*) FROM Comp  WHERE Comp_Event_ID_FK  = @P1 AND {HEAT_STATE} = {scheduled} AND {HEAT_DATE_TIME} IS NOT NULL AND {HEAT_CANCELLED} = 0) AS heats_scheduled,
           (SELECT COUNT(*) FROM Entry WHERE Entry_Event_ID_FK = @P1) AS entries_all,
           (SELECT COUNT(*) FROM Entry WHERE Entry_Event_ID_FK = @P1 AND {ENTRY_CANCELLED} > 0) AS entries_cancelled,
           (SELECT COUNT(*) FROM (
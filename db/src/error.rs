@@ -18,6 +18,12 @@ pub enum DbError {
     /// Cache-related error.
     #[error("Cache error: {0}")]
     CacheError(#[from] CacheError),
+    /// I/O error, e.g. writing the journal of the time stamps.
+    #[error("I/O error: {0}")]
+    Io(#[from] ::std::io::Error),
+    /// JSON serialization error.
+    #[error("JSON error: {0}")]
+    Json(#[from] ::serde_json::Error),
     /// Custom error with message.
     #[error("Database error: {0}")]
     Custom(String),
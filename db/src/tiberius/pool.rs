@@ -1,5 +1,5 @@
 use crate::error::DbError;
-use crate::tiberius::TiberiusConnectionManager;
+use crate::tiberius::{TiberiusClient, TiberiusConnectionManager};
 use ::bb8::Pool;
 use ::bb8::PooledConnection;
 use ::bb8::State;
@@ -75,6 +75,15 @@ impl TiberiusPool {
         self.inner.get().await.map_err(DbError::from)
     }
 
+    /// Opens a connection that is not part of the pool, e.g. for a transaction. The connection is closed when it goes
+    /// out of scope, so a transaction that could neither be committed nor rolled back is never left open in the pool.
+    ///
+    /// # Returns
+    /// A new connection to the Tiberius database.
+    pub async fn dedicated_connection(&self) -> Result<TiberiusClient, DbError> {
+        Ok(self.inner.dedicated_connection().await?)
+    }
+
     /// Returns the current state of the global `TiberiusPool`.
     ///
     /// # Returns
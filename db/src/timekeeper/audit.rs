@@ -0,0 +1,372 @@
+use crate::aquarius::model::{get_row, get_rows};
+use crate::error::DbError;
+use crate::tiberius::{RowColumn, TiberiusClient, TryRowColumn};
+use crate::timekeeper::Split;
+use crate::timekeeper::TimestampSource;
+use crate::timekeeper::timestamp::parse_source;
+use ::chrono::{DateTime, Utc};
+use ::serde::Serialize;
+use ::strum_macros::{Display, IntoStaticStr};
+use ::tiberius::{Query, Row};
+use ::utoipa::ToSchema;
+
+const ID: &str = "id";
+const TIMESTAMP_ID: &str = "timestampId";
+const EVENT_ID: &str = "eventId";
+const ACTION: &str = "action";
+const CHANGED_BY: &str = "changedBy";
+const CHANGED_AT: &str = "changedAt";
+const TIMESTAMP: &str = "timestamp";
+const SPLIT_NR: &str = "splitNr";
+const OLD_HEAT_NR: &str = "oldHeatNr";
+const NEW_HEAT_NR: &str = "newHeatNr";
+const OLD_BIB: &str = "oldBib";
+const NEW_BIB: &str = "newBib";
+const PREVIOUS_ID: &str = "previousId";
+const SOURCE: &str = "source";
+const NOTE: &str = "note";
+const VOIDED: &str = "voided";
+const VOID_REASON: &str = "voidReason";
+const STATION: &str = "station";
+const RAW_TIMESTAMP: &str = "rawTimestamp";
+
+/// The kind of change recorded in the audit trail of the time stamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, IntoStaticStr, Serialize, ToSchema)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    /// A time stamp was taken.
+    Insert,
+
+    /// The heat number or bib of a time stamp was changed.
+    Update,
+
+    /// A time stamp was deleted.
+    Delete,
+
+    /// A deleted time stamp was restored, the restored time stamp gets a new ID.
+    Restore,
+
+    /// The last change of the heat number or bib of a time stamp was reverted.
+    Undo,
+
+    /// The note of a time stamp was changed, or it was voided or made valid again.
+    Annotate,
+}
+
+impl AuditAction {
+    fn parse(action: &str) -> Result<Self, DbError> {
+        match action {
+            "insert" => Ok(AuditAction::Insert),
+            "update" => Ok(AuditAction::Update),
+            "delete" => Ok(AuditAction::Delete),
+            "restore" => Ok(AuditAction::Restore),
+            "undo" => Ok(AuditAction::Undo),
+            "annotate" => Ok(AuditAction::Annotate),
+            _ => Err(DbError::Custom(format!("Unknown audit action: {action}"))),
+        }
+    }
+
+    /// Returns `true` if the action changed the heat number or bib of an existing time stamp.
+    pub fn is_reassignment(&self) -> bool {
+        matches!(self, AuditAction::Update | AuditAction::Undo)
+    }
+}
+
+/// An entry of the audit trail of the time stamps. Entries are never changed or deleted.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEntry {
+    /// The identifier of the entry.
+    pub id: i32,
+
+    /// The ID of the changed time stamp.
+    pub timestamp_id: i32,
+
+    /// The kind of change.
+    pub action: AuditAction,
+
+    /// The user or timekeeper station that made the change.
+    pub changed_by: String,
+
+    /// When the change was made, taken from the DB clock.
+    pub changed_at: DateTime<Utc>,
+
+    /// The time of the changed time stamp.
+    pub time: DateTime<Utc>,
+
+    /// The split of the changed time stamp.
+    #[schema(value_type = String)]
+    pub split: Split,
+
+    /// The heat number before the change.
+    pub old_heat_nr: Option<i16>,
+
+    /// The heat number after the change.
+    pub new_heat_nr: Option<i16>,
+
+    /// The bib before the change.
+    pub old_bib: Option<u8>,
+
+    /// The bib after the change.
+    pub new_bib: Option<u8>,
+
+    /// The ID of the deleted time stamp a restored one replaces.
+    pub previous_id: Option<i32>,
+
+    /// How the time stamp was taken, if known.
+    pub source: Option<TimestampSource>,
+
+    /// The note of the time stamp after the change, or when it was deleted.
+    pub note: Option<String>,
+
+    /// Whether the time stamp was void after the change, or when it was deleted.
+    pub voided: bool,
+
+    /// Why the time stamp was void after the change, or when it was deleted.
+    pub void_reason: Option<String>,
+
+    /// The name of the station that took the time stamp, if known.
+    pub station: Option<String>,
+
+    /// The time of the clock of the station before it was corrected by the clock offset of the station, if known.
+    pub raw_time: Option<DateTime<Utc>>,
+}
+
+impl AuditEntry {
+    /// Queries the audit trail of a time stamp, newest first. The trail of a restored time stamp includes the entries
+    /// of the deleted time stamps it was restored from.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `timestamp_id` - The ID of the time stamp.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_for_timestamp(
+        regatta_id: i32,
+        timestamp_id: i32,
+        client: &mut TiberiusClient,
+    ) -> Result<Vec<AuditEntry>, DbError> {
+        let mut query = Query::new(format!(
+            "WITH chain ({ID}) AS ( \
+                SELECT @P2 \
+                UNION ALL \
+                SELECT a.{PREVIOUS_ID} FROM HRV_TimestampAudit a JOIN chain c ON a.{TIMESTAMP_ID} = c.{ID} \
+                WHERE a.{EVENT_ID} = @P1 AND a.{ACTION} = @P3 \
+            ) \
+            SELECT {} FROM HRV_TimestampAudit \
+            WHERE {EVENT_ID} = @P1 AND {TIMESTAMP_ID} IN (SELECT {ID} FROM chain) \
+            ORDER BY {CHANGED_AT} DESC, {ID} DESC",
+            Self::select_columns()
+        ));
+        query.bind(regatta_id);
+        query.bind(timestamp_id);
+        query.bind(<&str>::from(AuditAction::Restore));
+
+        Self::from_query(query, client).await
+    }
+
+    /// Queries the deleted time stamps of a regatta that have not been restored yet, newest deletion first.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_deleted(
+        regatta_id: i32,
+        client: &mut TiberiusClient,
+    ) -> Result<Vec<AuditEntry>, DbError> {
+        let mut query = Query::new(format!(
+            "SELECT {} FROM HRV_TimestampAudit d \
+            WHERE {EVENT_ID} = @P1 AND {ACTION} = @P2 AND NOT EXISTS ( \
+                SELECT 1 FROM HRV_TimestampAudit r \
+                WHERE r.{EVENT_ID} = @P1 AND r.{ACTION} = @P3 AND r.{PREVIOUS_ID} = d.{TIMESTAMP_ID} \
+            ) \
+            ORDER BY {CHANGED_AT} DESC, {ID} DESC",
+            Self::select_columns()
+        ));
+        query.bind(regatta_id);
+        query.bind(<&str>::from(AuditAction::Delete));
+        query.bind(<&str>::from(AuditAction::Restore));
+
+        Self::from_query(query, client).await
+    }
+
+    /// Queries the ID of the newest entry of a regatta, the starting point to sync changes with
+    /// [`AuditEntry::query_since`].
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `client` - The DB client to use.
+    /// # Returns
+    /// The ID of the newest entry, or 0 if there is none.
+    pub(crate) async fn query_last_id(regatta_id: i32, client: &mut TiberiusClient) -> Result<i32, DbError> {
+        let mut query = Query::new(format!(
+            "SELECT ISNULL(MAX({ID}), 0) AS {ID} FROM HRV_TimestampAudit WHERE {EVENT_ID} = @P1"
+        ));
+        query.bind(regatta_id);
+
+        let row = get_row(query.query(client).await?).await?;
+        Ok(row.get_column(ID))
+    }
+
+    /// Queries the entries of a regatta made after the given entry, in the order they were made. Used as a change log
+    /// to sync the time stamps changed by other stations.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `after_id` - Only entries with a greater ID are returned.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_since(
+        regatta_id: i32,
+        after_id: i32,
+        client: &mut TiberiusClient,
+    ) -> Result<Vec<AuditEntry>, DbError> {
+        let mut query = Query::new(format!(
+            "SELECT {} FROM HRV_TimestampAudit WHERE {EVENT_ID} = @P1 AND {ID} > @P2 ORDER BY {ID} ASC",
+            Self::select_columns()
+        ));
+        query.bind(regatta_id);
+        query.bind(after_id);
+
+        Self::from_query(query, client).await
+    }
+
+    /// Queries the entries of a regatta for the time stamps taken in a time window, e.g. a regatta day, in the order
+    /// they were made. Changes made after the time window are included, e.g. corrections made on the next morning.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `from` - The start of the time window, inclusive.
+    /// * `to` - The end of the time window, exclusive.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_between(
+        regatta_id: i32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        client: &mut TiberiusClient,
+    ) -> Result<Vec<AuditEntry>, DbError> {
+        let mut query = Query::new(format!(
+            "SELECT {} FROM HRV_TimestampAudit \
+            WHERE {EVENT_ID} = @P1 AND {TIMESTAMP} >= @P2 AND {TIMESTAMP} < @P3 ORDER BY {ID} ASC",
+            Self::select_columns()
+        ));
+        query.bind(regatta_id);
+        query.bind(from);
+        query.bind(to);
+
+        Self::from_query(query, client).await
+    }
+
+    async fn from_query(query: Query<'_>, client: &mut TiberiusClient) -> Result<Vec<AuditEntry>, DbError> {
+        let rows = get_rows(query.query(client).await?).await?;
+        rows.iter().map(AuditEntry::try_from).collect()
+    }
+
+    fn select_columns() -> String {
+        [
+            ID,
+            TIMESTAMP_ID,
+            ACTION,
+            CHANGED_BY,
+            CHANGED_AT,
+            TIMESTAMP,
+            SPLIT_NR,
+            OLD_HEAT_NR,
+            NEW_HEAT_NR,
+            OLD_BIB,
+            NEW_BIB,
+            PREVIOUS_ID,
+            SOURCE,
+            NOTE,
+            VOIDED,
+            VOID_REASON,
+            STATION,
+            RAW_TIMESTAMP,
+        ]
+        .join(", ")
+    }
+}
+
+impl TryFrom<&Row> for AuditEntry {
+    type Error = DbError;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let action: String = row.get_column(ACTION);
+        let split_nr: u8 = row.get_column(SPLIT_NR);
+        Ok(AuditEntry {
+            id: row.get_column(ID),
+            timestamp_id: row.get_column(TIMESTAMP_ID),
+            action: AuditAction::parse(&action)?,
+            changed_by: row.get_column(CHANGED_BY),
+            changed_at: row.get_column(CHANGED_AT),
+            time: row.get_column(TIMESTAMP),
+            split: Split::from(split_nr),
+            old_heat_nr: row.try_get_column(OLD_HEAT_NR),
+            new_heat_nr: row.try_get_column(NEW_HEAT_NR),
+            old_bib: row.try_get_column(OLD_BIB),
+            new_bib: row.try_get_column(NEW_BIB),
+            previous_id: row.try_get_column(PREVIOUS_ID),
+            source: parse_source(row.try_get_column(SOURCE)),
+            note: row.try_get_column(NOTE),
+            voided: row.try_get_column(VOIDED).unwrap_or_default(),
+            void_reason: row.try_get_column(VOID_REASON),
+            station: row.try_get_column(STATION),
+            raw_time: row.try_get_column(RAW_TIMESTAMP),
+        })
+    }
+}
+
+/// Returns an `OUTPUT ... INTO HRV_TimestampAudit` clause that records a change of `HRV_Timestamp` in the same
+/// statement, so a change can't be made without its audit entry. The action, the author and the ID of a replaced time
+/// stamp are bound to the parameters starting at `param`. The source, note, void state, station and raw time are taken
+/// from `row`.
+/// # Arguments
+/// * `row` - The pseudo table holding the changed row, `INSERTED` or `DELETED`.
+/// * `old` - The pseudo table holding the old values, `None` for an inserted row.
+/// * `new` - The pseudo table holding the new values, `None` for a deleted row.
+/// * `param` - The number of the first parameter.
+pub(crate) fn output_into(row: &str, old: Option<&str>, new: Option<&str>, param: usize) -> String {
+    let column = |table: Option<&str>, column: &str| match table {
+        Some(table) => format!("{table}.{column}"),
+        None => "NULL".to_owned(),
+    };
+    format!(
+        "OUTPUT {row}.id, {row}.eventId, @P{param}, @P{}, SYSUTCDATETIME(), {row}.timestamp, {row}.splitNr, \
+        {}, {}, {}, {}, @P{}, {row}.source, {row}.note, {row}.voided, {row}.voidReason, {row}.station, \
+        {row}.rawTimestamp \
+        INTO HRV_TimestampAudit ({TIMESTAMP_ID}, {EVENT_ID}, {ACTION}, {CHANGED_BY}, {CHANGED_AT}, {TIMESTAMP}, \
+        {SPLIT_NR}, {OLD_HEAT_NR}, {NEW_HEAT_NR}, {OLD_BIB}, {NEW_BIB}, {PREVIOUS_ID}, {SOURCE}, {NOTE}, {VOIDED}, \
+        {VOID_REASON}, {STATION}, {RAW_TIMESTAMP})",
+        param + 1,
+        column(old, "heatNr"),
+        column(new, "heatNr"),
+        column(old, "bib"),
+        column(new, "bib"),
+        param + 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_action_names() {
+        for action in [
+            AuditAction::Insert,
+            AuditAction::Update,
+            AuditAction::Delete,
+            AuditAction::Restore,
+            AuditAction::Undo,
+            AuditAction::Annotate,
+        ] {
+            assert_eq!(AuditAction::parse(&action.to_string()).unwrap(), action);
+            assert_eq!(
+                serde_json::to_string(&action).unwrap(),
+                format!("\"{}\"", <&str>::from(action))
+            );
+        }
+        assert!(AuditAction::parse("merge").is_err());
+    }
+
+    #[test]
+    fn test_output_into() {
+        let clause = output_into("DELETED", Some("DELETED"), None, 3);
+        assert!(clause.starts_with("OUTPUT DELETED.id, DELETED.eventId, @P3, @P4, SYSUTCDATETIME(),"));
+        assert!(clause.contains("DELETED.heatNr, NULL, DELETED.bib, NULL, @P5, DELETED.source,"));
+        assert!(clause.ends_with("previousId, source, note, voided, voidReason, station, rawTimestamp)"));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::error::DbError;
+use crate::timekeeper::Timestamp;
+use crate::timekeeper::TimestampSource;
+use crate::timekeeper::timestamp::Split;
+use ::chrono::{DateTime, Utc};
+use ::serde::{Deserialize, Serialize};
+use ::std::fs::{self, File, OpenOptions};
+use ::std::io::{ErrorKind, Write};
+use ::std::path::{Path, PathBuf};
+use ::tracing::warn;
+
+/// A record of the journal, stored as one line of JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum JournalRecord {
+    /// A time stamp was taken.
+    Taken(JournalEntry),
+
+    /// The time stamp with the given sequence number was persisted in DB with the given ID.
+    Synced { seq: u64, id: i32 },
+
+    /// The time stamp with the given sequence number was deleted before it was persisted, it is not persisted anymore.
+    Discarded { seq: u64 },
+
+    /// The heat number or bib of the time stamp with the given sequence number was changed before it was persisted.
+    Reassigned {
+        seq: u64,
+        heat_nr: Option<i16>,
+        bib: Option<u8>,
+    },
+}
+
+/// A time stamp written to the journal before it is persisted in DB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The sequence number of the entry, unique within the journal.
+    pub seq: u64,
+
+    /// The ID of the regatta the time stamp belongs to, unknown if it was taken before the time strip was loaded.
+    pub regatta_id: Option<i32>,
+
+    /// The corrected time of the time stamp.
+    pub time: DateTime<Utc>,
+
+    /// The split number of the time stamp.
+    pub split_nr: u8,
+
+    /// The name of the station that took the time stamp.
+    pub station: Option<String>,
+
+    /// The time of the clock of the station before it was corrected.
+    pub raw_time: Option<DateTime<Utc>>,
+
+    /// How the time stamp was taken, missing in journals written by older versions.
+    #[serde(default)]
+    pub source: Option<TimestampSource>,
+
+    /// The heat number assigned before the time stamp was persisted, missing in journals written by older versions.
+    #[serde(default)]
+    pub heat_nr: Option<i16>,
+
+    /// The bib assigned before the time stamp was persisted, missing in journals written by older versions.
+    #[serde(default)]
+    pub bib: Option<u8>,
+}
+
+impl JournalEntry {
+    /// Creates the time stamp of the entry, it is not persisted yet.
+    pub(crate) fn to_timestamp(&self) -> Timestamp {
+        let mut timestamp = Timestamp::from_journal(
+            self.time,
+            Split::from(self.split_nr),
+            self.station.clone(),
+            self.raw_time,
+            self.source,
+        );
+        timestamp.reassign(self.heat_nr, self.bib);
+        timestamp
+    }
+}
+
+/// A local append-only journal of the time stamps taken by a station. Each time stamp is written to the journal before
+/// it is persisted in DB, and marked as synced afterwards. So no time stamp is lost if the DB is unreachable or the
+/// application crashes, the time stamps not synced yet are persisted again on the next start.
+pub struct Journal {
+    /// The file the records are appended to.
+    path: PathBuf,
+
+    /// The file opened for appending.
+    file: File,
+
+    /// The sequence number of the next entry.
+    next_seq: u64,
+
+    /// The entries that have not been synced yet, in the order they were taken.
+    pending: Vec<JournalEntry>,
+
+    /// Whether the last write failed and may have left an incomplete line.
+    torn: bool,
+}
+
+impl Journal {
+    /// Opens a journal stored in the given file, the file is created if it does not exist. Synced entries are removed
+    /// from the file, so it only grows while the DB is unreachable.
+    /// # Arguments
+    /// * `path` - The path of the journal file.
+    /// # Returns
+    /// The journal holding the entries that have not been synced yet.
+    /// # Errors
+    /// If the file exists but could not be read, or could not be rewritten.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DbError> {
+        let path = path.as_ref().to_path_buf();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut pending: Vec<JournalEntry> = Vec::new();
+        let mut next_seq = 1;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            // a crash while appending may leave the last line incomplete
+            match serde_json::from_str::<JournalRecord>(line) {
+                Ok(JournalRecord::Taken(entry)) => {
+                    next_seq = next_seq.max(entry.seq + 1);
+                    pending.push(entry);
+                }
+                Ok(JournalRecord::Synced { seq, .. } | JournalRecord::Discarded { seq }) => {
+                    pending.retain(|entry| entry.seq != seq)
+                }
+                Ok(JournalRecord::Reassigned { seq, heat_nr, bib }) => {
+                    if let Some(entry) = pending.iter_mut().find(|entry| entry.seq == seq) {
+                        entry.heat_nr = heat_nr;
+                        entry.bib = bib;
+                    }
+                }
+                Err(err) => warn!(%err, line, "Skipping unreadable journal record:"),
+            }
+        }
+
+        // rewrite the journal with the pending entries only, to a temporary file first so it is never left half written
+        let tmp_path = path.with_extension("tmp");
+        let mut compacted = String::new();
+        for entry in &pending {
+            compacted.push_str(&serde_json::to_string(&JournalRecord::Taken(entry.clone()))?);
+            compacted.push('\n');
+        }
+        fs::write(&tmp_path, compacted)?;
+        fs::rename(&tmp_path, &path)?;
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Journal {
+            path,
+            file,
+            next_seq,
+            pending,
+            torn: false,
+        })
+    }
+
+    /// Returns the path of the journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the entries that have not been synced yet, in the order they were taken.
+    pub fn pending(&self) -> &[JournalEntry] {
+        &self.pending
+    }
+
+    /// Appends a time stamp to the journal. The record is flushed to disk before this returns.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to, if known.
+    /// * `timestamp` - The time stamp taken.
+    /// # Returns
+    /// The sequence number of the entry.
+    /// # Errors
+    /// If the record could not be written.
+    pub(crate) fn append(&mut self, regatta_id: Option<i32>, timestamp: &Timestamp) -> Result<u64, DbError> {
+        let entry = JournalEntry {
+            seq: self.next_seq,
+            regatta_id,
+            time: timestamp.time,
+            split_nr: u8::from(timestamp.split()),
+            station: timestamp.station().map(str::to_owned),
+            raw_time: timestamp.raw_time(),
+            source: timestamp.source(),
+            heat_nr: timestamp.heat_nr(),
+            bib: timestamp.bib(),
+        };
+        self.write(&JournalRecord::Taken(entry.clone()))?;
+        self.next_seq += 1;
+        self.pending.push(entry);
+        Ok(self.next_seq - 1)
+    }
+
+    /// Marks an entry as synced, it is not persisted again.
+    /// # Arguments
+    /// * `seq` - The sequence number of the entry.
+    /// * `id` - The ID of the time stamp in DB.
+    /// # Errors
+    /// If the record could not be written.
+    pub(crate) fn mark_synced(&mut self, seq: u64, id: i32) -> Result<(), DbError> {
+        self.write(&JournalRecord::Synced { seq, id })?;
+        self.pending.retain(|entry| entry.seq != seq);
+        Ok(())
+    }
+
+    /// Marks an entry as discarded, e.g. if the time stamp was deleted before it was persisted. It is not persisted
+    /// anymore.
+    /// # Arguments
+    /// * `seq` - The sequence number of the entry.
+    /// # Errors
+    /// If the record could not be written.
+    pub(crate) fn discard(&mut self, seq: u64) -> Result<(), DbError> {
+        self.write(&JournalRecord::Discarded { seq })?;
+        self.pending.retain(|entry| entry.seq != seq);
+        Ok(())
+    }
+
+    /// Records the heat number and bib of an entry, e.g. if they were assigned while the DB was unreachable. They are
+    /// persisted with the time stamp, even after a restart.
+    /// # Arguments
+    /// * `seq` - The sequence number of the entry.
+    /// * `heat_nr` - The heat number of the time stamp.
+    /// * `bib` - The bib of the time stamp.
+    /// # Errors
+    /// If the record could not be written.
+    pub(crate) fn reassign(&mut self, seq: u64, heat_nr: Option<i16>, bib: Option<u8>) -> Result<(), DbError> {
+        self.write(&JournalRecord::Reassigned { seq, heat_nr, bib })?;
+        if let Some(entry) = self.pending.iter_mut().find(|entry| entry.seq == seq) {
+            entry.heat_nr = heat_nr;
+            entry.bib = bib;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, record: &JournalRecord) -> Result<(), DbError> {
+        // start a new line after an incomplete one, so the record can be read again
+        let mut line = if self.torn { String::from("\n") } else { String::new() };
+        line.push_str(&serde_json::to_string(record)?);
+        line.push('\n');
+        let written = self
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data());
+        self.torn = written.is_err();
+        Ok(written?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+
+    #[test]
+    fn test_journal_replay() {
+        let path = env::temp_dir().join(format!("timekeeper-journal-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        let mut journal = Journal::open(&path).unwrap();
+        assert!(journal.pending().is_empty());
+        let start = journal
+            .append(Some(12), &Timestamp::new(time, Split::Start, None))
+            .unwrap();
+        let finish = journal
+            .append(Some(12), &Timestamp::new(time, Split::Finish, None))
+            .unwrap();
+        assert_eq!((start, finish), (1, 2));
+        journal.mark_synced(start, 100).unwrap();
+        assert_eq!(journal.pending().len(), 1);
+
+        // a crash while appending leaves an incomplete line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"kind\":\"taken\",\"seq\":3,\"reg").unwrap();
+
+        // the entry not synced yet survives a restart, the journal is compacted
+        let mut journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.pending().len(), 1);
+        let entry = journal.pending()[0].clone();
+        assert_eq!((entry.seq, entry.regatta_id, entry.time), (finish, Some(12), time));
+        let timestamp = entry.to_timestamp();
+        assert_eq!(*timestamp.split(), Split::Finish);
+        assert!(!timestamp.is_persisted());
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        // sequence numbers are not reused
+        assert_eq!(journal.append(Some(12), &timestamp).unwrap(), 3);
+        journal.mark_synced(finish, 101).unwrap();
+        journal.mark_synced(3, 102).unwrap();
+        let journal = Journal::open(journal.path()).unwrap();
+        assert!(journal.pending().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,29 @@
+mod audit;
+mod journal;
+mod protocol;
+mod ranking;
+mod station;
+mod suggestion;
 mod timestamp;
 mod timestrip;
 
+pub use audit::AuditAction;
+pub use audit::AuditEntry;
+pub use journal::Journal;
+pub use journal::JournalEntry;
+pub use protocol::HeatProtocol;
+pub use protocol::ProtocolFormat;
+pub use protocol::TimekeepingProtocol;
+pub use ranking::HeatRanking;
+pub use ranking::ProvisionalResult;
+pub use ranking::RankingProblem;
+pub use station::Station;
+pub use suggestion::HeatCandidate;
+pub use suggestion::HeatSuggester;
+pub use suggestion::HeatSuggestion;
 pub use timestamp::Split;
 pub use timestamp::Timestamp;
+pub use timestamp::TimestampFilter;
+pub use timestamp::TimestampSource;
 pub use timestrip::TimeStrip;
+pub use timestrip::TimeStripChange;
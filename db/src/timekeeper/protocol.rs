@@ -0,0 +1,622 @@
+use crate::aquarius::model::{Heat, Regatta};
+use crate::error::DbError;
+use crate::tiberius::TiberiusClient;
+use crate::timekeeper::audit::{AuditAction, AuditEntry};
+use crate::timekeeper::ranking::{HeatRanking, ProvisionalResult, RankingProblem};
+use crate::timekeeper::timestamp::{Split, Timestamp, TimestampFilter};
+use ::chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use ::serde::{Deserialize, Serialize};
+use ::std::collections::{BTreeMap, BTreeSet};
+use ::std::fmt::Write;
+use ::strum_macros::Display;
+use ::utoipa::ToSchema;
+
+/// The maximum number of time stamps of a regatta day, far more than any day has.
+const MAX_DAY_TIME_STAMPS: i32 = 10_000;
+
+const TIME_FORMAT: &str = "%H:%M:%S%.3f";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const CSV_HEADER: &str = "heat,record,id,split,time,bib,station,rank,net_time,delta,changed_by,changed_at,\
+    old_heat,new_heat,old_bib,new_bib,source,note,voided,void_reason";
+
+/// The format a timekeeping protocol is rendered in, see [`TimekeepingProtocol::render`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, Deserialize, ToSchema)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolFormat {
+    /// A printable HTML report with space for the signatures of the officials.
+    #[default]
+    Html,
+
+    /// One line per time stamp and per change, to be processed with a spreadsheet.
+    Csv,
+
+    /// The protocol as JSON.
+    Json,
+}
+
+impl ProtocolFormat {
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProtocolFormat::Html => "html",
+            ProtocolFormat::Csv => "csv",
+            ProtocolFormat::Json => "json",
+        }
+    }
+
+    /// Returns the MIME content type of the format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProtocolFormat::Html => "text/html; charset=utf-8",
+            ProtocolFormat::Csv => "text/csv; charset=utf-8",
+            ProtocolFormat::Json => "application/json",
+        }
+    }
+}
+
+/// The time stamps of a heat taken on a regatta day, with their provisional ranking and changes.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HeatProtocol {
+    /// The number of the heat, `None` for the time stamps that have not been assigned to a heat.
+    pub heat_nr: Option<i16>,
+
+    /// The time stamps of the heat, oldest first.
+    pub time_stamps: Vec<Timestamp>,
+
+    /// The provisional ranking of the heat, `None` for the unassigned time stamps.
+    pub ranking: Option<HeatRanking>,
+
+    /// The changes of the heat numbers and bibs and the deletions of time stamps, in the order they were made. A
+    /// time stamp moved to another heat is listed in both heats.
+    pub edits: Vec<AuditEntry>,
+}
+
+impl HeatProtocol {
+    fn new(heat_nr: Option<i16>) -> Self {
+        HeatProtocol {
+            heat_nr,
+            time_stamps: Vec::new(),
+            ranking: None,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Returns the ranked result of a finish time stamp, if it is the ranked finish of its bib.
+    fn result_of(&self, timestamp: &Timestamp) -> Option<&ProvisionalResult> {
+        if *timestamp.split() != Split::Finish {
+            return None;
+        }
+        self.ranking
+            .as_ref()?
+            .results
+            .iter()
+            .find(|result| timestamp.bib() == Some(result.bib) && timestamp.time == result.finish)
+    }
+}
+
+/// The timekeeping protocol of a regatta day, signed by the officials after the day. It lists the time stamps of the
+/// day grouped by heat, with the net times and all edits and deletions.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimekeepingProtocol {
+    /// The ID of the regatta.
+    pub regatta_id: i32,
+
+    /// The title of the regatta.
+    pub regatta_title: String,
+
+    /// The regatta day, in local time.
+    pub day: NaiveDate,
+
+    /// When the protocol was created.
+    pub created_at: DateTime<Utc>,
+
+    /// The heats ordered by heat number, followed by the unassigned time stamps if there are any.
+    pub heats: Vec<HeatProtocol>,
+}
+
+impl TimekeepingProtocol {
+    /// Queries the time stamps and changes of a regatta day and compiles them into a protocol.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `day` - The regatta day, in local time.
+    /// * `client` - The DB client to use.
+    /// # Returns
+    /// The protocol, or `None` if there is no such regatta.
+    pub async fn query(regatta_id: i32, day: NaiveDate, client: &mut TiberiusClient) -> Result<Option<Self>, DbError> {
+        let Some(regatta) = Regatta::query_by_id(regatta_id, client).await? else {
+            return Ok(None);
+        };
+        let (from, to) = day_bounds(day);
+        let filter = TimestampFilter {
+            from: Some(from),
+            to: Some(to),
+            ..TimestampFilter::default()
+        };
+        let time_stamps =
+            Timestamp::query_for_regatta(regatta_id, &filter, None, 0, MAX_DAY_TIME_STAMPS, client).await?;
+        let edits = AuditEntry::query_between(regatta_id, from, to, client).await?;
+
+        let heat_nrs: BTreeSet<i16> = time_stamps.iter().filter_map(Timestamp::heat_nr).collect();
+        let boats: BTreeMap<i16, u8> = Heat::query_boats(regatta_id, None, client)
+            .await?
+            .into_iter()
+            .filter(|(heat_nr, _)| heat_nrs.contains(heat_nr))
+            .collect();
+
+        let mut protocol = Self::compile(day, time_stamps, edits, &boats);
+        protocol.regatta_id = regatta_id;
+        regatta.title().clone_into(&mut protocol.regatta_title);
+        Ok(Some(protocol))
+    }
+
+    /// Groups the time stamps and changes of a day by heat and ranks the heats.
+    /// # Arguments
+    /// * `day` - The regatta day.
+    /// * `time_stamps` - The time stamps of the day.
+    /// * `entries` - The audit trail of the time stamps of the day, inserts are left out.
+    /// * `boats` - The number of boats per heat, if known.
+    fn compile(
+        day: NaiveDate,
+        mut time_stamps: Vec<Timestamp>,
+        entries: Vec<AuditEntry>,
+        boats: &BTreeMap<i16, u8>,
+    ) -> Self {
+        time_stamps.sort_by_key(|timestamp| (timestamp.time, timestamp.id()));
+        let mut heats: BTreeMap<i16, HeatProtocol> = BTreeMap::new();
+        let mut unassigned = HeatProtocol::new(None);
+        for timestamp in time_stamps {
+            match timestamp.heat_nr() {
+                Some(heat_nr) => heats
+                    .entry(heat_nr)
+                    .or_insert_with(|| HeatProtocol::new(Some(heat_nr)))
+                    .time_stamps
+                    .push(timestamp),
+                None => unassigned.time_stamps.push(timestamp),
+            }
+        }
+        for entry in entries.into_iter().filter(|entry| entry.action != AuditAction::Insert) {
+            let heat_nrs: BTreeSet<i16> = entry.old_heat_nr.into_iter().chain(entry.new_heat_nr).collect();
+            if heat_nrs.is_empty() {
+                unassigned.edits.push(entry);
+                continue;
+            }
+            for heat_nr in heat_nrs {
+                heats
+                    .entry(heat_nr)
+                    .or_insert_with(|| HeatProtocol::new(Some(heat_nr)))
+                    .edits
+                    .push(entry.clone());
+            }
+        }
+
+        let mut heats: Vec<HeatProtocol> = heats
+            .into_iter()
+            .map(|(heat_nr, mut heat)| {
+                if !heat.time_stamps.is_empty() {
+                    heat.ranking = Some(HeatRanking::compute(
+                        heat_nr,
+                        &heat.time_stamps,
+                        boats.get(&heat_nr).copied(),
+                    ));
+                }
+                heat
+            })
+            .collect();
+        if !unassigned.time_stamps.is_empty() || !unassigned.edits.is_empty() {
+            heats.push(unassigned);
+        }
+
+        TimekeepingProtocol {
+            regatta_id: 0,
+            regatta_title: String::new(),
+            day,
+            created_at: Utc::now(),
+            heats,
+        }
+    }
+
+    /// Renders the protocol in the given format.
+    /// # Arguments
+    /// * `format` - The format to render.
+    /// # Errors
+    /// If the protocol could not be serialized to JSON.
+    pub fn render(&self, format: ProtocolFormat) -> Result<String, DbError> {
+        match format {
+            ProtocolFormat::Html => Ok(self.to_html()),
+            ProtocolFormat::Csv => Ok(self.to_csv()),
+            ProtocolFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    /// Returns the file name of the protocol in the given format, e.g. `timekeeping-12-2026-06-01.csv`.
+    pub fn file_name(&self, format: ProtocolFormat) -> String {
+        format!("timekeeping-{}-{}.{}", self.regatta_id, self.day, format.extension())
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for heat in &self.heats {
+            let heat_nr = heat.heat_nr.map(|heat_nr| heat_nr.to_string()).unwrap_or_default();
+            for timestamp in &heat.time_stamps {
+                let result = heat.result_of(timestamp);
+                let row = [
+                    heat_nr.clone(),
+                    "timestamp".to_owned(),
+                    optional(timestamp.id()),
+                    timestamp.split().to_string(),
+                    local_time(timestamp.time),
+                    optional(timestamp.bib()),
+                    timestamp.station().unwrap_or_default().to_owned(),
+                    optional(result.map(|result| result.rank)),
+                    result
+                        .map(|result| format_millis(result.net_time_ms))
+                        .unwrap_or_default(),
+                    result.map(|result| format_millis(result.delta_ms)).unwrap_or_default(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    optional(timestamp.source()),
+                    timestamp.note().unwrap_or_default().to_owned(),
+                    timestamp.is_voided().to_string(),
+                    timestamp.void_reason().unwrap_or_default().to_owned(),
+                ];
+                push_csv_row(&mut csv, &row);
+            }
+            for edit in &heat.edits {
+                let row = [
+                    heat_nr.clone(),
+                    edit.action.to_string(),
+                    edit.timestamp_id.to_string(),
+                    edit.split.to_string(),
+                    local_time(edit.time),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    edit.changed_by.clone(),
+                    edit.changed_at
+                        .with_timezone(&Local)
+                        .format(DATE_TIME_FORMAT)
+                        .to_string(),
+                    optional(edit.old_heat_nr),
+                    optional(edit.new_heat_nr),
+                    optional(edit.old_bib),
+                    optional(edit.new_bib),
+                    optional(edit.source),
+                    edit.note.clone().unwrap_or_default(),
+                    edit.voided.to_string(),
+                    edit.void_reason.clone().unwrap_or_default(),
+                ];
+                push_csv_row(&mut csv, &row);
+            }
+        }
+        csv
+    }
+
+    fn to_html(&self) -> String {
+        let title = format!(
+            "Zeitnahmeprotokoll {} – {}",
+            escape_html(&self.regatta_title),
+            self.day.format("%d.%m.%Y")
+        );
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"de\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+            <style>\n\
+            body {{ font-family: sans-serif; font-size: 10pt; }}\n\
+            table {{ border-collapse: collapse; width: 100%; margin-bottom: 1em; }}\n\
+            th, td {{ border: 1px solid #999; padding: 2px 6px; text-align: left; }}\n\
+            .problems {{ color: #b00; }}\n\
+            .void {{ color: #888; }}\n\
+            .signatures {{ display: flex; gap: 4em; margin-top: 4em; }}\n\
+            .signature {{ flex: 1; border-top: 1px solid #000; padding-top: 4px; }}\n\
+            @media print {{ section {{ break-inside: avoid; }} }}\n\
+            </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>Erstellt am {}</p>\n",
+            self.created_at.with_timezone(&Local).format(DATE_TIME_FORMAT)
+        );
+        for heat in &self.heats {
+            match heat.heat_nr {
+                Some(heat_nr) => {
+                    let _ = write!(html, "<section>\n<h2>Lauf {heat_nr}</h2>\n");
+                }
+                None => html.push_str("<section>\n<h2>Ohne Lauf</h2>\n"),
+            }
+            if let Some(ranking) = heat.ranking.as_ref().filter(|ranking| !ranking.problems.is_empty()) {
+                html.push_str("<ul class=\"problems\">\n");
+                for problem in &ranking.problems {
+                    let _ = writeln!(html, "<li>{}</li>", describe(problem));
+                }
+                html.push_str("</ul>\n");
+            }
+            if !heat.time_stamps.is_empty() {
+                html.push_str(
+                    "<table>\n<tr><th>Split</th><th>Zeit</th><th>Bug</th><th>Platz</th><th>Nettozeit</th>\
+                    <th>Rückstand</th><th>Station</th><th>ID</th><th>Bemerkung</th></tr>\n",
+                );
+                for timestamp in &heat.time_stamps {
+                    let result = heat.result_of(timestamp);
+                    let class = if timestamp.is_voided() { " class=\"void\"" } else { "" };
+                    let _ = writeln!(
+                        html,
+                        "<tr{class}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                        <td>{}</td></tr>",
+                        escape_html(&timestamp.split().to_string()),
+                        local_time(timestamp.time),
+                        optional(timestamp.bib()),
+                        optional(result.map(|result| result.rank)),
+                        result
+                            .map(|result| format_millis(result.net_time_ms))
+                            .unwrap_or_default(),
+                        result.map(|result| format_millis(result.delta_ms)).unwrap_or_default(),
+                        escape_html(timestamp.station().unwrap_or_default()),
+                        optional(timestamp.id()),
+                        escape_html(&remark(timestamp)),
+                    );
+                }
+                html.push_str("</table>\n");
+            }
+            if !heat.edits.is_empty() {
+                html.push_str(
+                    "<h3>Änderungen</h3>\n<table>\n<tr><th>Zeitpunkt</th><th>Von</th><th>Änderung</th><th>ID</th>\
+                    <th>Split</th><th>Zeit</th><th>Lauf</th><th>Bug</th></tr>\n",
+                );
+                for edit in &heat.edits {
+                    let _ = writeln!(
+                        html,
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{} → {}</td><td>{} → {}</td></tr>",
+                        edit.changed_at.with_timezone(&Local).format(DATE_TIME_FORMAT),
+                        escape_html(&edit.changed_by),
+                        action_label(edit.action),
+                        edit.timestamp_id,
+                        escape_html(&edit.split.to_string()),
+                        local_time(edit.time),
+                        optional(edit.old_heat_nr),
+                        optional(edit.new_heat_nr),
+                        optional(edit.old_bib),
+                        optional(edit.new_bib),
+                    );
+                }
+                html.push_str("</table>\n");
+            }
+            html.push_str("</section>\n");
+        }
+        html.push_str(
+            "<div class=\"signatures\">\n<div class=\"signature\">Zeitnehmer</div>\n\
+            <div class=\"signature\">Schiedsrichter</div>\n</div>\n</body>\n</html>\n",
+        );
+        html
+    }
+}
+
+/// Returns the start and the end of a day in local time.
+fn day_bounds(day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start_of = |day: NaiveDate| {
+        let midnight = day.and_time(NaiveTime::MIN);
+        midnight
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|time| time.to_utc())
+            .unwrap_or_else(|| midnight.and_utc())
+    };
+    (start_of(day), start_of(day.succ_opt().unwrap_or(day)))
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format(TIME_FORMAT).to_string()
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Formats a duration in milliseconds as minutes, seconds and milliseconds, e.g. `6:58.250`.
+fn format_millis(millis: i64) -> String {
+    let sign = if millis < 0 { "-" } else { "" };
+    let millis = millis.unsigned_abs();
+    format!(
+        "{sign}{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1_000 % 60,
+        millis % 1_000
+    )
+}
+
+fn push_csv_row(csv: &mut String, row: &[String]) {
+    let fields: Vec<String> = row
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn action_label(action: AuditAction) -> &'static str {
+    match action {
+        AuditAction::Insert => "Genommen",
+        AuditAction::Update => "Geändert",
+        AuditAction::Delete => "Gelöscht",
+        AuditAction::Restore => "Wiederhergestellt",
+        AuditAction::Undo => "Rückgängig",
+        AuditAction::Annotate => "Bemerkung",
+    }
+}
+
+/// Describes the note and the void state of a time stamp, e.g. `ungültig: Fehlstart`.
+fn remark(timestamp: &Timestamp) -> String {
+    let void = timestamp.is_voided().then(|| match timestamp.void_reason() {
+        Some(reason) => format!("ungültig: {reason}"),
+        None => "ungültig".to_owned(),
+    });
+    [void.as_deref(), timestamp.note()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+fn describe(problem: &RankingProblem) -> String {
+    match problem {
+        RankingProblem::MissingStart => "Keine Startzeit".to_owned(),
+        RankingProblem::DuplicateFinish { bib, count } => {
+            format!("Bug {bib} hat {count} Zielzeiten, die früheste ist gewertet")
+        }
+        RankingProblem::TooManyFinishes { finishes, boats } => {
+            format!("{finishes} Zielzeiten für {boats} Boote")
+        }
+        RankingProblem::MissingBib { count } => format!("{count} Zielzeiten ohne Bug"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::chrono::Duration;
+
+    fn at(millis: i64, split: Split, heat_nr: Option<i16>, bib: Option<u8>) -> Timestamp {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        let mut timestamp = Timestamp::new(time + Duration::milliseconds(millis), split, heat_nr);
+        if let Some(bib) = bib {
+            timestamp.set_bib(bib);
+        }
+        timestamp
+    }
+
+    fn edit(action: AuditAction, old_heat_nr: Option<i16>, new_heat_nr: Option<i16>) -> AuditEntry {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:07:00Z").unwrap().to_utc();
+        AuditEntry {
+            id: 1,
+            timestamp_id: 7,
+            action,
+            changed_by: "finish, \"tower\"".to_owned(),
+            changed_at: time,
+            time,
+            split: Split::Finish,
+            old_heat_nr,
+            new_heat_nr,
+            old_bib: None,
+            new_bib: None,
+            previous_id: None,
+            source: None,
+            note: None,
+            voided: false,
+            void_reason: None,
+            station: None,
+            raw_time: None,
+        }
+    }
+
+    fn protocol() -> TimekeepingProtocol {
+        let mut time_stamps = vec![
+            at(420_500, Split::Finish, Some(3), Some(2)),
+            at(0, Split::Start, Some(3), None),
+            at(418_250, Split::Finish, Some(3), Some(1)),
+            at(500_000, Split::Finish, None, None),
+            at(1_000, Split::Start, Some(3), None),
+        ];
+        time_stamps[4].set_void(Some("Fehlstart".to_owned()));
+        let entries = vec![
+            edit(AuditAction::Insert, None, Some(3)),
+            edit(AuditAction::Update, Some(3), Some(4)),
+            edit(AuditAction::Delete, None, None),
+        ];
+        let day = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        let mut protocol = TimekeepingProtocol::compile(day, time_stamps, entries, &BTreeMap::from([(3, 2)]));
+        protocol.regatta_id = 12;
+        protocol.regatta_title = "Frühregatta <Hamburg>".to_owned();
+        protocol
+    }
+
+    #[test]
+    fn test_protocol_groups_by_heat() {
+        let protocol = protocol();
+        let heat_nrs: Vec<Option<i16>> = protocol.heats.iter().map(|heat| heat.heat_nr).collect();
+        assert_eq!(heat_nrs, vec![Some(3), Some(4), None]);
+
+        let heat = &protocol.heats[0];
+        assert_eq!(*heat.time_stamps[0].split(), Split::Start);
+        assert_eq!(heat.edits.len(), 1);
+        let ranking = heat.ranking.as_ref().unwrap();
+        assert!(ranking.problems.is_empty());
+        assert_eq!(ranking.results[0].bib, 1);
+
+        // the heat a time stamp was moved to lists the change, but has nothing to rank
+        assert!(protocol.heats[1].time_stamps.is_empty());
+        assert!(protocol.heats[1].ranking.is_none());
+        assert_eq!(protocol.heats[1].edits[0].action, AuditAction::Update);
+
+        assert_eq!(protocol.heats[2].time_stamps.len(), 1);
+        assert_eq!(protocol.heats[2].edits[0].action, AuditAction::Delete);
+    }
+
+    #[test]
+    fn test_protocol_csv() {
+        let csv = protocol().render(ProtocolFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines.len(), 1 + 4 + 1 + 1 + 1 + 1);
+        assert!(lines[2].ends_with(",,true,Fehlstart"));
+        let finish: Vec<&str> = lines[3].split(',').collect();
+        assert_eq!(finish[0], "3");
+        assert_eq!(
+            &finish[3..6],
+            ["Ziel", &local_time(at(418_250, Split::Finish, None, None).time), "1"]
+        );
+        assert_eq!(&finish[7..10], ["1", "6:58.250", "0:00.000"]);
+        assert!(lines[5].starts_with("3,update,7,Ziel,"));
+        assert!(lines[5].contains(",\"finish, \"\"tower\"\"\","));
+        assert!(lines[5].ends_with(",3,4,,,,,false,"));
+    }
+
+    #[test]
+    fn test_protocol_html_and_json() {
+        let protocol = protocol();
+        let html = protocol.render(ProtocolFormat::Html).unwrap();
+        assert!(html.contains("<h1>Zeitnahmeprotokoll Frühregatta &lt;Hamburg&gt; – 01.06.2026</h1>"));
+        assert!(html.contains("<h2>Lauf 3</h2>"));
+        assert!(html.contains("<h2>Ohne Lauf</h2>"));
+        assert!(html.contains("<td>Gelöscht</td>"));
+        assert!(html.contains("<td>ungültig: Fehlstart</td>"));
+        assert!(html.contains("Schiedsrichter"));
+
+        let json: serde_json::Value = serde_json::from_str(&protocol.render(ProtocolFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["day"], "2026-06-01");
+        assert_eq!(json["heats"][0]["ranking"]["results"][1]["net_time_ms"], 420_500);
+        assert_eq!(protocol.file_name(ProtocolFormat::Csv), "timekeeping-12-2026-06-01.csv");
+    }
+
+    #[test]
+    fn test_format_millis() {
+        assert_eq!(format_millis(418_250), "6:58.250");
+        assert_eq!(format_millis(2_250), "0:02.250");
+        assert_eq!(format_millis(-1_500), "-0:01.500");
+    }
+}
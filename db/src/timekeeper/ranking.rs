@@ -0,0 +1,244 @@
+use crate::timekeeper::{Split, Timestamp};
+use ::chrono::{DateTime, Utc};
+use ::serde::Serialize;
+use ::std::collections::BTreeMap;
+use ::utoipa::ToSchema;
+
+/// The provisional result of a boat, computed from the start time stamp of its heat and its finish time stamp.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ProvisionalResult {
+    /// The rank of the boat, boats with the same net time share a rank.
+    pub rank: u8,
+
+    /// The bib of the boat.
+    pub bib: u8,
+
+    /// The finish time of the boat.
+    pub finish: DateTime<Utc>,
+
+    /// The net time from the start to the finish in milliseconds.
+    pub net_time_ms: i64,
+
+    /// The difference to the net time of the winner in milliseconds.
+    pub delta_ms: i64,
+}
+
+/// A problem found while ranking the time stamps of a heat. A heat with problems must not go official before the
+/// times are checked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub enum RankingProblem {
+    /// There is no start time stamp for the heat, so no net times can be computed.
+    MissingStart,
+
+    /// There are several finish time stamps for a bib, the earliest one is ranked.
+    DuplicateFinish {
+        /// The bib of the boat.
+        bib: u8,
+        /// The number of finish time stamps of the bib.
+        count: usize,
+    },
+
+    /// There are more finish time stamps than boats in the heat.
+    TooManyFinishes {
+        /// The number of finish time stamps.
+        finishes: usize,
+        /// The number of boats in the heat.
+        boats: u8,
+    },
+
+    /// Some finish time stamps have no bib yet and are not ranked.
+    MissingBib {
+        /// The number of finish time stamps without bib.
+        count: usize,
+    },
+}
+
+/// The provisional ranking of a heat, to be checked against Aquarius before the heat goes official.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct HeatRanking {
+    /// The number of the heat.
+    pub heat_nr: i16,
+
+    /// The start time of the heat. The latest start time stamp is used, e.g. after a false start.
+    pub start: Option<DateTime<Utc>>,
+
+    /// The ranked boats, fastest first.
+    pub results: Vec<ProvisionalResult>,
+
+    /// The problems found, empty if the ranking is complete.
+    pub problems: Vec<RankingProblem>,
+}
+
+impl HeatRanking {
+    /// Pairs the start time stamp of a heat with its finish time stamps and ranks the boats by their net time.
+    /// # Arguments
+    /// * `heat_nr` - The number of the heat.
+    /// * `time_stamps` - The time stamps, void ones and time stamps of other heats and intermediate splits are ignored.
+    /// * `boats` - The number of boats in the heat, if known.
+    pub fn compute<'a>(heat_nr: i16, time_stamps: impl IntoIterator<Item = &'a Timestamp>, boats: Option<u8>) -> Self {
+        let mut start: Option<DateTime<Utc>> = None;
+        let mut finishes: BTreeMap<u8, Vec<DateTime<Utc>>> = BTreeMap::new();
+        let mut without_bib = 0;
+        let mut finish_count = 0;
+        for timestamp in time_stamps
+            .into_iter()
+            .filter(|ts| ts.heat_nr() == Some(heat_nr) && !ts.is_voided())
+        {
+            match (timestamp.split(), timestamp.bib()) {
+                (Split::Start, _) => start = start.max(Some(timestamp.time)),
+                (Split::Finish, Some(bib)) => {
+                    finishes.entry(bib).or_default().push(timestamp.time);
+                    finish_count += 1;
+                }
+                (Split::Finish, None) => {
+                    without_bib += 1;
+                    finish_count += 1;
+                }
+                (Split::Intermediate(_), _) => {}
+            }
+        }
+
+        let mut problems = Vec::new();
+        if start.is_none() {
+            problems.push(RankingProblem::MissingStart);
+        }
+        for (bib, times) in finishes.iter().filter(|(_, times)| times.len() > 1) {
+            problems.push(RankingProblem::DuplicateFinish {
+                bib: *bib,
+                count: times.len(),
+            });
+        }
+        if let Some(boats) = boats
+            && finish_count > usize::from(boats)
+        {
+            problems.push(RankingProblem::TooManyFinishes {
+                finishes: finish_count,
+                boats,
+            });
+        }
+        if without_bib > 0 {
+            problems.push(RankingProblem::MissingBib { count: without_bib });
+        }
+
+        let results = start.map(|start| Self::rank(start, &finishes)).unwrap_or_default();
+        HeatRanking {
+            heat_nr,
+            start,
+            results,
+            problems,
+        }
+    }
+
+    /// Ranks the boats by the net time of their earliest finish time stamp.
+    fn rank(start: DateTime<Utc>, finishes: &BTreeMap<u8, Vec<DateTime<Utc>>>) -> Vec<ProvisionalResult> {
+        let mut results: Vec<ProvisionalResult> = finishes
+            .iter()
+            .filter_map(|(bib, times)| times.iter().min().map(|finish| (*bib, *finish)))
+            .map(|(bib, finish)| ProvisionalResult {
+                rank: 0,
+                bib,
+                finish,
+                net_time_ms: (finish - start).num_milliseconds(),
+                delta_ms: 0,
+            })
+            .collect();
+        results.sort_by_key(|result| (result.net_time_ms, result.bib));
+
+        let winner = results.first().map(|result| result.net_time_ms).unwrap_or_default();
+        let mut previous: Option<(i64, u8)> = None;
+        for (index, result) in results.iter_mut().enumerate() {
+            result.rank = match previous {
+                Some((net_time_ms, rank)) if net_time_ms == result.net_time_ms => rank,
+                _ => u8::try_from(index + 1).unwrap_or(u8::MAX),
+            };
+            result.delta_ms = result.net_time_ms - winner;
+            previous = Some((result.net_time_ms, result.rank));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::chrono::Duration;
+
+    fn at(millis: i64, split: Split, heat_nr: i16, bib: Option<u8>) -> Timestamp {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        let mut timestamp = Timestamp::new(time + Duration::milliseconds(millis), split, Some(heat_nr));
+        if let Some(bib) = bib {
+            timestamp.set_bib(bib);
+        }
+        timestamp
+    }
+
+    #[test]
+    fn test_ranking() {
+        let time_stamps = [
+            at(0, Split::Start, 3, None),
+            at(1_000, Split::Start, 4, None),
+            at(420_500, Split::Finish, 3, Some(2)),
+            at(418_250, Split::Finish, 3, Some(1)),
+            at(420_500, Split::Finish, 3, Some(3)),
+            at(210_000, Split::Intermediate(1), 3, Some(1)),
+            at(419_000, Split::Finish, 4, Some(1)),
+        ];
+        let ranking = HeatRanking::compute(3, &time_stamps, Some(3));
+        assert_eq!(ranking.start, Some(time_stamps[0].time));
+        assert!(ranking.problems.is_empty());
+        let ranks: Vec<(u8, u8, i64, i64)> = ranking
+            .results
+            .iter()
+            .map(|result| (result.rank, result.bib, result.net_time_ms, result.delta_ms))
+            .collect();
+        assert_eq!(
+            ranks,
+            vec![(1, 1, 418_250, 0), (2, 2, 420_500, 2_250), (2, 3, 420_500, 2_250)]
+        );
+    }
+
+    #[test]
+    fn test_ranking_problems() {
+        let time_stamps = [
+            at(418_250, Split::Finish, 3, Some(1)),
+            at(418_300, Split::Finish, 3, Some(1)),
+            at(420_500, Split::Finish, 3, None),
+        ];
+        let ranking = HeatRanking::compute(3, &time_stamps, Some(2));
+        assert!(ranking.results.is_empty());
+        assert_eq!(
+            ranking.problems,
+            vec![
+                RankingProblem::MissingStart,
+                RankingProblem::DuplicateFinish { bib: 1, count: 2 },
+                RankingProblem::TooManyFinishes { finishes: 3, boats: 2 },
+                RankingProblem::MissingBib { count: 1 },
+            ]
+        );
+
+        // with a start, the earliest of the duplicate finishes is ranked
+        let mut time_stamps = time_stamps.to_vec();
+        time_stamps.push(at(0, Split::Start, 3, None));
+        let ranking = HeatRanking::compute(3, &time_stamps, None);
+        assert_eq!(ranking.results.len(), 1);
+        assert_eq!(ranking.results[0].net_time_ms, 418_250);
+    }
+
+    #[test]
+    fn test_ranking_without_void() {
+        let mut false_start = at(1_000, Split::Start, 3, None);
+        false_start.set_void(Some("Fehlstart".to_owned()));
+        let mut duplicate = at(418_000, Split::Finish, 3, Some(1));
+        duplicate.set_void(Some("Doppelt".to_owned()));
+        let time_stamps = [
+            at(0, Split::Start, 3, None),
+            false_start,
+            duplicate,
+            at(418_250, Split::Finish, 3, Some(1)),
+        ];
+        let ranking = HeatRanking::compute(3, &time_stamps, Some(1));
+        assert_eq!(ranking.start, Some(time_stamps[0].time));
+        assert!(ranking.problems.is_empty());
+        assert_eq!(ranking.results[0].net_time_ms, 418_250);
+    }
+}
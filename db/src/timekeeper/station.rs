@@ -0,0 +1,130 @@
+use crate::aquarius::model::get_row;
+use crate::error::DbError;
+use crate::tiberius::{RowColumn, TiberiusClient};
+use ::chrono::{DateTime, TimeDelta, Utc};
+use ::serde::Serialize;
+use ::tiberius::Query;
+use ::utoipa::ToSchema;
+
+/// A station taking time stamps, e.g. the start or finish tower. Its clock is corrected by an offset, so the time
+/// stamps of all stations are comparable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Station {
+    /// The name of the station.
+    name: String,
+
+    /// The offset added to the clock of the station in milliseconds.
+    #[serde(rename = "clock_offset_ms", serialize_with = "serialize_millis")]
+    #[schema(value_type = i64)]
+    clock_offset: TimeDelta,
+}
+
+impl Station {
+    /// Creates a station.
+    /// # Arguments
+    /// * `name` - The name of the station.
+    /// * `clock_offset` - The offset added to the clock of the station.
+    pub fn new(name: &str, clock_offset: TimeDelta) -> Self {
+        Station {
+            name: name.to_owned(),
+            clock_offset,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn clock_offset(&self) -> TimeDelta {
+        self.clock_offset
+    }
+
+    pub(crate) fn set_clock_offset(&mut self, clock_offset: TimeDelta) {
+        self.clock_offset = clock_offset;
+    }
+
+    /// Corrects a time taken with the clock of the station.
+    /// # Arguments
+    /// * `raw_time` - The time of the clock of the station.
+    /// # Returns
+    /// The time of the reference clock.
+    pub fn correct(&self, raw_time: DateTime<Utc>) -> DateTime<Utc> {
+        raw_time + self.clock_offset
+    }
+
+    /// Measures the offset of the local clock to the clock of the DB server, which is the reference clock of all
+    /// stations. The round trip to the DB server is halved to estimate the local time the DB server time was taken at.
+    /// # Arguments
+    /// * `client` - The DB client to use.
+    pub(crate) async fn measure_clock_offset(client: &mut TiberiusClient) -> Result<TimeDelta, DbError> {
+        let before = Utc::now();
+        let db_time = Self::query_db_time(client).await?;
+        let after = Utc::now();
+        Ok(Self::offset_by_round_trip(before, db_time, after))
+    }
+
+    /// Calculates the offset of a clock to the clock of the DB server from a round trip. The DB server time is
+    /// estimated to be taken in the middle of the round trip.
+    /// # Arguments
+    /// * `sent` - The time the round trip started at, by the clock of the station.
+    /// * `db_time` - The time of the DB server.
+    /// * `received` - The time the round trip ended at, by the clock of the station.
+    /// # Returns
+    /// The offset to add to the clock of the station.
+    pub(crate) fn offset_by_round_trip(
+        sent: DateTime<Utc>,
+        db_time: DateTime<Utc>,
+        received: DateTime<Utc>,
+    ) -> TimeDelta {
+        db_time - (sent + (received - sent) / 2)
+    }
+
+    /// Queries the current time of the DB server.
+    /// # Arguments
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_db_time(client: &mut TiberiusClient) -> Result<DateTime<Utc>, DbError> {
+        let query = Query::new("SELECT SYSUTCDATETIME() AS now");
+        let row = get_row(query.query(client).await?).await?;
+        Ok(row.get_column("now"))
+    }
+}
+
+fn serialize_millis<S: ::serde::Serializer>(offset: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(offset.num_milliseconds())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correct_clock() {
+        let raw_time = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        let station = Station::new("finish", TimeDelta::milliseconds(-1_250));
+        assert_eq!(
+            station.correct(raw_time),
+            DateTime::parse_from_rfc3339("2026-06-01T09:59:58.750Z")
+                .unwrap()
+                .to_utc()
+        );
+        assert_eq!(
+            serde_json::to_value(&station).unwrap(),
+            serde_json::json!({"name": "finish", "clock_offset_ms": -1250})
+        );
+    }
+
+    #[test]
+    fn test_offset_by_round_trip() {
+        let sent = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        let db_time = DateTime::parse_from_rfc3339("2026-06-01T10:00:02.100Z")
+            .unwrap()
+            .to_utc();
+        let received = DateTime::parse_from_rfc3339("2026-06-01T10:00:00.200Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(
+            Station::offset_by_round_trip(sent, db_time, received),
+            TimeDelta::milliseconds(2_000)
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use crate::aquarius::model::Heat;
+use crate::error::DbError;
+use crate::tiberius::TiberiusPool;
+use crate::timekeeper::{Split, Timestamp};
+use ::chrono::{DateTime, Utc};
+use ::serde::Serialize;
+use ::std::collections::HashMap;
+use ::utoipa::ToSchema;
+
+/// The confidence of a suggestion without any ambiguity.
+const MAX_CONFIDENCE: f32 = 0.9;
+
+/// The factor the confidence is reduced by for each unassigned start time stamp in front of the suggested one.
+const START_DECAY: f32 = 0.8;
+
+/// The factor the confidence is reduced by for each heat passed while counting the boats in front of the suggested one.
+const FINISH_DECAY: f32 = 0.75;
+
+/// The factor the confidence is reduced by if the suggested heat is not open in Aquarius.
+const NOT_OPEN_PENALTY: f32 = 0.5;
+
+/// A heat a time stamp can be assigned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeatCandidate {
+    /// The number of the heat.
+    pub heat_nr: i16,
+
+    /// The number of boats in the heat.
+    pub boats: u8,
+}
+
+/// A heat number proposed for a time stamp.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct HeatSuggestion {
+    /// The proposed heat number.
+    pub heat_nr: i16,
+
+    /// How sure the suggestion is, from 0 (a guess) to 1 (certain).
+    pub confidence: f32,
+}
+
+/// Proposes heat numbers for unassigned time stamps. Start time stamps are proposed the next heat ready to start,
+/// finish and intermediate time stamps the earliest started heat that still misses times of some of its boats.
+#[derive(Debug, Clone, Default)]
+pub struct HeatSuggester {
+    /// The heats ready to start, in the order they start.
+    ready: Vec<HeatCandidate>,
+
+    /// The started heats, ordered by their number.
+    started: Vec<HeatCandidate>,
+
+    /// The numbers of the heats open in Aquarius, if known.
+    open: Option<Vec<i16>>,
+}
+
+impl HeatSuggester {
+    /// Creates a suggester for the given heats.
+    /// # Arguments
+    /// * `ready` - The heats ready to start, in the order they start.
+    /// * `started` - The started heats, ordered by their number.
+    pub fn new(ready: Vec<HeatCandidate>, started: Vec<HeatCandidate>) -> Self {
+        HeatSuggester {
+            ready,
+            started,
+            open: None,
+        }
+    }
+
+    /// Lowers the confidence of suggested heats that are not open in Aquarius.
+    /// # Arguments
+    /// * `heat_nrs` - The numbers of the heats open in Aquarius.
+    pub fn with_open_heats(mut self, heat_nrs: Vec<i16>) -> Self {
+        self.open = Some(heat_nrs);
+        self
+    }
+
+    /// Loads the heats of a regatta that are ready to start or started, with the number of their boats that have not
+    /// been withdrawn. Cancelled heats are left out.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `pool` - The DB connection pool.
+    pub(crate) async fn load(regatta_id: i32, pool: &TiberiusPool) -> Result<Self, DbError> {
+        let ready = Heat::query_heats_ready_to_start(regatta_id, pool).await?;
+        let started = Heat::query_heats_started(regatta_id, pool).await?;
+        let boats = Heat::query_boats(regatta_id, None, &mut *pool.get().await?).await?;
+        let candidates = |heats: Vec<Heat>| -> Vec<HeatCandidate> {
+            heats
+                .into_iter()
+                .filter(|heat| !heat.cancelled)
+                .map(|heat| HeatCandidate {
+                    heat_nr: heat.number,
+                    boats: boats.get(&heat.number).copied().unwrap_or_default(),
+                })
+                .collect()
+        };
+        Ok(HeatSuggester::new(candidates(ready), candidates(started)))
+    }
+
+    /// Proposes a heat number for an unassigned time stamp.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to propose a heat number for.
+    /// * `time_stamps` - The time stamps of the time strip, used to find the heats that already got their times. Void
+    ///   time stamps are ignored.
+    /// # Returns
+    /// The proposed heat number, or `None` if the time stamp is assigned already, is void or no heat fits.
+    pub fn suggest<'a>(
+        &self,
+        timestamp: &Timestamp,
+        time_stamps: impl IntoIterator<Item = &'a Timestamp>,
+    ) -> Option<HeatSuggestion> {
+        if timestamp.heat_nr().is_some() || timestamp.is_voided() {
+            return None;
+        }
+        let time_stamps: Vec<&Timestamp> = time_stamps.into_iter().filter(|ts| !ts.is_voided()).collect();
+        let same_split: Vec<&Timestamp> = time_stamps
+            .iter()
+            .copied()
+            .filter(|ts| ts.split() == timestamp.split())
+            .collect();
+        let suggestion = match timestamp.split() {
+            Split::Start => self.suggest_start(timestamp, &same_split),
+            _ => self.suggest_arrival(timestamp, &same_split, &Self::start_times(&time_stamps)),
+        }?;
+        Some(self.penalize_closed(suggestion))
+    }
+
+    /// Proposes the next heat ready to start. Unassigned start time stamps taken before are expected to belong to the
+    /// heats in front of it.
+    fn suggest_start(&self, timestamp: &Timestamp, starts: &[&Timestamp]) -> Option<HeatSuggestion> {
+        let last_assigned = Self::last_assigned_before(timestamp, starts);
+        let pending = Self::unassigned_between(last_assigned, timestamp, starts);
+        let started: Vec<i16> = starts.iter().filter_map(|ts| ts.heat_nr()).collect();
+        self.ready
+            .iter()
+            .filter(|heat| !started.contains(&heat.heat_nr))
+            .nth(pending)
+            .map(|heat| HeatSuggestion {
+                heat_nr: heat.heat_nr,
+                confidence: MAX_CONFIDENCE * START_DECAY.powi(i32::try_from(pending).unwrap_or(i32::MAX)),
+            })
+    }
+
+    /// Proposes the earliest started heat that still misses times of its boats at the split of the time stamp.
+    /// Unassigned time stamps taken before fill up the missing times first.
+    fn suggest_arrival(
+        &self,
+        timestamp: &Timestamp,
+        arrivals: &[&Timestamp],
+        start_times: &HashMap<i16, DateTime<Utc>>,
+    ) -> Option<HeatSuggestion> {
+        // heats with a start time stamp in order of their start, the others after them by their number. Heats that got
+        // a start time stamp count as started, even if Aquarius has not updated their state yet.
+        let mut heats: Vec<&HeatCandidate> = self
+            .started
+            .iter()
+            .chain(self.ready.iter().filter(|heat| start_times.contains_key(&heat.heat_nr)))
+            .filter(|heat| {
+                start_times
+                    .get(&heat.heat_nr)
+                    .is_none_or(|start| *start < timestamp.time)
+            })
+            .collect();
+        heats.sort_by_key(|heat| {
+            (
+                start_times.get(&heat.heat_nr).is_none(),
+                start_times.get(&heat.heat_nr).copied(),
+            )
+        });
+
+        let last_assigned = Self::last_assigned_before(timestamp, arrivals);
+        let mut pending = Self::unassigned_between(last_assigned, timestamp, arrivals);
+        let mut passed = 0;
+        for heat in heats {
+            let assigned = arrivals.iter().filter(|ts| ts.heat_nr() == Some(heat.heat_nr)).count();
+            let missing = usize::from(heat.boats).saturating_sub(assigned);
+            if pending < missing {
+                return Some(HeatSuggestion {
+                    heat_nr: heat.heat_nr,
+                    confidence: MAX_CONFIDENCE * FINISH_DECAY.powi(passed),
+                });
+            }
+            pending -= missing;
+            if missing > 0 {
+                passed += 1;
+            }
+        }
+        None
+    }
+
+    /// The start times of the heats, taken from the assigned start time stamps. The latest one wins, e.g. after a
+    /// false start.
+    fn start_times(time_stamps: &[&Timestamp]) -> HashMap<i16, DateTime<Utc>> {
+        let mut start_times = HashMap::new();
+        for ts in time_stamps.iter().filter(|ts| *ts.split() == Split::Start) {
+            if let Some(heat_nr) = ts.heat_nr() {
+                let start = start_times.entry(heat_nr).or_insert(ts.time);
+                *start = (*start).max(ts.time);
+            }
+        }
+        start_times
+    }
+
+    fn penalize_closed(&self, suggestion: HeatSuggestion) -> HeatSuggestion {
+        match &self.open {
+            Some(open) if !open.contains(&suggestion.heat_nr) => HeatSuggestion {
+                confidence: suggestion.confidence * NOT_OPEN_PENALTY,
+                ..suggestion
+            },
+            _ => suggestion,
+        }
+    }
+
+    /// The time of the newest assigned time stamp taken before the given one.
+    fn last_assigned_before(timestamp: &Timestamp, time_stamps: &[&Timestamp]) -> Option<DateTime<Utc>> {
+        time_stamps
+            .iter()
+            .filter(|ts| ts.heat_nr().is_some() && ts.time < timestamp.time)
+            .map(|ts| ts.time)
+            .max()
+    }
+
+    /// The number of unassigned time stamps taken after `after` and before the given one. Unassigned time stamps in
+    /// front of an assigned one are ignored, e.g. false starts.
+    fn unassigned_between(after: Option<DateTime<Utc>>, timestamp: &Timestamp, time_stamps: &[&Timestamp]) -> usize {
+        time_stamps
+            .iter()
+            .filter(|ts| {
+                ts.heat_nr().is_none() && ts.time < timestamp.time && after.is_none_or(|after| ts.time > after)
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::chrono::Duration;
+
+    fn at(seconds: i64, split: Split, heat_nr: Option<i16>) -> Timestamp {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:00:00Z").unwrap().to_utc();
+        Timestamp::new(time + Duration::seconds(seconds), split, heat_nr)
+    }
+
+    fn heat(heat_nr: i16, boats: u8) -> HeatCandidate {
+        HeatCandidate { heat_nr, boats }
+    }
+
+    #[test]
+    fn test_suggest_start() {
+        let suggester = HeatSuggester::new(vec![heat(3, 4), heat(4, 4), heat(5, 2)], vec![]);
+        let first = at(0, Split::Start, Some(3));
+        let second = at(60, Split::Start, None);
+        let third = at(120, Split::Start, None);
+        let time_stamps = [third.clone(), second.clone(), first.clone()];
+
+        let suggestion = suggester.suggest(&second, &time_stamps).unwrap();
+        assert_eq!(suggestion.heat_nr, 4);
+        assert_eq!(suggestion.confidence, MAX_CONFIDENCE);
+        // the second start is not assigned yet, so the third one is less certain
+        let suggestion = suggester.suggest(&third, &time_stamps).unwrap();
+        assert_eq!(suggestion.heat_nr, 5);
+        assert!(suggestion.confidence < MAX_CONFIDENCE);
+        // assigned time stamps get no suggestion
+        assert!(suggester.suggest(&first, &time_stamps).is_none());
+    }
+
+    #[test]
+    fn test_suggest_finish() {
+        let suggester = HeatSuggester::new(vec![heat(4, 2)], vec![heat(2, 2), heat(3, 1)]);
+        let start_3 = at(0, Split::Start, Some(3));
+        let start_2 = at(30, Split::Start, Some(2));
+        let start_4 = at(400, Split::Start, Some(4));
+        let finish_1 = at(410, Split::Finish, Some(3));
+        let finish_2 = at(420, Split::Finish, None);
+        let finish_3 = at(425, Split::Finish, None);
+        let finish_4 = at(430, Split::Finish, None);
+        let time_stamps = [
+            finish_4.clone(),
+            finish_3.clone(),
+            finish_2.clone(),
+            finish_1,
+            start_4,
+            start_2,
+            start_3,
+        ];
+
+        // heat 3 started first and got its only finish, so heat 2 is next
+        let suggestion = suggester.suggest(&finish_2, &time_stamps).unwrap();
+        assert_eq!((suggestion.heat_nr, suggestion.confidence), (2, MAX_CONFIDENCE));
+        assert_eq!(suggester.suggest(&finish_3, &time_stamps).unwrap().heat_nr, 2);
+        // heat 2 is complete with the unassigned finishes in front, heat 4 started by its time stamp
+        let suggestion = suggester.suggest(&finish_4, &time_stamps).unwrap();
+        assert_eq!(suggestion.heat_nr, 4);
+        assert!(suggestion.confidence < MAX_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_suggest_not_open_in_aquarius() {
+        let suggester = HeatSuggester::new(vec![heat(3, 4)], vec![]).with_open_heats(vec![7]);
+        let start = at(0, Split::Start, None);
+        let suggestion = suggester.suggest(&start, [&start]).unwrap();
+        assert_eq!(suggestion.heat_nr, 3);
+        assert_eq!(suggestion.confidence, MAX_CONFIDENCE * NOT_OPEN_PENALTY);
+        assert!(HeatSuggester::default().suggest(&start, [&start]).is_none());
+    }
+
+    #[test]
+    fn test_suggest_without_void() {
+        let suggester = HeatSuggester::new(vec![heat(3, 4), heat(4, 4)], vec![]);
+        let mut false_start = at(0, Split::Start, None);
+        false_start.set_void(Some("Fehlstart".to_owned()));
+        let start = at(60, Split::Start, None);
+        let time_stamps = [start.clone(), false_start.clone()];
+
+        // the void start is not expected to belong to a heat in front
+        let suggestion = suggester.suggest(&start, &time_stamps).unwrap();
+        assert_eq!((suggestion.heat_nr, suggestion.confidence), (3, MAX_CONFIDENCE));
+        assert!(suggester.suggest(&false_start, &time_stamps).is_none());
+    }
+}
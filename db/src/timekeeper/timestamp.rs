@@ -1,28 +1,59 @@
-use crate::aquarius::model::get_rows;
+use crate::aquarius::model::{get_row, get_rows};
 use crate::tiberius::TiberiusClient;
+use crate::timekeeper::Station;
+use crate::timekeeper::audit::{AuditAction, AuditEntry, output_into};
 use crate::{
     error::DbError,
     tiberius::{RowColumn, TryRowColumn},
 };
-use ::chrono::{DateTime, Utc};
-use ::serde::Serialize;
-use ::strum_macros::Display;
+use ::chrono::{DateTime, TimeDelta, Utc};
+use ::serde::{Deserialize, Serialize, Serializer};
+use ::strum_macros::{Display, IntoStaticStr};
 use ::tiberius::{Query, Row};
 use ::utoipa::ToSchema;
 
+const ID: &str = "id";
 const TIMESTAMP: &str = "timestamp";
 const EVENT_ID: &str = "eventId";
 const SPLIT_NR: &str = "splitNr";
 const HEAT_NR: &str = "heatNr";
 const BIB: &str = "bib";
+const STATION: &str = "station";
+const RAW_TIMESTAMP: &str = "rawTimestamp";
+const SOURCE: &str = "source";
+const NOTE: &str = "note";
+const VOIDED: &str = "voided";
+const VOID_REASON: &str = "voidReason";
+
+/// How a time stamp was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, IntoStaticStr, Serialize, Deserialize, ToSchema)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    /// Taken with a key of the timekeeper.
+    Keyboard,
+
+    /// Taken in the browser and sent through the timekeeping websocket.
+    Websocket,
+
+    /// Taken by a hardware trigger, e.g. a light barrier or a start pistol.
+    Hardware,
+
+    /// Entered by hand, e.g. from a backup stopwatch.
+    Manual,
+}
 
 /// A time stamp of an event, such as a start or finish time stamp in a race.
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Timestamp {
+    /// The identifier of the time stamp in DB, `None` until it is persisted.
+    id: Option<i32>,
+
     /// The time of the event.
     pub time: DateTime<Utc>,
 
-    /// The split of the time stamp. Either start or finish.
+    /// The split of the time stamp, e.g. start, an intermediate split or finish.
+    #[schema(value_type = String)]
     split: Split,
 
     /// The optional heat number.
@@ -31,21 +62,120 @@ pub struct Timestamp {
     /// The optional bib number.
     bib: Option<u8>,
 
+    /// The name of the station that took the time stamp, if known.
+    station: Option<String>,
+
+    /// The time of the clock of the station before it was corrected by the clock offset of the station, if known.
+    raw_time: Option<DateTime<Utc>>,
+
+    /// How the time stamp was taken, if known.
+    source: Option<TimestampSource>,
+
+    /// A free-text note, e.g. "photo finish needed".
+    note: Option<String>,
+
+    /// Whether the time stamp is void, e.g. after a false start. Void time stamps are not used to rank the heats.
+    voided: bool,
+
+    /// Why the time stamp is void.
+    void_reason: Option<String>,
+
     /// Whether the time stamp is persisted in DB or not.
     persisted: bool,
 }
 
 impl Timestamp {
+    /// Creates a new time stamp that is not persisted yet.
+    /// # Arguments
+    /// * `time` - The time of the event.
+    /// * `split` - The split of the time stamp.
+    /// * `heat_nr` - The optional heat number.
+    pub fn new(time: DateTime<Utc>, split: Split, heat_nr: Option<i16>) -> Timestamp {
+        Timestamp {
+            heat_nr,
+            ..Timestamp::from_time(time, split)
+        }
+    }
+
     pub(crate) fn from_time(time: DateTime<Utc>, split: Split) -> Timestamp {
         Timestamp {
+            id: None,
             time,
             split,
             heat_nr: None,
             bib: None,
+            station: None,
+            raw_time: None,
+            source: None,
+            note: None,
+            voided: false,
+            void_reason: None,
             persisted: false,
         }
     }
 
+    /// Creates a time stamp taken by a station, its time is corrected by the clock offset of the station. The time of a
+    /// timing device is taken by the clock of the device, it is not corrected.
+    /// # Arguments
+    /// * `station` - The station taking the time stamp.
+    /// * `raw_time` - The time of the clock of the station, or of the timing device.
+    /// * `split` - The split of the time stamp.
+    /// * `source` - How the time stamp was taken.
+    pub(crate) fn from_station(
+        station: &Station,
+        raw_time: DateTime<Utc>,
+        split: Split,
+        source: TimestampSource,
+    ) -> Timestamp {
+        Timestamp {
+            station: Some(station.name().to_owned()),
+            raw_time: Some(raw_time),
+            source: Some(source),
+            ..match source {
+                TimestampSource::Hardware => Timestamp::from_time(raw_time, split),
+                _ => Timestamp::from_time(station.correct(raw_time), split),
+            }
+        }
+    }
+
+    /// Creates a time stamp read from the journal of a station, it is not persisted yet.
+    pub(crate) fn from_journal(
+        time: DateTime<Utc>,
+        split: Split,
+        station: Option<String>,
+        raw_time: Option<DateTime<Utc>>,
+        source: Option<TimestampSource>,
+    ) -> Timestamp {
+        Timestamp {
+            station,
+            raw_time,
+            source,
+            ..Timestamp::from_time(time, split)
+        }
+    }
+
+    /// Creates a time stamp inserted or restored by another station from its audit entry.
+    pub(crate) fn from_insert(entry: &AuditEntry) -> Self {
+        Timestamp {
+            id: Some(entry.timestamp_id),
+            heat_nr: entry.new_heat_nr,
+            bib: entry.new_bib,
+            source: entry.source,
+            note: entry.note.clone(),
+            voided: entry.voided,
+            void_reason: entry.void_reason.clone(),
+            station: entry.station.clone(),
+            raw_time: entry.raw_time,
+            persisted: true,
+            ..Timestamp::from_time(entry.time, entry.split.clone())
+        }
+    }
+
+    /// Returns the identifier of the time stamp in DB, or `None` if it has never been persisted.
+    pub fn id(&self) -> Option<i32> {
+        self.id
+    }
+
     pub fn split(&self) -> &Split {
         &self.split
     }
@@ -72,89 +202,452 @@ impl Timestamp {
         self.bib
     }
 
-    pub(crate) async fn query_all_for_regatta(
+    /// Returns the name of the station that took the time stamp, if known.
+    pub fn station(&self) -> Option<&str> {
+        self.station.as_deref()
+    }
+
+    /// Returns the time of the clock of the station before it was corrected, if known.
+    pub fn raw_time(&self) -> Option<DateTime<Utc>> {
+        self.raw_time
+    }
+
+    /// Returns how the time stamp was taken, if known.
+    pub fn source(&self) -> Option<TimestampSource> {
+        self.source
+    }
+
+    /// Returns the free-text note of the time stamp.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub(crate) fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+        self.persisted = false;
+    }
+
+    /// Returns `true` if the time stamp is void, e.g. after a false start. Void time stamps stay visible, but are not
+    /// paired with other time stamps to rank the heats or to propose heat numbers.
+    pub fn is_voided(&self) -> bool {
+        self.voided
+    }
+
+    /// Returns why the time stamp is void.
+    pub fn void_reason(&self) -> Option<&str> {
+        self.void_reason.as_deref()
+    }
+
+    /// Voids the time stamp with the given reason, or makes it valid again with `None`.
+    pub(crate) fn set_void(&mut self, reason: Option<String>) {
+        self.voided = reason.is_some();
+        self.void_reason = reason;
+        self.persisted = false;
+    }
+
+    /// Returns `true` if both time stamps were taken at the same time of the same split by the same station, e.g. a
+    /// time stamp and its copy in DB or in the journal.
+    pub(crate) fn is_same_take(&self, other: &Timestamp) -> bool {
+        self.time == other.time && self.split == other.split && self.station == other.station
+    }
+
+    /// Takes over the heat number, bib, note and void state changed by another station. The time stamp stays
+    /// persisted.
+    /// # Arguments
+    /// * `entry` - The audit entry of the change.
+    /// # Returns
+    /// `true` if any of the values differed.
+    pub(crate) fn merge(&mut self, entry: &AuditEntry) -> bool {
+        let changed = self.heat_nr != entry.new_heat_nr
+            || self.bib != entry.new_bib
+            || self.note != entry.note
+            || self.voided != entry.voided
+            || self.void_reason != entry.void_reason;
+        self.heat_nr = entry.new_heat_nr;
+        self.bib = entry.new_bib;
+        self.note.clone_from(&entry.note);
+        self.voided = entry.voided;
+        self.void_reason.clone_from(&entry.void_reason);
+        changed
+    }
+
+    /// Sets the heat number and bib back to the given values, e.g. to revert a change.
+    pub(crate) fn reassign(&mut self, heat_nr: Option<i16>, bib: Option<u8>) {
+        self.heat_nr = heat_nr;
+        self.bib = bib;
+        self.persisted = false;
+    }
+
+    /// Queries the time stamps of a regatta matching the given filter, newest first.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta.
+    /// * `filter` - The filter the time stamps have to match.
+    /// * `before` - Only time stamps older than the given time and ID are returned, used to page through the time
+    ///   stamps while new ones are added.
+    /// * `offset` - The number of matching time stamps to skip.
+    /// * `limit` - The maximum number of time stamps to return.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn query_for_regatta(
         regatta_id: i32,
-        offset: Option<i32>,
-        top: Option<i32>,
+        filter: &TimestampFilter,
+        before: Option<(DateTime<Utc>, i32)>,
+        offset: i32,
+        limit: i32,
         client: &mut TiberiusClient,
     ) -> Result<Vec<Timestamp>, DbError> {
+        // Build the where clause based on the provided filter
+        let mut clauses = vec![format!("{EVENT_ID} = @P1")];
+        let mut param_count = 2;
+        if filter.from.is_some() {
+            clauses.push(format!("{TIMESTAMP} >= @P{param_count}"));
+            param_count += 1;
+        }
+        if filter.to.is_some() {
+            clauses.push(format!("{TIMESTAMP} < @P{param_count}"));
+            param_count += 1;
+        }
+        if filter.split_nr.is_some() {
+            clauses.push(format!("{SPLIT_NR} = @P{param_count}"));
+            param_count += 1;
+        }
+        if filter.heat_nr.is_some() {
+            clauses.push(format!("{HEAT_NR} = @P{param_count}"));
+            param_count += 1;
+        }
+        if before.is_some() {
+            clauses.push(format!(
+                "({TIMESTAMP} < @P{param_count} OR ({TIMESTAMP} = @P{param_count} AND {ID} < @P{}))",
+                param_count + 1
+            ));
+            param_count += 2;
+        }
+
         let mut query = Query::new(format!(
-            "SELECT {TIMESTAMP}, {EVENT_ID}, {SPLIT_NR}, {HEAT_NR}, {BIB} FROM HRV_Timestamp \
-            WHERE {EVENT_ID} = @P1 ORDER BY {TIMESTAMP} DESC \
-            OFFSET @P2 ROWS FETCH NEXT @P3 ROWS ONLY"
+            "SELECT {ID}, {TIMESTAMP}, {EVENT_ID}, {SPLIT_NR}, {HEAT_NR}, {BIB}, {STATION}, {RAW_TIMESTAMP}, \
+            {SOURCE}, {NOTE}, {VOIDED}, {VOID_REASON} \
+            FROM HRV_Timestamp \
+            WHERE {} ORDER BY {TIMESTAMP} DESC, {ID} DESC \
+            OFFSET @P{param_count} ROWS FETCH NEXT @P{} ROWS ONLY",
+            clauses.join(" AND "),
+            param_count + 1
         ));
+        // Bind parameters in the same order as the clauses
         query.bind(regatta_id);
-        query.bind(offset.unwrap_or(0)); // OFFSET
-        query.bind(top.unwrap_or(30)); // FETCH NEXT
+        if let Some(from) = filter.from {
+            query.bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.bind(to);
+        }
+        if let Some(split_nr) = filter.split_nr {
+            query.bind(split_nr);
+        }
+        if let Some(heat_nr) = filter.heat_nr {
+            query.bind(heat_nr);
+        }
+        if let Some((time, id)) = before {
+            query.bind(time);
+            query.bind(id);
+        }
+        query.bind(offset); // OFFSET
+        query.bind(limit); // FETCH NEXT
 
         let stream = query.query(client).await?;
         let time_stamps = get_rows(stream).await?;
         Ok(time_stamps.into_iter().map(|row| Timestamp::from(&row)).collect())
     }
 
-    pub(crate) async fn delete(&self, client: &mut TiberiusClient) -> Result<(), DbError> {
-        let mut query = Query::new(format!("DELETE FROM HRV_Timestamp WHERE {TIMESTAMP} = @P1"));
-        query.bind(self.time);
+    /// Deletes the time stamp from DB and records the deletion in the audit trail. A time stamp that has never been
+    /// persisted is not deleted.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `author` - The user or timekeeper station deleting the time stamp.
+    /// * `client` - The DB client to use.
+    /// # Errors
+    /// If the time stamp could not be deleted, e.g. because another station deleted it already.
+    pub(crate) async fn delete(
+        &self,
+        regatta_id: i32,
+        author: &str,
+        client: &mut TiberiusClient,
+    ) -> Result<(), DbError> {
+        if let Some(id) = self.id {
+            let mut query = Query::new(format!(
+                "DELETE FROM HRV_Timestamp {} WHERE {ID} = @P1 AND {EVENT_ID} = @P2",
+                output_into("DELETED", Some("DELETED"), None, 3)
+            ));
+            query.bind(id);
+            query.bind(regatta_id);
+            query.bind(<&str>::from(AuditAction::Delete));
+            query.bind(author);
+            query.bind(None::<i32>);
 
-        query.execute(client).await?;
+            if query.execute(client).await?.total() == 0 {
+                return Err(not_found(id));
+            }
+        }
         Ok(())
     }
 
-    pub(crate) async fn persist(&mut self, regatta_id: i32, client: &mut TiberiusClient) -> Result<(), DbError> {
+    /// Inserts the time stamp into DB and records the insertion in the audit trail.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `author` - The user or timekeeper station taking the time stamp.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn persist(
+        &mut self,
+        regatta_id: i32,
+        author: &str,
+        client: &mut TiberiusClient,
+    ) -> Result<(), DbError> {
+        self.insert(regatta_id, AuditAction::Insert, None, author, client).await
+    }
+
+    /// Inserts a deleted time stamp again and records the restoration in the audit trail.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `previous_id` - The ID of the deleted time stamp.
+    /// * `author` - The user or timekeeper station restoring the time stamp.
+    /// * `client` - The DB client to use.
+    pub(crate) async fn restore(
+        &mut self,
+        regatta_id: i32,
+        previous_id: i32,
+        author: &str,
+        client: &mut TiberiusClient,
+    ) -> Result<(), DbError> {
+        self.insert(regatta_id, AuditAction::Restore, Some(previous_id), author, client)
+            .await
+    }
+
+    async fn insert(
+        &mut self,
+        regatta_id: i32,
+        action: AuditAction,
+        previous_id: Option<i32>,
+        author: &str,
+        client: &mut TiberiusClient,
+    ) -> Result<(), DbError> {
         if !self.persisted {
             let mut query = Query::new(format!(
-                "INSERT INTO HRV_Timestamp ({TIMESTAMP}, {EVENT_ID}, {SPLIT_NR}, {HEAT_NR}, {BIB}) VALUES (@P1, @P2, @P3, @P4, @P5)"
+                "INSERT INTO HRV_Timestamp \
+                ({TIMESTAMP}, {EVENT_ID}, {SPLIT_NR}, {HEAT_NR}, {BIB}, {STATION}, {RAW_TIMESTAMP}, \
+                {SOURCE}, {NOTE}, {VOIDED}, {VOID_REASON}) \
+                {} OUTPUT INSERTED.{ID} VALUES (@P1, @P2, @P3, @P4, @P5, @P6, @P7, @P8, @P9, @P10, @P11)",
+                output_into("INSERTED", None, Some("INSERTED"), 12)
             ));
             query.bind(self.time);
             query.bind(regatta_id);
             query.bind(u8::from(&self.split));
             query.bind(self.heat_nr);
             query.bind(self.bib);
+            query.bind(self.station.clone());
+            query.bind(self.raw_time);
+            query.bind(self.source.map(<&str>::from));
+            query.bind(self.note.clone());
+            query.bind(self.voided);
+            query.bind(self.void_reason.clone());
+            query.bind(<&str>::from(action));
+            query.bind(author);
+            query.bind(previous_id);
 
-            query.execute(client).await?;
+            let row = get_row(query.query(client).await?).await?;
+            self.id = Some(row.get_column(ID));
             self.persisted = true;
         }
         Ok(())
     }
 
-    pub(crate) async fn update(&mut self, client: &mut TiberiusClient) -> Result<(), DbError> {
+    /// Queries the persisted copy of a time stamp that may have been inserted before, e.g. if the connection was lost
+    /// before the insert was confirmed. A copy has the same time, split and station.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `client` - The DB client to use.
+    /// # Returns
+    /// The persisted copy, or `None` if the time stamp has not been inserted.
+    pub(crate) async fn query_persisted(
+        &self,
+        regatta_id: i32,
+        client: &mut TiberiusClient,
+    ) -> Result<Option<Timestamp>, DbError> {
+        let filter = TimestampFilter {
+            from: Some(self.time),
+            to: Some(self.time + TimeDelta::milliseconds(1)),
+            split_nr: Some(u8::from(&self.split)),
+            heat_nr: None,
+        };
+        let copies = Self::query_for_regatta(regatta_id, &filter, None, 0, 10, client).await?;
+        Ok(copies.into_iter().find(|copy| copy.station == self.station))
+    }
+
+    /// Queries a time stamp by its ID.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `id` - The ID of the time stamp.
+    /// * `client` - The DB client to use.
+    /// # Returns
+    /// The time stamp, or `None` if there is no time stamp with the given ID.
+    pub(crate) async fn query_by_id(
+        regatta_id: i32,
+        id: i32,
+        client: &mut TiberiusClient,
+    ) -> Result<Option<Timestamp>, DbError> {
+        let mut query = Query::new(format!(
+            "SELECT {ID}, {TIMESTAMP}, {EVENT_ID}, {SPLIT_NR}, {HEAT_NR}, {BIB}, {STATION}, {RAW_TIMESTAMP}, \
+            {SOURCE}, {NOTE}, {VOIDED}, {VOID_REASON} \
+            FROM HRV_Timestamp WHERE {ID} = @P1 AND {EVENT_ID} = @P2"
+        ));
+        query.bind(id);
+        query.bind(regatta_id);
+        let rows = get_rows(query.query(client).await?).await?;
+        Ok(rows.first().map(Timestamp::from))
+    }
+
+    /// Updates the heat number, bib, note and void state of the time stamp in DB and records the change in the audit
+    /// trail. A time stamp that has never been persisted is inserted.
+    /// # Arguments
+    /// * `regatta_id` - The ID of the regatta the time stamp belongs to.
+    /// * `action` - The action recorded in the audit trail, an update, an undo or an annotation.
+    /// * `author` - The user or timekeeper station changing the time stamp.
+    /// * `client` - The DB client to use.
+    /// # Errors
+    /// If the time stamp could not be updated, e.g. because another station deleted it.
+    pub(crate) async fn update(
+        &mut self,
+        regatta_id: i32,
+        action: AuditAction,
+        author: &str,
+        client: &mut TiberiusClient,
+    ) -> Result<(), DbError> {
+        let Some(id) = self.id else {
+            return self.persist(regatta_id, author, client).await;
+        };
         if !self.persisted {
             let mut query = Query::new(format!(
-                "UPDATE HRV_Timestamp SET {HEAT_NR} = @P2, {BIB} = @P3 WHERE {TIMESTAMP} = @P1"
+                "UPDATE HRV_Timestamp \
+                SET {HEAT_NR} = @P3, {BIB} = @P4, {NOTE} = @P5, {VOIDED} = @P6, {VOID_REASON} = @P7 {} \
+                WHERE {ID} = @P1 AND {EVENT_ID} = @P2",
+                output_into("INSERTED", Some("DELETED"), Some("INSERTED"), 8)
             ));
-            query.bind(self.time);
+            query.bind(id);
+            query.bind(regatta_id);
             query.bind(self.heat_nr);
             query.bind(self.bib);
-            query.execute(client).await?;
+            query.bind(self.note.clone());
+            query.bind(self.voided);
+            query.bind(self.void_reason.clone());
+            query.bind(<&str>::from(action));
+            query.bind(author);
+            query.bind(None::<i32>);
+            if query.execute(client).await?.total() == 0 {
+                return Err(not_found(id));
+            }
             self.persisted = true;
         }
         Ok(())
     }
 }
 
+/// Creates the error of a time stamp that is not in DB, e.g. because another station deleted it.
+fn not_found(id: i32) -> DbError {
+    DbError::Custom(format!("Timestamp with ID {id} not found"))
+}
+
+/// Creates the deleted time stamp of an audit entry with the values it had when it was deleted.
+impl From<&AuditEntry> for Timestamp {
+    fn from(entry: &AuditEntry) -> Self {
+        Timestamp {
+            heat_nr: entry.old_heat_nr,
+            bib: entry.old_bib,
+            source: entry.source,
+            note: entry.note.clone(),
+            voided: entry.voided,
+            void_reason: entry.void_reason.clone(),
+            station: entry.station.clone(),
+            raw_time: entry.raw_time,
+            ..Timestamp::from_time(entry.time, entry.split.clone())
+        }
+    }
+}
+
 impl From<&Row> for Timestamp {
     fn from(row: &Row) -> Self {
         let split_nr: u8 = row.get_column(SPLIT_NR);
         Timestamp {
+            id: Some(row.get_column(ID)),
             time: row.get_column(TIMESTAMP),
             split: Split::from(split_nr),
             heat_nr: row.try_get_column(HEAT_NR),
             bib: row.try_get_column(BIB),
+            station: row.try_get_column(STATION),
+            raw_time: row.try_get_column(RAW_TIMESTAMP),
+            source: parse_source(row.try_get_column(SOURCE)),
+            note: row.try_get_column(NOTE),
+            voided: row.try_get_column(VOIDED).unwrap_or_default(),
+            void_reason: row.try_get_column(VOID_REASON),
             persisted: true,
         }
     }
 }
 
+/// Parses the source of a time stamp read from DB, unknown sources are ignored.
+pub(crate) fn parse_source(source: Option<String>) -> Option<TimestampSource> {
+    match source.as_deref()? {
+        "keyboard" => Some(TimestampSource::Keyboard),
+        "websocket" => Some(TimestampSource::Websocket),
+        "hardware" => Some(TimestampSource::Hardware),
+        "manual" => Some(TimestampSource::Manual),
+        _ => None,
+    }
+}
+
+/// A filter for time stamps. Only time stamps matching all given criteria are selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub struct TimestampFilter {
+    /// Only time stamps at or after this time.
+    pub from: Option<DateTime<Utc>>,
+
+    /// Only time stamps before this time.
+    pub to: Option<DateTime<Utc>>,
+
+    /// Only time stamps of this split number, e.g. 0 for the start or 64 for the finish.
+    pub split_nr: Option<u8>,
+
+    /// Only time stamps assigned to this heat number.
+    pub heat_nr: Option<i16>,
+}
+
+impl TimestampFilter {
+    /// Checks whether a time stamp matches the filter.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to check.
+    /// # Returns
+    /// `true` if the time stamp matches all given criteria.
+    pub fn matches(&self, timestamp: &Timestamp) -> bool {
+        self.from.is_none_or(|from| timestamp.time >= from)
+            && self.to.is_none_or(|to| timestamp.time < to)
+            && self
+                .split_nr
+                .is_none_or(|split_nr| u8::from(&timestamp.split) == split_nr)
+            && self.heat_nr.is_none_or(|heat_nr| timestamp.heat_nr == Some(heat_nr))
+    }
+}
+
 const SPLIT_START: u8 = 0;
 const SPLIT_FINISH: u8 = 64;
 
-/// The type of a time stamp.
-#[derive(Debug, Clone, Display, Serialize, ToSchema)]
+/// The type of a time stamp. Aquarius identifies it by its split number: 0 for the start, 64 for the finish and any
+/// other number for an intermediate split, e.g. at 500 m, 1000 m or 1500 m.
+#[derive(Debug, Clone, PartialEq, Eq, Display, ToSchema)]
 pub enum Split {
     /// A start time stamp.
     #[strum(to_string = "Start")]
     Start,
 
+    /// An intermediate time stamp with its split number.
+    #[strum(to_string = "ZZ {0}")]
+    Intermediate(u8),
+
     /// A finish time stamp.
     #[strum(to_string = "Ziel")]
     Finish,
@@ -165,7 +658,7 @@ impl From<u8> for Split {
         match value {
             SPLIT_START => Self::Start,
             SPLIT_FINISH => Self::Finish,
-            _ => Self::Start,
+            _ => Self::Intermediate(value),
         }
     }
 }
@@ -174,6 +667,7 @@ impl From<&Split> for u8 {
     fn from(split: &Split) -> Self {
         match split {
             Split::Start => SPLIT_START,
+            Split::Intermediate(split_nr) => *split_nr,
             Split::Finish => SPLIT_FINISH,
         }
     }
@@ -184,3 +678,128 @@ impl From<&Split> for String {
         split.to_string()
     }
 }
+
+/// Serializes the split as "Start", "Finish" or "Intermediate" followed by its split number, e.g. "Intermediate 1".
+impl Serialize for Split {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Split::Start => serializer.serialize_str("Start"),
+            Split::Intermediate(split_nr) => serializer.serialize_str(&format!("Intermediate {split_nr}")),
+            Split::Finish => serializer.serialize_str("Finish"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_values() {
+        assert_eq!(Split::from(0), Split::Start);
+        assert_eq!(Split::from(64), Split::Finish);
+        assert_eq!(Split::from(2), Split::Intermediate(2));
+        for value in 0..=u8::MAX {
+            assert_eq!(u8::from(&Split::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_split_display_and_serialization() {
+        assert_eq!(Split::Intermediate(1).to_string(), "ZZ 1");
+        assert_eq!(Split::Finish.to_string(), "Ziel");
+        assert_eq!(serde_json::to_string(&Split::Start).unwrap(), "\"Start\"");
+        assert_eq!(
+            serde_json::to_string(&Split::Intermediate(3)).unwrap(),
+            "\"Intermediate 3\""
+        );
+    }
+
+    #[test]
+    fn test_new_timestamp_has_no_id() {
+        let timestamp = Timestamp::new(Utc::now(), Split::Finish, Some(3));
+        assert_eq!(timestamp.id(), None);
+        assert!(!timestamp.is_persisted());
+        let json = serde_json::to_value(&timestamp).unwrap();
+        assert!(json["id"].is_null());
+        assert!(json["station"].is_null());
+        assert_eq!(json["heat_nr"], 3);
+    }
+
+    #[test]
+    fn test_timestamp_from_station() {
+        let raw_time = DateTime::parse_from_rfc3339("2026-06-01T10:15:00Z").unwrap().to_utc();
+        let station = Station::new("start", chrono::TimeDelta::milliseconds(500));
+        let timestamp = Timestamp::from_station(&station, raw_time, Split::Start, TimestampSource::Keyboard);
+        assert_eq!(timestamp.time, raw_time + chrono::TimeDelta::milliseconds(500));
+        assert_eq!(timestamp.raw_time(), Some(raw_time));
+        assert_eq!(timestamp.station(), Some("start"));
+        assert_eq!(timestamp.source(), Some(TimestampSource::Keyboard));
+
+        // the clock of a timing device is not the one of the station
+        let timestamp = Timestamp::from_station(&station, raw_time, Split::Start, TimestampSource::Hardware);
+        assert_eq!(timestamp.time, raw_time);
+    }
+
+    #[test]
+    fn test_timestamp_void_and_source() {
+        let mut timestamp = Timestamp::new(Utc::now(), Split::Start, Some(3));
+        assert!(!timestamp.is_voided());
+        timestamp.set_void(Some("Fehlstart".to_owned()));
+        timestamp.set_note(Some("Zielfoto prüfen".to_owned()));
+        assert!(timestamp.is_voided());
+        assert_eq!(timestamp.void_reason(), Some("Fehlstart"));
+        let json = serde_json::to_value(&timestamp).unwrap();
+        assert_eq!(json["voided"], true);
+        assert_eq!(json["note"], "Zielfoto prüfen");
+        timestamp.set_void(None);
+        assert!(!timestamp.is_voided());
+        assert_eq!(timestamp.void_reason(), None);
+
+        assert_eq!(
+            parse_source(Some("hardware".to_owned())),
+            Some(TimestampSource::Hardware)
+        );
+        assert_eq!(parse_source(Some("pigeon".to_owned())), None);
+        assert_eq!(
+            serde_json::to_string(&TimestampSource::Websocket).unwrap(),
+            "\"websocket\""
+        );
+    }
+
+    #[test]
+    fn test_timestamp_filter() {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:15:00Z").unwrap().to_utc();
+        let timestamp = Timestamp::new(time, Split::Intermediate(2), Some(17));
+        assert!(TimestampFilter::default().matches(&timestamp));
+
+        let filter = TimestampFilter {
+            from: Some(time),
+            to: Some(time + chrono::Duration::minutes(1)),
+            split_nr: Some(2),
+            heat_nr: Some(17),
+        };
+        assert!(filter.matches(&timestamp));
+        assert!(
+            !TimestampFilter {
+                to: Some(time),
+                ..filter.clone()
+            }
+            .matches(&timestamp)
+        );
+        assert!(
+            !TimestampFilter {
+                split_nr: Some(64),
+                ..filter.clone()
+            }
+            .matches(&timestamp)
+        );
+        assert!(
+            !TimestampFilter {
+                heat_nr: Some(18),
+                ..filter
+            }
+            .matches(&timestamp)
+        );
+    }
+}
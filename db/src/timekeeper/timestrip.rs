@@ -1,98 +1,954 @@
-use crate::aquarius::model::Regatta;
+use crate::aquarius::model::{Heat, Regatta};
 use crate::error::DbError;
-use crate::tiberius::TiberiusPool;
+use crate::tiberius::{TiberiusClient, TiberiusConnectionManager, TiberiusPool};
+use crate::timekeeper::Journal;
+use crate::timekeeper::Station;
 use crate::timekeeper::Timestamp;
+use crate::timekeeper::TimestampFilter;
+use crate::timekeeper::TimestampSource;
+use crate::timekeeper::audit::{AuditAction, AuditEntry};
+use crate::timekeeper::protocol::TimekeepingProtocol;
+use crate::timekeeper::ranking::HeatRanking;
+use crate::timekeeper::suggestion::{HeatSuggester, HeatSuggestion};
 use crate::timekeeper::timestamp::Split;
+use ::bb8::{PooledConnection, RunError};
 use ::chrono::DateTime;
+use ::chrono::NaiveDate;
+use ::chrono::TimeDelta;
 use ::chrono::Utc;
-use ::std::collections::VecDeque;
+use ::serde::Serialize;
 use ::std::collections::vec_deque;
+use ::std::collections::{HashSet, VecDeque};
 use ::std::sync::Arc;
-use ::std::time::Instant;
-use ::tracing::info;
+use ::std::time::{Duration, Instant};
+use ::tokio::time::timeout;
+use ::tracing::{debug, error, info, warn};
+use ::utoipa::ToSchema;
 
-/// A time strip is a collection of time stamps.
+/// The number of time stamps loaded at once.
+const PAGE_SIZE: i32 = 30;
+
+/// The maximum number of time stamps of a heat that are ranked, far more than any heat has.
+const MAX_HEAT_TIME_STAMPS: i32 = 1000;
+
+/// How long the time strip waits for a DB connection, so an unreachable DB does not block the station, e.g. its UI.
+/// A time stamp that could not be persisted is kept and persisted with the next sync, see
+/// [`TimeStrip::persist_pending`]. Only getting the connection is timed out: cancelling a running insert would leave the
+/// connection with an unread reply in the pool, and the insert may be committed anyway.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The number of already synced changes that are read again on each sync. Concurrent statements may commit their
+/// change log IDs out of order, reading a few again makes sure late commits are not missed. The changes read again are
+/// only applied if they were missed before.
+const SYNC_OVERLAP: i32 = 20;
+
+/// A change of the time strip made by another station or a time stamp persisted late, see [`TimeStrip::sync`].
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub enum TimeStripChange {
+    /// A time stamp was taken or restored.
+    Inserted(Timestamp),
+
+    /// The heat number or bib of a time stamp was changed.
+    Updated(Timestamp),
+
+    /// The time stamp with the given ID was deleted.
+    Deleted(i32),
+
+    /// A time stamp of this time strip that could not be persisted when it was taken got its ID. It is identified by
+    /// its time, split and station.
+    Persisted(Timestamp),
+}
+
+/// A time strip is a collection of time stamps. Initially only the newest time stamps are loaded, older ones are loaded
+/// page by page with [`TimeStrip::load_more`].
 pub struct TimeStrip {
-    // The ID of the regatta this time strip belongs to.
-    regatta_id: i32,
+    // The ID of the regatta this time strip belongs to, unknown until it is loaded from DB.
+    regatta_id: Option<i32>,
 
     // A deque of time stamps.
     time_stamps: VecDeque<Timestamp>,
 
+    // The user or timekeeper station changing the time stamps, recorded in the audit trail.
+    author: String,
+
+    // The station taking the time stamps, its clock offset is applied to new time stamps.
+    station: Station,
+
+    // The ID of the newest change in the change log that has been synced.
+    last_change_id: i32,
+
+    // The IDs of the changes that have been synced and are read again, see `SYNC_OVERLAP`.
+    applied_change_ids: HashSet<i32>,
+
+    // The journal the time stamps are written to before they are persisted, if any.
+    journal: Option<Journal>,
+
     pool: Arc<TiberiusPool>,
 }
 
 impl TimeStrip {
-    pub async fn load(pool: Arc<TiberiusPool>) -> Result<Self, DbError> {
+    /// Loads the newest time stamps of the active regatta.
+    /// # Arguments
+    /// * `pool` - The DB connection pool.
+    /// * `author` - The user or timekeeper station that changes the time stamps, recorded in the audit trail.
+    pub async fn load(pool: Arc<TiberiusPool>, author: &str) -> Result<Self, DbError> {
+        let mut time_strip = TimeStrip::offline(pool, author);
+        time_strip.load_regatta().await?;
+        Ok(time_strip)
+    }
+
+    /// Creates an empty time strip, e.g. if the DB is unreachable on start. Time stamps can be taken, the ones of the
+    /// active regatta are loaded and the taken ones persisted with the next sync.
+    /// # Arguments
+    /// * `pool` - The DB connection pool.
+    /// * `author` - The user or timekeeper station that changes the time stamps, recorded in the audit trail.
+    pub fn offline(pool: Arc<TiberiusPool>, author: &str) -> Self {
+        TimeStrip {
+            regatta_id: None,
+            time_stamps: VecDeque::new(),
+            author: author.to_owned(),
+            station: Station::new(author, TimeDelta::zero()),
+            last_change_id: 0,
+            applied_change_ids: HashSet::new(),
+            journal: None,
+            pool,
+        }
+    }
+
+    /// Loads the newest time stamps of the active regatta, if not loaded yet. The time stamps taken before are kept.
+    /// # Returns
+    /// The ID of the active regatta.
+    async fn load_regatta(&mut self) -> Result<i32, DbError> {
+        if let Some(regatta_id) = self.regatta_id {
+            return Ok(regatta_id);
+        }
         let start = Instant::now();
-        let pool_clone = pool.clone();
-        let mut client = pool_clone.get().await?;
+        let pool = self.pool.clone();
+        let mut client = connect(&pool).await?;
         let regatta = Regatta::query_active_regatta(&mut client).await?;
-        let time_stamps = Timestamp::query_all_for_regatta(regatta.id, None, None, &mut client).await?;
-        let time_strip = TimeStrip {
-            regatta_id: regatta.id,
-            time_stamps: VecDeque::from(time_stamps),
-            pool,
-        };
+        // read the change log position first, changes made while loading are synced again
+        let last_change_id = AuditEntry::query_last_id(regatta.id, &mut client).await?;
+        let time_stamps =
+            Timestamp::query_for_regatta(regatta.id, &TimestampFilter::default(), None, 0, PAGE_SIZE, &mut client)
+                .await?;
+        for timestamp in time_stamps {
+            self.insert_ordered(timestamp);
+        }
+        self.regatta_id = Some(regatta.id);
+        self.last_change_id = last_change_id;
         info!(regatta_id = regatta.id, elapsed = ?start.elapsed(), "Loaded time strip:");
-        Ok(time_strip)
+        Ok(regatta.id)
     }
 
-    pub async fn add_start(&mut self, time: Option<DateTime<Utc>>) -> Result<Timestamp, DbError> {
-        let timestamp = Timestamp::from_time(time.unwrap_or_else(Utc::now), Split::Start);
-        self.time_stamps.push_front(timestamp.clone());
-        if let Some(timestamp) = self.time_stamps.front_mut() {
-            let mut client = self.pool.get().await?;
-            timestamp.persist(self.regatta_id, &mut client).await?;
-            Ok(timestamp.clone())
-        } else {
-            Ok(timestamp)
+    /// Returns the ID of the regatta of the time strip.
+    /// # Errors
+    /// If the time strip has not been loaded from DB yet.
+    fn regatta_id(&self) -> Result<i32, DbError> {
+        self.regatta_id
+            .ok_or_else(|| DbError::Custom("Time strip not loaded yet".to_string()))
+    }
+
+    /// Returns the station taking the time stamps.
+    pub fn station(&self) -> &Station {
+        &self.station
+    }
+
+    /// Sets the station taking the time stamps, e.g. with a configured clock offset. Defaults to a station named
+    /// after the author without clock offset.
+    pub fn set_station(&mut self, station: Station) {
+        self.station = station;
+    }
+
+    /// Sets the journal the time stamps are written to before they are persisted. The time stamps left in the journal,
+    /// e.g. by a crash or while the DB was unreachable, are persisted with [`TimeStrip::persist_pending`].
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// Persists the time stamps that could not be persisted when they were taken, including the ones left in the
+    /// journal by a previous run. A time stamp that has been inserted before the connection was lost is not inserted
+    /// again.
+    /// # Returns
+    /// The number of persisted time stamps.
+    /// # Errors
+    /// If the DB is unreachable, the remaining time stamps are persisted with the next call.
+    pub async fn persist_pending(&mut self) -> Result<usize, DbError> {
+        // time stamps taken before the time strip was loaded belong to the active regatta
+        let active_id = self.load_regatta().await?;
+        let mut pending: Vec<(i32, Timestamp)> = self
+            .journal
+            .iter()
+            .flat_map(Journal::pending)
+            .map(|entry| (entry.regatta_id.unwrap_or(active_id), entry.to_timestamp()))
+            .collect();
+        // time stamps that could not be written to the journal either
+        for timestamp in self.time_stamps.iter().filter(|ts| ts.id().is_none()) {
+            if !pending.iter().any(|(_, other)| other.is_same_take(timestamp)) {
+                pending.push((active_id, timestamp.clone()));
+            }
+        }
+        if pending.is_empty() {
+            return Ok(0);
         }
+
+        let pool = self.pool.clone();
+        let mut client = connect(&pool).await?;
+        for (regatta_id, timestamp) in &pending {
+            let position = (*regatta_id == active_id).then(|| self.position(timestamp)).flatten();
+            // the heat number and bib may have been assigned in the meantime
+            let mut timestamp = position
+                .and_then(|pos| self.time_stamps.get(pos))
+                .unwrap_or(timestamp)
+                .clone();
+            match timestamp.query_persisted(*regatta_id, &mut client).await? {
+                Some(copy) => timestamp = copy,
+                None => timestamp.persist(*regatta_id, &self.author, &mut client).await?,
+            }
+            self.journal_synced(&timestamp);
+            match position {
+                Some(pos) => self.time_stamps[pos] = timestamp,
+                None if *regatta_id == active_id => self.insert_ordered(timestamp),
+                None => {}
+            }
+        }
+        info!(count = pending.len(), "Persisted pending time stamps:");
+        Ok(pending.len())
     }
 
-    pub async fn add_finish(&mut self, time: Option<DateTime<Utc>>) -> Result<Timestamp, DbError> {
-        let timestamp = Timestamp::from_time(time.unwrap_or_else(Utc::now), Split::Finish);
-        self.time_stamps.push_front(timestamp.clone());
-        if let Some(timestamp) = self.time_stamps.front_mut() {
-            let mut client = self.pool.get().await?;
-            timestamp.persist(self.regatta_id, &mut client).await?;
-            Ok(timestamp.clone())
-        } else {
-            Ok(timestamp)
+    /// Marks the journal entries of a persisted time stamp as synced.
+    fn journal_synced(&mut self, timestamp: &Timestamp) {
+        let (Some(journal), Some(id)) = (self.journal.as_mut(), timestamp.id()) else {
+            return;
+        };
+        let seqs: Vec<u64> = journal
+            .pending()
+            .iter()
+            .filter(|entry| entry.to_timestamp().is_same_take(timestamp))
+            .map(|entry| entry.seq)
+            .collect();
+        for seq in seqs {
+            if let Err(err) = journal.mark_synced(seq, id) {
+                error!(%err, seq, "Error marking journal entry as synced:");
+            }
+        }
+    }
+
+    /// Records the heat number and bib of a time stamp not persisted yet in its journal entries.
+    fn journal_reassigned(&mut self, timestamp: &Timestamp) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        let seqs: Vec<u64> = journal
+            .pending()
+            .iter()
+            .filter(|entry| entry.to_timestamp().is_same_take(timestamp))
+            .filter(|entry| (entry.heat_nr, entry.bib) != (timestamp.heat_nr(), timestamp.bib()))
+            .map(|entry| entry.seq)
+            .collect();
+        for seq in seqs {
+            if let Err(err) = journal.reassign(seq, timestamp.heat_nr(), timestamp.bib()) {
+                error!(%err, seq, "Error recording reassignment in journal:");
+            }
+        }
+    }
+
+    /// Discards the journal entries of a time stamp deleted before it was persisted, so it is not persisted later.
+    fn journal_discarded(&mut self, timestamp: &Timestamp) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        let seqs: Vec<u64> = journal
+            .pending()
+            .iter()
+            .filter(|entry| entry.to_timestamp().is_same_take(timestamp))
+            .map(|entry| entry.seq)
+            .collect();
+        for seq in seqs {
+            if let Err(err) = journal.discard(seq) {
+                error!(%err, seq, "Error discarding journal entry:");
+            }
+        }
+    }
+
+    /// Measures the offset of the local clock against the clock of the DB server by the round trip to the DB server
+    /// and applies it to new time stamps.
+    /// # Returns
+    /// The measured clock offset.
+    pub async fn measure_clock_offset(&mut self) -> Result<TimeDelta, DbError> {
+        let pool = self.pool.clone();
+        let mut client = connect(&pool).await?;
+        let clock_offset = Station::measure_clock_offset(&mut client).await?;
+        self.apply_clock_offset(clock_offset);
+        Ok(clock_offset)
+    }
+
+    /// Queries the current time of the DB server, the reference clock of all stations. A station that is not on the
+    /// local clock, e.g. a browser, measures its clock offset by a round trip, see [`TimeStrip::set_clock_offset`].
+    pub async fn query_db_time(&self) -> Result<DateTime<Utc>, DbError> {
+        let mut client = connect(&self.pool).await?;
+        Station::query_db_time(&mut client).await
+    }
+
+    /// Sets the clock offset of a station that is not on the local clock, e.g. a browser, from a round trip to the DB
+    /// server and applies it to new time stamps. The DB server time is estimated to be taken in the middle of the round
+    /// trip, like the local clock is measured.
+    /// # Arguments
+    /// * `sent` - The time the station started the round trip at, by its clock.
+    /// * `db_time` - The time of the DB server, see [`TimeStrip::query_db_time`].
+    /// * `received` - The time the station received the DB server time at, by its clock.
+    /// # Returns
+    /// The measured clock offset.
+    pub fn set_clock_offset(
+        &mut self,
+        sent: DateTime<Utc>,
+        db_time: DateTime<Utc>,
+        received: DateTime<Utc>,
+    ) -> TimeDelta {
+        let clock_offset = Station::offset_by_round_trip(sent, db_time, received);
+        self.apply_clock_offset(clock_offset);
+        clock_offset
+    }
+
+    fn apply_clock_offset(&mut self, clock_offset: TimeDelta) {
+        self.station.set_clock_offset(clock_offset);
+        info!(
+            station = self.station.name(),
+            offset_ms = clock_offset.num_milliseconds(),
+            "Measured clock offset:"
+        );
+    }
+
+    /// Loads the next page of older time stamps and appends them to the time strip.
+    /// # Returns
+    /// The number of loaded time stamps, less than a page if there are no older time stamps left.
+    pub async fn load_more(&mut self) -> Result<usize, DbError> {
+        // page by the oldest loaded time stamp, so time stamps added in the meantime don't shift the pages
+        let before = self
+            .time_stamps
+            .iter()
+            .rev()
+            .find_map(|timestamp| timestamp.id().map(|id| (timestamp.time, id)));
+        let regatta_id = self.regatta_id()?;
+        let mut client = connect(&self.pool).await?;
+        let time_stamps = Timestamp::query_for_regatta(
+            regatta_id,
+            &TimestampFilter::default(),
+            before,
+            0,
+            PAGE_SIZE,
+            &mut client,
+        )
+        .await?;
+        let count = time_stamps.len();
+        self.time_stamps.extend(time_stamps);
+        debug!(regatta_id, count, "Loaded older time stamps:");
+        Ok(count)
+    }
+
+    /// Merges the time stamps inserted, changed or deleted by other stations since the last sync into the time strip.
+    /// Time stamps older than the loaded ones are left out, unless the whole time strip fits into one page.
+    /// # Returns
+    /// The changes that were merged, changes of this time strip that are already applied are left out. Time stamps of
+    /// this time strip persisted in the meantime are returned as [`TimeStripChange::Persisted`], the ones restored from
+    /// the journal as [`TimeStripChange::Inserted`].
+    /// # Errors
+    /// If the DB is unreachable, it is waited for at most [`CONNECT_TIMEOUT`].
+    pub async fn sync(&mut self) -> Result<Vec<TimeStripChange>, DbError> {
+        let known: HashSet<i32> = self.time_stamps.iter().filter_map(Timestamp::id).collect();
+        let unpersisted: Vec<Timestamp> = self
+            .time_stamps
+            .iter()
+            .filter(|ts| ts.id().is_none())
+            .cloned()
+            .collect();
+        if let Err(err) = self.persist_pending().await {
+            warn!(%err, "Error persisting pending time stamps:");
+        }
+        // also the ones persisted before an error, their audit entries are left out by the merge
+        let mut changes: Vec<TimeStripChange> = self
+            .time_stamps
+            .iter()
+            .filter(|ts| ts.id().is_some_and(|id| !known.contains(&id)))
+            .map(
+                |timestamp| match unpersisted.iter().any(|taken| taken.is_same_take(timestamp)) {
+                    true => TimeStripChange::Persisted(timestamp.clone()),
+                    // restored from the journal of a previous run
+                    false => TimeStripChange::Inserted(timestamp.clone()),
+                },
+            )
+            .collect();
+
+        // nothing can be synced before the time strip was loaded
+        let regatta_id = self.regatta_id()?;
+        let pool = self.pool.clone();
+        let since = self.last_change_id.saturating_sub(SYNC_OVERLAP);
+        let entries = match connect(&pool).await {
+            Ok(mut client) => AuditEntry::query_since(regatta_id, since, &mut client).await,
+            Err(err) => Err(err),
+        };
+        let entries = match entries {
+            Ok(entries) => entries,
+            // the persisted time stamps are not reported again
+            Err(err) if !changes.is_empty() => {
+                warn!(%err, "Error syncing time strip changes:");
+                return Ok(changes);
+            }
+            Err(err) => return Err(err),
+        };
+        changes.extend(self.apply(&entries));
+        if !changes.is_empty() {
+            debug!(regatta_id, count = changes.len(), "Synced time strip changes:");
         }
+        Ok(changes)
     }
 
+    /// Applies the changes read from the change log. Changes that have been applied by a previous sync are left out,
+    /// as well as changes made obsolete by a later change of the same time stamp, e.g. the insert of a time stamp that
+    /// is deleted later on.
+    /// # Arguments
+    /// * `entries` - The changes, in the order they were made.
+    /// # Returns
+    /// The changes that were merged.
+    fn apply(&mut self, entries: &[AuditEntry]) -> Vec<TimeStripChange> {
+        let entries: Vec<&AuditEntry> = entries
+            .iter()
+            .filter(|entry| self.applied_change_ids.insert(entry.id))
+            .collect();
+        let mut changes = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let superseded = entries[index + 1..]
+                .iter()
+                .filter(|later| later.timestamp_id == entry.timestamp_id)
+                .any(|later| match entry.action {
+                    // an update of a later inserted time stamp has to be applied to it
+                    AuditAction::Insert | AuditAction::Restore => later.action == AuditAction::Delete,
+                    // each change carries all values that can be changed
+                    _ => true,
+                });
+            if !superseded && let Some(change) = self.merge(entry) {
+                changes.push(change);
+            }
+        }
+        if let Some(last) = entries.iter().map(|entry| entry.id).max() {
+            self.last_change_id = self.last_change_id.max(last);
+        }
+        // changes before the overlap are not read again
+        let oldest = self.last_change_id.saturating_sub(SYNC_OVERLAP);
+        self.applied_change_ids.retain(|id| *id > oldest);
+        changes
+    }
+
+    fn merge(&mut self, entry: &AuditEntry) -> Option<TimeStripChange> {
+        match entry.action {
+            AuditAction::Insert | AuditAction::Restore => {
+                if self.get_by_id(entry.timestamp_id).is_some() {
+                    return None;
+                }
+                let is_older = self.time_stamps.back().is_some_and(|oldest| entry.time < oldest.time);
+                if is_older && self.time_stamps.len() >= PAGE_SIZE as usize {
+                    return None;
+                }
+                let timestamp = Timestamp::from_insert(entry);
+                self.insert_ordered(timestamp.clone());
+                Some(TimeStripChange::Inserted(timestamp))
+            }
+            AuditAction::Update | AuditAction::Undo | AuditAction::Annotate => {
+                let timestamp = self
+                    .time_stamps
+                    .iter_mut()
+                    .find(|ts| ts.id() == Some(entry.timestamp_id))?;
+                timestamp
+                    .merge(entry)
+                    .then(|| TimeStripChange::Updated(timestamp.clone()))
+            }
+            AuditAction::Delete => {
+                let pos = self
+                    .time_stamps
+                    .iter()
+                    .position(|ts| ts.id() == Some(entry.timestamp_id))?;
+                self.time_stamps.remove(pos);
+                Some(TimeStripChange::Deleted(entry.timestamp_id))
+            }
+        }
+    }
+
+    /// Inserts a time stamp by its time, the time strip is ordered newest first.
+    fn insert_ordered(&mut self, timestamp: Timestamp) {
+        let pos = self
+            .time_stamps
+            .iter()
+            .position(|ts| ts.time < timestamp.time)
+            .unwrap_or(self.time_stamps.len());
+        self.time_stamps.insert(pos, timestamp);
+    }
+
+    /// Queries the time stamps of the regatta in DB, independent of the time stamps loaded into the time strip. Used
+    /// to review the time stamps of a whole day, e.g. of a time window, a split or a heat.
+    /// # Arguments
+    /// * `filter` - The filter the time stamps have to match.
+    /// * `offset` - The number of matching time stamps to skip.
+    /// * `limit` - The maximum number of time stamps to return.
+    /// # Returns
+    /// The matching time stamps, newest first.
+    pub async fn query(&self, filter: &TimestampFilter, offset: i32, limit: i32) -> Result<Vec<Timestamp>, DbError> {
+        let mut client = connect(&self.pool).await?;
+        Timestamp::query_for_regatta(self.regatta_id()?, filter, None, offset, limit, &mut client).await
+    }
+
+    /// Adds a start time stamp, see [`TimeStrip::add`].
+    pub async fn add_start(&mut self, time: Option<DateTime<Utc>>, source: TimestampSource) -> Timestamp {
+        self.add(Split::Start, time, source).await
+    }
+
+    /// Adds an intermediate time stamp, e.g. taken at a 500 m tower.
+    /// # Arguments
+    /// * `split_nr` - The split number of the intermediate split. Must neither be the start nor the finish split.
+    /// * `time` - The time of the time stamp, defaults to now.
+    /// * `source` - How the time stamp was taken.
+    /// # Returns
+    /// The persisted time stamp.
+    /// # Errors
+    /// If the split number is invalid or the time stamp could not be persisted.
+    pub async fn add_intermediate(
+        &mut self,
+        split_nr: u8,
+        time: Option<DateTime<Utc>>,
+        source: TimestampSource,
+    ) -> Result<Timestamp, DbError> {
+        match Split::from(split_nr) {
+            split @ Split::Intermediate(_) => Ok(self.add(split, time, source).await),
+            _ => Err(DbError::Custom(format!(
+                "Invalid intermediate split number: {split_nr}"
+            ))),
+        }
+    }
+
+    /// Adds a finish time stamp, see [`TimeStrip::add`].
+    pub async fn add_finish(&mut self, time: Option<DateTime<Utc>>, source: TimestampSource) -> Timestamp {
+        self.add(Split::Finish, time, source).await
+    }
+
+    /// Adds a time stamp of the given split and persists it. The time is corrected by the clock offset of the station,
+    /// unless it was taken by a timing device with a clock of its own.
+    /// Taking a time never fails: the time stamp is written to the journal first, and if the DB is unreachable it is
+    /// kept and persisted with the next sync.
+    /// # Arguments
+    /// * `split` - The split of the time stamp.
+    /// * `time` - The time of the clock of the station, defaults to now.
+    /// * `source` - How the time stamp was taken.
+    /// # Returns
+    /// The time stamp, persisted if the DB was reachable.
+    pub async fn add(&mut self, split: Split, time: Option<DateTime<Utc>>, source: TimestampSource) -> Timestamp {
+        let mut timestamp = Timestamp::from_station(&self.station, time.unwrap_or_else(Utc::now), split, source);
+        if let Some(journal) = self.journal.as_mut()
+            && let Err(err) = journal.append(self.regatta_id, &timestamp)
+        {
+            error!(%err, path = %journal.path().display(), "Error writing time stamp to journal:");
+        }
+
+        let pool = self.pool.clone();
+        let persisted = match (self.regatta_id(), connect(&pool).await) {
+            (Ok(regatta_id), Ok(mut client)) => timestamp.persist(regatta_id, &self.author, &mut client).await,
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        };
+        match persisted {
+            Ok(()) => self.journal_synced(&timestamp),
+            Err(err) => warn!(%err, time = %timestamp.time, "Time stamp is persisted with the next sync:"),
+        }
+        self.time_stamps.push_front(timestamp.clone());
+        timestamp
+    }
+
+    /// Sets the heat number of a time stamp and updates it in DB.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to update, identified by its ID.
+    /// * `heat_nr` - The new heat number.
+    /// # Returns
+    /// The updated time stamp, see [`TimeStrip::update_one`].
     pub async fn set_heat_nr(&mut self, timestamp: &Timestamp, heat_nr: i16) -> Result<Timestamp, DbError> {
-        if let Some(timestamp) = self.time_stamps.iter_mut().find(|ts| ts.time == timestamp.time) {
+        self.update_one(timestamp, AuditAction::Update, |timestamp| {
+            timestamp.set_heat_nr(heat_nr)
+        })
+        .await
+    }
+
+    /// Sets the bib of a time stamp and updates it in DB.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to update, identified by its ID.
+    /// * `bib` - The new bib.
+    /// # Returns
+    /// The updated time stamp, see [`TimeStrip::update_one`].
+    pub async fn set_bib(&mut self, timestamp: &Timestamp, bib: u8) -> Result<Timestamp, DbError> {
+        self.update_one(timestamp, AuditAction::Update, |timestamp| timestamp.set_bib(bib))
+            .await
+    }
+
+    /// Sets or clears the note of a time stamp and updates it in DB.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to update, identified by its ID.
+    /// * `note` - The new note, or `None` to clear it.
+    /// # Returns
+    /// The updated time stamp, see [`TimeStrip::update_one`].
+    pub async fn set_note(&mut self, timestamp: &Timestamp, note: Option<&str>) -> Result<Timestamp, DbError> {
+        self.update_one(timestamp, AuditAction::Annotate, |timestamp| {
+            timestamp.set_note(note.map(str::to_owned))
+        })
+        .await
+    }
+
+    /// Voids a time stamp, or makes a void one valid again, and updates it in DB. Void time stamps stay in the time
+    /// strip, but are neither ranked nor used to propose heat numbers.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to update, identified by its ID.
+    /// * `reason` - Why the time stamp is void, or `None` to make it valid again.
+    /// # Returns
+    /// The updated time stamp, see [`TimeStrip::update_one`].
+    pub async fn set_void(&mut self, timestamp: &Timestamp, reason: Option<&str>) -> Result<Timestamp, DbError> {
+        let timestamp = self
+            .update_one(timestamp, AuditAction::Annotate, |timestamp| {
+                timestamp.set_void(reason.map(str::to_owned))
+            })
+            .await?;
+        info!(
+            id = timestamp.id(),
+            voided = timestamp.is_voided(),
+            by = self.author,
+            "Changed void state of time stamp:"
+        );
+        Ok(timestamp)
+    }
+
+    /// Changes a time stamp and updates it in DB. A persisted time stamp that is not loaded, e.g. one found by
+    /// [`TimeStrip::find_by_id`], is changed in DB only.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to update, identified by its ID.
+    /// * `action` - The action recorded in the audit trail.
+    /// * `change` - Changes the time stamp.
+    /// # Returns
+    /// The updated time stamp, or the given one if it is neither part of the time strip nor persisted.
+    async fn update_one(
+        &mut self,
+        timestamp: &Timestamp,
+        action: AuditAction,
+        change: impl FnOnce(&mut Timestamp),
+    ) -> Result<Timestamp, DbError> {
+        let pool = self.pool.clone();
+        match self.position(timestamp) {
+            Some(pos) => {
+                change(&mut self.time_stamps[pos]);
+                if self.time_stamps[pos].id().is_none() {
+                    // kept in the journal, so the change survives a restart while the DB is unreachable
+                    let timestamp = self.time_stamps[pos].clone();
+                    self.journal_reassigned(&timestamp);
+                }
+                let regatta_id = self.regatta_id()?;
+                let timestamp = &mut self.time_stamps[pos];
+                let mut client = connect(&pool).await?;
+                timestamp.update(regatta_id, action, &self.author, &mut client).await?;
+                // a time stamp that could not be persisted when it was taken is inserted now
+                let timestamp = timestamp.clone();
+                self.journal_synced(&timestamp);
+                Ok(timestamp)
+            }
+            None if timestamp.id().is_some() => {
+                let mut timestamp = timestamp.clone();
+                change(&mut timestamp);
+                let mut client = connect(&pool).await?;
+                timestamp
+                    .update(self.regatta_id()?, action, &self.author, &mut client)
+                    .await?;
+                Ok(timestamp)
+            }
+            None => Ok(timestamp.clone()),
+        }
+    }
+
+    /// Sets the heat number of several time stamps, e.g. of all time stamps taken in a busy block, and updates them in
+    /// DB in one transaction.
+    /// # Arguments
+    /// * `time_stamps` - The time stamps to update, identified by their ID.
+    /// * `heat_nr` - The new heat number.
+    /// # Returns
+    /// The updated time stamps.
+    /// # Errors
+    /// If a time stamp is not part of the time strip or the time stamps could not be updated, none of them is changed
+    /// then.
+    pub async fn set_heat_nrs(&mut self, time_stamps: &[Timestamp], heat_nr: i16) -> Result<Vec<Timestamp>, DbError> {
+        self.update_all(time_stamps, |_, timestamp| {
             timestamp.set_heat_nr(heat_nr);
-            let mut client = self.pool.get().await?;
-            timestamp.update(&mut client).await?;
-            return Ok(timestamp.clone());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Assigns the bibs of a heat to its finish time stamps in the order the boats finished, e.g. the bibs of the start
+    /// list of a head race, where the boats finish in the order they started. Start, intermediate and void time stamps
+    /// get no bib. The time stamps are updated in DB in one transaction.
+    /// # Arguments
+    /// * `time_stamps` - The time stamps to update, identified by their ID.
+    /// * `bibs` - The bibs in the order the boats finished.
+    /// # Returns
+    /// The updated time stamps, in the order they were taken.
+    /// # Errors
+    /// If there are more finish time stamps than bibs, a time stamp is not part of the time strip or the time stamps could not
+    /// be updated, none of them is changed then.
+    pub async fn set_bibs(&mut self, time_stamps: &[Timestamp], bibs: &[u8]) -> Result<Vec<Timestamp>, DbError> {
+        let time_stamps = finish_order(time_stamps);
+        if time_stamps.len() > bibs.len() {
+            return Err(DbError::Custom(format!(
+                "{} time stamps, but only {} bibs",
+                time_stamps.len(),
+                bibs.len()
+            )));
         }
-        Ok(timestamp.clone())
+        self.update_all(&time_stamps, |index, timestamp| {
+            timestamp.set_bib(bibs[index]);
+            Ok(())
+        })
+        .await
     }
 
-    pub async fn set_bib(&mut self, timestamp: &Timestamp, bib: u8) -> Result<Timestamp, DbError> {
-        if let Some(timestamp) = self.time_stamps.iter_mut().find(|ts| ts.time == timestamp.time) {
-            timestamp.set_bib(bib);
-            let mut client = self.pool.get().await?;
-            timestamp.update(&mut client).await?;
-            return Ok(timestamp.clone());
+    /// Shifts the heat numbers of several time stamps, e.g. if a block of time stamps was assigned to the heat after
+    /// the right one. Time stamps without heat number are not changed. The time stamps are updated in DB in one
+    /// transaction.
+    /// # Arguments
+    /// * `time_stamps` - The time stamps to update, identified by their ID.
+    /// * `delta` - The number added to the heat numbers, e.g. `-1` to assign the time stamps to the previous heats.
+    /// # Returns
+    /// The updated time stamps.
+    /// # Errors
+    /// If a heat number would not be positive, a time stamp is not part of the time strip or the time stamps could not
+    /// be updated, none of them is changed then.
+    pub async fn shift_heat_nrs(&mut self, time_stamps: &[Timestamp], delta: i16) -> Result<Vec<Timestamp>, DbError> {
+        let time_stamps: Vec<Timestamp> = time_stamps
+            .iter()
+            .filter(|ts| ts.heat_nr().is_some())
+            .cloned()
+            .collect();
+        self.update_all(&time_stamps, |_, timestamp| {
+            let heat_nr = timestamp
+                .heat_nr()
+                .and_then(|heat_nr| heat_nr.checked_add(delta))
+                .filter(|heat_nr| *heat_nr > 0)
+                .ok_or_else(|| {
+                    DbError::Custom(format!("Cannot shift heat number {:?} by {delta}", timestamp.heat_nr()))
+                })?;
+            timestamp.set_heat_nr(heat_nr);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Changes several time stamps and updates them in DB in one transaction. All time stamps are changed before the
+    /// transaction is begun, if the change of a time stamp fails nothing is updated. If the update of a time stamp
+    /// fails, the transaction is rolled back. Either way the time stamps are left unchanged.
+    /// # Arguments
+    /// * `time_stamps` - The time stamps to change, identified by their ID.
+    /// * `change` - Changes a time stamp, called with its index in `time_stamps`.
+    /// # Returns
+    /// The changed time stamps, in the order given.
+    async fn update_all(
+        &mut self,
+        time_stamps: &[Timestamp],
+        change: impl Fn(usize, &mut Timestamp) -> Result<(), DbError>,
+    ) -> Result<Vec<Timestamp>, DbError> {
+        let positions = time_stamps
+            .iter()
+            .map(|timestamp| {
+                self.position(timestamp)
+                    .ok_or_else(|| DbError::Custom("Timestamp not found".to_string()))
+            })
+            .collect::<Result<Vec<usize>, DbError>>()?;
+        if positions.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut updated = positions
+            .iter()
+            .enumerate()
+            .map(|(index, pos)| {
+                let mut timestamp = self.time_stamps[*pos].clone();
+                change(index, &mut timestamp)?;
+                Ok(timestamp)
+            })
+            .collect::<Result<Vec<Timestamp>, DbError>>()?;
+
+        let regatta_id = self.regatta_id()?;
+        // a connection of its own, so a transaction left open by a failed commit or rollback is closed with it
+        let mut client = timeout(CONNECT_TIMEOUT, self.pool.dedicated_connection())
+            .await
+            .map_err(|_| DbError::Pool(RunError::TimedOut))??;
+        execute_batch(&mut client, "BEGIN TRANSACTION").await?;
+        let mut result = Ok(());
+        for timestamp in &mut updated {
+            result = timestamp
+                .update(regatta_id, AuditAction::Update, &self.author, &mut client)
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+        match result {
+            Ok(()) => execute_batch(&mut client, "COMMIT TRANSACTION").await?,
+            Err(err) => {
+                if let Err(err) = execute_batch(&mut client, "ROLLBACK TRANSACTION").await {
+                    error!(%err, "Error rolling back time stamp changes:");
+                }
+                return Err(err);
+            }
+        }
+
+        for (pos, timestamp) in positions.iter().zip(&updated) {
+            self.time_stamps[*pos] = timestamp.clone();
+            self.journal_synced(timestamp);
         }
-        Ok(timestamp.clone())
+        info!(count = updated.len(), by = self.author, "Changed time stamps:");
+        Ok(updated)
     }
 
-    pub async fn delete(&mut self, time: &DateTime<Utc>) -> Result<Timestamp, DbError> {
-        if let Some(pos) = self.get_index(time)
-            && let Some(timestamp) = self.time_stamps.remove(pos)
-        {
-            let mut client = self.pool.get().await?;
-            timestamp.delete(&mut client).await?;
+    /// Removes a time stamp from the time strip and deletes it in DB. A persisted time stamp that is not loaded, e.g.
+    /// one found by [`TimeStrip::find_by_id`], is deleted in DB only.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to delete, identified by its ID.
+    /// # Returns
+    /// The deleted time stamp.
+    /// # Errors
+    /// If the time stamp is neither part of the time strip nor persisted, or could not be deleted.
+    pub async fn delete(&mut self, timestamp: &Timestamp) -> Result<Timestamp, DbError> {
+        let timestamp = match self.position(timestamp) {
+            Some(pos) => self.time_stamps.remove(pos),
+            None => timestamp.id().map(|_| timestamp.clone()),
+        };
+        let Some(timestamp) = timestamp else {
+            return Err(DbError::Custom("Timestamp not found".to_string()));
+        };
+        // a time stamp that has not been persisted yet only has to be kept from being persisted later
+        if timestamp.id().is_none() {
+            self.journal_discarded(&timestamp);
             return Ok(timestamp);
         }
+        let mut client = connect(&self.pool).await?;
+        timestamp.delete(self.regatta_id()?, &self.author, &mut client).await?;
+        Ok(timestamp)
+    }
+
+    /// Reverts the last change of the heat number or bib of a time stamp. Undoing twice restores the change again.
+    /// # Arguments
+    /// * `timestamp` - The time stamp to revert, identified by its ID.
+    /// # Returns
+    /// The reverted time stamp.
+    /// # Errors
+    /// If the time stamp is not part of the time strip, has never been changed or could not be updated.
+    pub async fn undo(&mut self, timestamp: &Timestamp) -> Result<Timestamp, DbError> {
+        let (Some(id), Some(pos)) = (timestamp.id(), self.position(timestamp)) else {
+            return Err(DbError::Custom("Timestamp not found".to_string()));
+        };
+        let regatta_id = self.regatta_id()?;
+        let mut client = connect(&self.pool).await?;
+        let history = AuditEntry::query_for_timestamp(regatta_id, id, &mut client).await?;
+        let Some(change) = history.iter().find(|entry| entry.action.is_reassignment()) else {
+            return Err(DbError::Custom(format!("Timestamp {id} has never been changed")));
+        };
+        if let Some(timestamp) = self.time_stamps.get_mut(pos) {
+            timestamp.reassign(change.old_heat_nr, change.old_bib);
+            timestamp
+                .update(regatta_id, AuditAction::Undo, &self.author, &mut client)
+                .await?;
+            info!(id, by = self.author, "Undid change of time stamp:");
+            return Ok(timestamp.clone());
+        }
         Err(DbError::Custom("Timestamp not found".to_string()))
     }
 
+    /// Restores a deleted time stamp with the heat number and bib it had when it was deleted. The restored time stamp
+    /// gets a new ID, the audit trail links it to the deleted one.
+    /// # Arguments
+    /// * `timestamp_id` - The ID of the deleted time stamp.
+    /// # Returns
+    /// The restored time stamp.
+    /// # Errors
+    /// If there is no deleted time stamp with the given ID that has not been restored yet, or it could not be inserted.
+    pub async fn restore(&mut self, timestamp_id: i32) -> Result<Timestamp, DbError> {
+        let pool = self.pool.clone();
+        let mut client = connect(&pool).await?;
+        let regatta_id = self.regatta_id()?;
+        let deleted = AuditEntry::query_deleted(regatta_id, &mut client).await?;
+        let Some(entry) = deleted.iter().find(|entry| entry.timestamp_id == timestamp_id) else {
+            return Err(DbError::Custom(format!("No deleted timestamp {timestamp_id}")));
+        };
+        let mut timestamp = Timestamp::from(entry);
+        timestamp
+            .restore(regatta_id, timestamp_id, &self.author, &mut client)
+            .await?;
+        self.insert_ordered(timestamp.clone());
+        info!(
+            timestamp_id,
+            id = timestamp.id(),
+            by = self.author,
+            "Restored time stamp:"
+        );
+        Ok(timestamp)
+    }
+
+    /// Returns the audit trail of a time stamp, newest first.
+    /// # Arguments
+    /// * `timestamp_id` - The ID of the time stamp.
+    pub async fn history(&self, timestamp_id: i32) -> Result<Vec<AuditEntry>, DbError> {
+        let mut client = connect(&self.pool).await?;
+        AuditEntry::query_for_timestamp(self.regatta_id()?, timestamp_id, &mut client).await
+    }
+
+    /// Returns the deleted time stamps that can be restored, newest deletion first.
+    pub async fn deleted(&self) -> Result<Vec<AuditEntry>, DbError> {
+        let mut client = connect(&self.pool).await?;
+        AuditEntry::query_deleted(self.regatta_id()?, &mut client).await
+    }
+
+    /// Computes the provisional ranking of a heat from all its time stamps in DB, including the ones not loaded into
+    /// the time strip.
+    /// # Arguments
+    /// * `heat_nr` - The number of the heat.
+    /// # Returns
+    /// The ranked boats with their net times and the problems found.
+    pub async fn ranking(&self, heat_nr: i16) -> Result<HeatRanking, DbError> {
+        let regatta_id = self.regatta_id()?;
+        let mut client = connect(&self.pool).await?;
+        let filter = TimestampFilter {
+            heat_nr: Some(heat_nr),
+            ..TimestampFilter::default()
+        };
+        let time_stamps =
+            Timestamp::query_for_regatta(regatta_id, &filter, None, 0, MAX_HEAT_TIME_STAMPS, &mut client).await?;
+        let boats = Heat::query_boats(regatta_id, Some(heat_nr), &mut client)
+            .await?
+            .get(&heat_nr)
+            .copied();
+        Ok(HeatRanking::compute(heat_nr, &time_stamps, boats))
+    }
+
+    /// Compiles the timekeeping protocol of a regatta day from all time stamps in DB.
+    /// # Arguments
+    /// * `day` - The regatta day, in local time.
+    pub async fn protocol(&self, day: NaiveDate) -> Result<TimekeepingProtocol, DbError> {
+        let regatta_id = self.regatta_id()?;
+        let mut client = connect(&self.pool).await?;
+        TimekeepingProtocol::query(regatta_id, day, &mut client)
+            .await?
+            .ok_or_else(|| DbError::Custom(format!("Regatta {regatta_id} not found")))
+    }
+
+    /// Loads the heats ready to start or started, to propose heat numbers for the unassigned time stamps with.
+    pub async fn heat_suggester(&self) -> Result<HeatSuggester, DbError> {
+        HeatSuggester::load(self.regatta_id()?, &self.pool).await
+    }
+
+    /// Proposes a heat number for an unassigned time stamp of the time strip.
+    /// # Arguments
+    /// * `suggester` - The suggester holding the heats, see [`TimeStrip::heat_suggester`].
+    /// * `timestamp` - The time stamp to propose a heat number for.
+    pub fn suggest_heat(&self, suggester: &HeatSuggester, timestamp: &Timestamp) -> Option<HeatSuggestion> {
+        suggester.suggest(timestamp, self.time_stamps.iter())
+    }
+
     /// Returns an iterator over the time stamps.
     pub fn iter(&self) -> vec_deque::Iter<'_, Timestamp> {
         self.time_stamps.iter()
@@ -123,7 +979,264 @@ impl TimeStrip {
         self.time_stamps.clone().into()
     }
 
-    fn get_index(&self, time: &DateTime<Utc>) -> Option<usize> {
-        self.time_stamps.iter().position(|timestamp| timestamp.time == *time)
+    /// Returns a reference to the time stamp with the given ID.
+    pub fn get_by_id(&self, id: i32) -> Option<&Timestamp> {
+        self.time_stamps.iter().find(|timestamp| timestamp.id() == Some(id))
+    }
+
+    /// Finds a time stamp by its ID, in DB if it is not loaded, e.g. an older one found by [`TimeStrip::query`].
+    /// # Arguments
+    /// * `id` - The ID of the time stamp.
+    /// # Returns
+    /// The time stamp, or `None` if there is no time stamp with the given ID in the regatta.
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<Timestamp>, DbError> {
+        if let Some(timestamp) = self.get_by_id(id) {
+            return Ok(Some(timestamp.clone()));
+        }
+        let mut client = connect(&self.pool).await?;
+        Timestamp::query_by_id(self.regatta_id()?, id, &mut client).await
+    }
+
+    /// Returns the index of the given time stamp. Persisted time stamps are identified by their ID, time stamps that
+    /// could not be persisted yet by their time.
+    fn position(&self, timestamp: &Timestamp) -> Option<usize> {
+        match timestamp.id() {
+            Some(id) => self.time_stamps.iter().position(|ts| ts.id() == Some(id)),
+            None => self
+                .time_stamps
+                .iter()
+                .position(|ts| ts.id().is_none() && ts.time == timestamp.time),
+        }
+    }
+}
+
+/// Executes a batch without parameters, e.g. to begin or commit a transaction. Unlike parameterized queries, the batch
+/// is not wrapped in `sp_executesql`, so a transaction begun stays open for the following queries.
+async fn execute_batch(client: &mut TiberiusClient, sql: &str) -> Result<(), DbError> {
+    client.simple_query(sql).await?.into_results().await?;
+    Ok(())
+}
+
+/// Returns the valid finish time stamps in the order the boats finished.
+fn finish_order(time_stamps: &[Timestamp]) -> Vec<Timestamp> {
+    let mut finished: Vec<Timestamp> = time_stamps
+        .iter()
+        .filter(|ts| *ts.split() == Split::Finish && !ts.is_voided())
+        .cloned()
+        .collect();
+    finished.sort_by_key(|timestamp| timestamp.time);
+    finished
+}
+
+/// Gets a connection of the pool, waiting at most [`CONNECT_TIMEOUT`].
+/// # Errors
+/// If the DB is unreachable or no connection got free in time.
+async fn connect(pool: &TiberiusPool) -> Result<PooledConnection<'_, TiberiusConnectionManager>, DbError> {
+    timeout(CONNECT_TIMEOUT, pool.get())
+        .await
+        .map_err(|_| DbError::Pool(RunError::TimedOut))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::{env, fs};
+    use ::tiberius::Config;
+
+    /// Creates a pool of a DB that is unreachable.
+    async fn unreachable_pool() -> Arc<TiberiusPool> {
+        let mut config = Config::new();
+        config.host("127.0.0.1");
+        config.port(1);
+        Arc::new(TiberiusPool::new(config, 1, 0).await)
+    }
+
+    /// Creates a time strip whose DB is unreachable, nothing can be persisted.
+    async fn offline_time_strip(journal: Journal) -> TimeStrip {
+        TimeStrip {
+            regatta_id: Some(12),
+            time_stamps: VecDeque::new(),
+            author: "finish".to_owned(),
+            station: Station::new("finish", TimeDelta::zero()),
+            last_change_id: 0,
+            applied_change_ids: HashSet::new(),
+            journal: Some(journal),
+            pool: unreachable_pool().await,
+        }
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_delete_before_persist() {
+        let path = env::temp_dir().join(format!("timekeeper-delete-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = offline_time_strip(Journal::open(&path).unwrap()).await;
+
+        let kept = time_strip.add_start(None, TimestampSource::Keyboard).await;
+        let deleted = time_strip.add_finish(None, TimestampSource::Keyboard).await;
+        assert!(deleted.id().is_none());
+        time_strip.delete(&deleted).await.unwrap();
+        assert_eq!(time_strip.len(), 1);
+
+        // the deleted time stamp is not persisted later, not even after a restart
+        let pending = time_strip.journal.as_ref().unwrap().pending().to_vec();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].to_timestamp().is_same_take(&kept));
+        let journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.pending(), pending.as_slice());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_reassign_before_persist() {
+        let path = env::temp_dir().join(format!("timekeeper-reassign-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = offline_time_strip(Journal::open(&path).unwrap()).await;
+
+        let finish = time_strip.add_finish(None, TimestampSource::Keyboard).await;
+        assert!(time_strip.set_heat_nr(&finish, 7).await.is_err());
+        assert!(time_strip.set_bib(&finish, 3).await.is_err());
+        assert_eq!(
+            time_strip.get(0).map(|ts| (ts.heat_nr(), ts.bib())),
+            Some((Some(7), Some(3)))
+        );
+
+        // the heat number and bib are persisted with the time stamp, even after a restart
+        let journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.pending().len(), 1);
+        let timestamp = journal.pending()[0].to_timestamp();
+        assert!(timestamp.is_same_take(&finish));
+        assert_eq!((timestamp.heat_nr(), timestamp.bib()), (Some(7), Some(3)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_take_before_load() {
+        let path = env::temp_dir().join(format!("timekeeper-offline-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = TimeStrip::offline(unreachable_pool().await, "finish");
+        time_strip.set_journal(Journal::open(&path).unwrap());
+
+        // the time is taken, its regatta is the active one once the time strip is loaded
+        let finish = time_strip.add_finish(None, TimestampSource::Keyboard).await;
+        assert!(!finish.is_persisted());
+        assert!(time_strip.sync().await.is_err());
+        assert_eq!(time_strip.len(), 1);
+        let pending = time_strip.journal.as_ref().unwrap().pending().to_vec();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].regatta_id, None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn change(id: i32, timestamp_id: i32, action: AuditAction, heat_nr: Option<i16>) -> AuditEntry {
+        let time = DateTime::parse_from_rfc3339("2026-06-01T10:07:00Z").unwrap().to_utc();
+        AuditEntry {
+            id,
+            timestamp_id,
+            action,
+            changed_by: "start".to_owned(),
+            changed_at: time,
+            time,
+            split: Split::Finish,
+            old_heat_nr: None,
+            new_heat_nr: heat_nr,
+            old_bib: None,
+            new_bib: None,
+            previous_id: None,
+            source: None,
+            note: None,
+            voided: false,
+            void_reason: None,
+            station: Some("start".to_owned()),
+            raw_time: None,
+        }
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_apply_changes_once() {
+        let path = env::temp_dir().join(format!("timekeeper-apply-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = offline_time_strip(Journal::open(&path).unwrap()).await;
+
+        let entries = vec![
+            change(1, 7, AuditAction::Insert, None),
+            change(2, 7, AuditAction::Update, Some(3)),
+            change(3, 7, AuditAction::Update, Some(4)),
+            change(4, 8, AuditAction::Insert, None),
+            change(5, 8, AuditAction::Delete, None),
+        ];
+        let changes = time_strip.apply(&entries);
+        // the inserted time stamp gets the last heat number, the deleted one is not inserted at all
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(&changes[0], TimeStripChange::Inserted(ts) if ts.id() == Some(7)));
+        assert!(matches!(&changes[1], TimeStripChange::Updated(ts) if ts.heat_nr() == Some(4)));
+        assert_eq!(time_strip.len(), 1);
+        assert_eq!(time_strip.last_change_id, 5);
+
+        // the changes read again by the next sync are not applied twice
+        assert!(time_strip.apply(&entries).is_empty());
+        let changes = time_strip.apply(&[entries[4].clone(), change(6, 7, AuditAction::Delete, None)]);
+        assert!(matches!(changes[..], [TimeStripChange::Deleted(7)]));
+        assert!(time_strip.is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_shift_heat_nrs_invalid() {
+        let path = env::temp_dir().join(format!("timekeeper-shift-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = offline_time_strip(Journal::open(&path).unwrap()).await;
+        time_strip.add_finish(None, TimestampSource::Keyboard).await;
+        time_strip.add_finish(None, TimestampSource::Keyboard).await;
+        time_strip.time_stamps[0].set_heat_nr(2);
+        time_strip.time_stamps[1].set_heat_nr(1);
+
+        // all heat numbers are checked before anything is updated in DB
+        let time_stamps: Vec<Timestamp> = time_strip.iter().cloned().collect();
+        let err = time_strip.shift_heat_nrs(&time_stamps, -1).await.unwrap_err();
+        assert!(
+            err.to_string().contains("Cannot shift heat number Some(1) by -1"),
+            "{err}"
+        );
+        let heat_nrs: Vec<Option<i16>> = time_strip.iter().map(Timestamp::heat_nr).collect();
+        assert_eq!(heat_nrs, vec![Some(2), Some(1)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio_shared_rt::test(shared)]
+    async fn test_sync_unreachable_db() {
+        let path = env::temp_dir().join(format!("timekeeper-sync-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut time_strip = offline_time_strip(Journal::open(&path).unwrap()).await;
+        time_strip.add_finish(None, TimestampSource::Keyboard).await;
+
+        // an unreachable DB does not block the station
+        let start = Instant::now();
+        assert!(time_strip.sync().await.is_err());
+        assert!(start.elapsed() < 3 * CONNECT_TIMEOUT);
+        assert_eq!(time_strip.len(), 1);
+        assert_eq!(time_strip.journal.as_ref().unwrap().pending().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_finish_order() {
+        let time = Utc::now();
+        let second = Timestamp::new(time + TimeDelta::seconds(2), Split::Finish, Some(3));
+        let first = Timestamp::new(time + TimeDelta::seconds(1), Split::Finish, Some(3));
+        let mut voided = Timestamp::new(time + TimeDelta::seconds(3), Split::Finish, Some(3));
+        voided.set_void(Some("double take".to_owned()));
+        let time_stamps = [
+            second.clone(),
+            Timestamp::new(time, Split::Start, Some(3)),
+            first.clone(),
+            Timestamp::new(time + TimeDelta::milliseconds(1500), Split::Intermediate(1), Some(3)),
+            voided,
+        ];
+
+        // only the valid finish time stamps get bibs
+        let finished = finish_order(&time_stamps);
+        assert_eq!(finished.len(), 2);
+        assert!(finished[0].is_same_take(&first));
+        assert!(finished[1].is_same_take(&second));
     }
 }
@@ -1,4 +1,6 @@
 use crate::built_info;
+use ::aquarius::codec::Codec;
+use ::aquarius::endpoint::Endpoint;
 use ::db::tiberius_client::{AuthMethod, Config as TiberiusConfig, EncryptionLevel};
 use ::dotenv::dotenv;
 use ::secret_string::SecretString;
@@ -66,13 +68,16 @@ pub struct Config {
     pub active_regatta_id: Option<i32>,
     /// The cache TTL in seconds. The cache TTL can be set by setting the environment variable `CACHE_TTL`.
     pub cache_ttl: u64,
-    /// The Aquarius host. The Aquarius host can be set by setting the environment variable `AQUARIUS_HOST`. Defaults to `aquarius`.
-    pub aquarius_host: String,
-    /// The Aquarius port. The Aquarius port can be set by setting the environment variable `AQUARIUS_PORT`. Defaults to `2048`.
-    pub aquarius_port: u16,
+    /// The Aquarius endpoints, starting with the primary one. The endpoints can be set by setting the environment variable
+    /// `AQUARIUS_HOST` to a comma separated list, e.g. `aquarius,aquarius-backup:2049`. Endpoints without a port use the
+    /// port set by the environment variable `AQUARIUS_PORT`. Defaults to `aquarius` and `2048`.
+    pub aquarius_endpoints: Vec<Endpoint>,
     /// The connection timeout for the Aquarius client in milliseconds. The timeout can be set by setting the environment variable `AQUARIUS_TIMEOUT`.
     /// Defaults to `500`.
     pub aquarius_timeout: u16,
+    /// The text encoding of the link to Aquarius, either `windows1252` or `utf8`. The encoding can be set by setting the
+    /// environment variable `AQUARIUS_CODEC`. Defaults to `windows1252`.
+    pub aquarius_codec: Codec,
 }
 
 impl Config {
@@ -154,10 +159,9 @@ impl Config {
             cache_ttl: Self::parse_env_var(consts::CACHE_TTL, consts::DEFAULT_CACHE_TTL)?,
             http_app_content_path: env::var(consts::HTTP_APP_CONTENT_PATH)
                 .unwrap_or_else(|_| consts::DEFAULT_STATIC_CONTENT_PATH.to_owned()),
-            aquarius_host: env::var(consts::AQUARIUS_HOST)
-                .unwrap_or_else(|_| consts::DEFAULT_AQUARIUS_HOST.to_string()),
-            aquarius_port: Self::parse_env_var(consts::AQUARIUS_PORT, consts::DEFAULT_AQUARIUS_PORT)?,
+            aquarius_endpoints: Self::parse_aquarius_endpoints()?,
             aquarius_timeout: Self::parse_env_var(consts::AQUARIUS_TIMEOUT, consts::DEFAULT_AQUARIUS_TIMEOUT)?,
+            aquarius_codec: Self::parse_env_var(consts::AQUARIUS_CODEC, Codec::default())?,
         };
         // Validate database configuration values
         Self::validate_db_config(
@@ -197,9 +201,14 @@ impl Config {
         );
         info!(path = config.http_app_content_path, "Serving static content:");
         info!(
-            host = config.aquarius_host,
-            port = config.aquarius_port,
+            endpoints = config
+                .aquarius_endpoints
+                .iter()
+                .map(Endpoint::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
             timeout_in_ms = config.aquarius_timeout,
+            codec = ?config.aquarius_codec,
             "Aquarius Client:"
         );
 
@@ -286,6 +295,17 @@ impl Config {
         }
     }
 
+    /// Helper function to parse the comma separated list of Aquarius endpoints
+    fn parse_aquarius_endpoints() -> Result<Vec<Endpoint>, ConfigError> {
+        let hosts = env::var(consts::AQUARIUS_HOST).unwrap_or_else(|_| consts::DEFAULT_AQUARIUS_HOST.to_string());
+        let port = Self::parse_env_var(consts::AQUARIUS_PORT, consts::DEFAULT_AQUARIUS_PORT)?;
+        Endpoint::parse_list(&hosts, port).map_err(|e| ConfigError::ParseError {
+            var_name: consts::AQUARIUS_HOST.to_string(),
+            value: hosts.clone(),
+            error: e.to_string(),
+        })
+    }
+
     /// Helper function to get required environment variable
     fn get_required_env_var(var_name: &str) -> Result<String, ConfigError> {
         env::var(var_name).map_err(|_| ConfigError::MissingRequired(var_name.to_string()))
@@ -347,6 +367,7 @@ mod consts {
     pub(super) const AQUARIUS_HOST: &str = "AQUARIUS_HOST";
     pub(super) const AQUARIUS_PORT: &str = "AQUARIUS_PORT";
     pub(super) const AQUARIUS_TIMEOUT: &str = "AQUARIUS_TIMEOUT";
+    pub(super) const AQUARIUS_CODEC: &str = "AQUARIUS_CODEC";
 
     // Default values
     pub(super) const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0";
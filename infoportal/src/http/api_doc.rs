@@ -31,6 +31,7 @@ use ::utoipa_swagger_ui::SwaggerUi;
         rest_api::notification::update_notification,
         rest_api::notification::delete_notification,
         rest_api::notification::notification_read,
+        rest_api::timekeeping::get_timekeeping_protocol,
     ),
     tags(
         (name = "regatta-infopoint", description = "Regatta Infopoint REST Endpoints.")
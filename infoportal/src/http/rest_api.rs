@@ -165,6 +165,7 @@ pub(crate) fn config(cfg: &mut ServiceConfig) {
             .service(misc::get_statistics)
             .service(misc::get_schedule)
             .service(timekeeping::get_timekeeping_ws)
+            .service(timekeeping::get_timekeeping_protocol)
             .service(notification::get_visible_notifications)
             .service(notification::get_all_notifications)
             .service(notification::create_notification)
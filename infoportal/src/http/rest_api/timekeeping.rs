@@ -1,39 +1,53 @@
 use crate::config::CONFIG;
+use crate::http::rest_api::ApiError;
+use crate::http::rest_api::INTERNAL_SERVER_ERROR;
+use crate::http::rest_api::PATH;
 use crate::http::rest_api::get_user_pool;
 use ::actix::ActorFutureExt;
 use ::actix::Message as ActixMessage;
 use ::actix::StreamHandler;
-use ::actix::{Actor, ActorContext, Addr, AsyncContext, Handler};
+use ::actix::{Actor, ActorContext, AsyncContext, Handler};
 use ::actix_identity::Identity;
 use ::actix_web::Error;
 use ::actix_web::HttpRequest;
 use ::actix_web::HttpResponse;
+use ::actix_web::error::ErrorNotFound;
 use ::actix_web::get;
+use ::actix_web::http::header::CONTENT_DISPOSITION;
 use ::actix_web::web::Data;
+use ::actix_web::web::Path;
 use ::actix_web::web::Payload;
 use ::actix_web_actors::ws;
 use ::actix_web_actors::ws::Message;
 use ::actix_web_actors::ws::ProtocolError;
 use ::actix_web_actors::ws::WebsocketContext;
-use ::aquarius::client::AquariusClient;
+use ::aquarius::async_client::{AquariusEventStream, AsyncAquariusClient};
 use ::aquarius::event::AquariusEvent;
 use ::aquarius::messages::Heat as AquariusHeat;
+use ::aquarius::messages::{EventResultUpdated, EventTimeConfirmed};
+use ::aquarius::status::{ConnectionState, ConnectionStats};
 use ::chrono::DateTime;
+use ::chrono::NaiveDate;
 use ::chrono::Utc;
 use ::db::aquarius::Aquarius;
 use ::db::aquarius::model::Heat as DbHeat;
+use ::db::error::DbError;
 use ::db::tiberius::TiberiusPool;
 use ::db::tiberius::user_pool::UserPoolManager;
+use ::db::timekeeper::AuditEntry;
+use ::db::timekeeper::HeatRanking;
+use ::db::timekeeper::ProtocolFormat;
+use ::db::timekeeper::Station;
 use ::db::timekeeper::TimeStrip;
+use ::db::timekeeper::TimeStripChange;
+use ::db::timekeeper::TimekeepingProtocol;
 use ::db::timekeeper::Timestamp;
+use ::db::timekeeper::TimestampFilter;
+use ::db::timekeeper::TimestampSource;
 use ::serde::Deserialize;
 use ::serde::Serialize;
 use ::std::sync::Arc;
-use ::std::sync::RwLock;
-use ::std::sync::mpsc;
-use ::std::sync::mpsc::Receiver;
-use ::std::thread;
-use ::std::time::Instant;
+use ::std::time::{Duration, Instant};
 use ::tracing::debug;
 use ::tracing::error;
 use ::tracing::trace;
@@ -41,6 +55,9 @@ use ::tracing::warn;
 
 use super::{WS_CLIENT_TIMEOUT, WS_HEARTBEAT_INTERVAL};
 
+/// How often the timestrip is synced with the changes made by other stations or sessions.
+const TIMESTRIP_SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
 /// A timekeeping command sent from the client to trigger timekeeping actions on the server.
 /// Direction: Client -> Server
 #[derive(Debug, Deserialize)]
@@ -49,30 +66,141 @@ enum TimekeepingCommand {
     AddStart {
         /// The time of the timestamp to add
         time: Option<DateTime<Utc>>,
+        /// How the timestamp was taken, defaults to the websocket, e.g. `manual` for a time of a backup stopwatch
+        #[serde(default)]
+        source: Option<TimestampSource>,
+    },
+    /// Add an intermediate timestamp to the timestrip, e.g. taken at a 500 m tower
+    AddIntermediate {
+        /// The split number of the intermediate split
+        split: u8,
+        /// The time of the timestamp to add
+        time: Option<DateTime<Utc>>,
+        /// How the timestamp was taken, defaults to the websocket, e.g. `manual` for a time of a backup stopwatch
+        #[serde(default)]
+        source: Option<TimestampSource>,
     },
     /// Add a finish timestamp to the timestrip
     AddFinish {
         /// The time of the timestamp to add
         time: Option<DateTime<Utc>>,
+        /// How the timestamp was taken, defaults to the websocket, e.g. `manual` for a time of a backup stopwatch
+        #[serde(default)]
+        source: Option<TimestampSource>,
     },
     /// Delete a timestamp from the timestrip
     DeleteTimestamp {
-        /// The time of the timestamp to delete
-        time: DateTime<Utc>,
+        /// The ID of the timestamp to delete
+        id: i32,
     },
-    /// Update a timestamp with a new heat number
+    /// Update a timestamp with a new heat number or bib
     UpdateTimestamp {
-        /// The time of the timestamp to update
-        time: DateTime<Utc>,
-        /// The new heat number to set for the timestamp
+        /// The ID of the timestamp to update
+        id: i32,
+        /// The new heat number to set for the timestamp, if any
+        #[serde(default)]
+        heat_nr: Option<i16>,
+        /// The new bib to set for the timestamp, if any
+        #[serde(default)]
+        bib: Option<u8>,
+    },
+    /// Set the heat number of several timestamps, e.g. of all timestamps taken in a busy block
+    UpdateTimestamps {
+        /// The IDs of the timestamps to update
+        ids: Vec<i32>,
+        /// The new heat number to set for the timestamps
+        heat_nr: i16,
+    },
+    /// Assign the bibs of the start list of a heat to its finish timestamps in the order the boats finished
+    AssignBibs {
+        /// The IDs of the timestamps to update
+        ids: Vec<i32>,
+        /// The bibs in the order the boats finished
+        bibs: Vec<u8>,
+    },
+    /// Shift the heat numbers of several timestamps, e.g. by -1 to assign them to the previous heats
+    ShiftHeatNumbers {
+        /// The IDs of the timestamps to update
+        ids: Vec<i32>,
+        /// The number added to the heat numbers
+        delta: i16,
+    },
+    /// Revert the last change of the heat number or bib of a timestamp
+    UndoTimestamp {
+        /// The ID of the timestamp to revert
+        id: i32,
+    },
+    /// Set or clear the note of a timestamp
+    SetTimestampNote {
+        /// The ID of the timestamp to update
+        id: i32,
+        /// The new note, or `None` to clear it
+        note: Option<String>,
+    },
+    /// Void a timestamp, e.g. after a false start, or make a void timestamp valid again
+    SetTimestampVoid {
+        /// The ID of the timestamp to update
+        id: i32,
+        /// Why the timestamp is void, or `None` to make it valid again
+        reason: Option<String>,
+    },
+    /// Restore a deleted timestamp
+    RestoreTimestamp {
+        /// The ID of the deleted timestamp
+        id: i32,
+    },
+    /// Get the audit trail of a timestamp
+    GetTimestampHistory {
+        /// The ID of the timestamp
+        id: i32,
+    },
+    /// Get the deleted timestamps that can be restored
+    GetDeletedTimestamps,
+    /// Get the provisional ranking of a heat computed from its start and finish timestamps
+    GetRanking {
+        /// The number of the heat
         heat_nr: i16,
     },
+    /// Start measuring the offset of the client clock against the DB server, it is answered with the DB server time
+    SyncClock {
+        /// The time the client sent the command at, by its clock
+        time: DateTime<Utc>,
+    },
+    /// Finish measuring the offset of the client clock against the DB server by the round trip of `SyncClock`, it is
+    /// applied to the timestamps sent by the client
+    SetClockOffset {
+        /// The time the client sent `SyncClock` at, by its clock
+        sent: DateTime<Utc>,
+        /// The DB server time the client received
+        db_time: DateTime<Utc>,
+        /// The time the client received the DB server time at, by its clock
+        received: DateTime<Utc>,
+    },
     /// Get the current timestrip data
     GetTimestrip,
+    /// Load the next page of older timestamps into the timestrip
+    LoadMoreTimestamps,
+    /// Query the timestamps of the regatta, e.g. of a time window, a split or a heat
+    QueryTimestamps {
+        /// The filter the timestamps have to match
+        #[serde(flatten)]
+        filter: TimestampFilter,
+        /// The number of matching timestamps to skip
+        #[serde(default)]
+        offset: i32,
+        /// The maximum number of timestamps to return
+        #[serde(default = "default_query_limit")]
+        limit: i32,
+    },
     /// Get the current heats open in Aquarius
     GetHeatsReadyToStart,
 }
 
+/// The default number of timestamps returned by a query
+fn default_query_limit() -> i32 {
+    100
+}
+
 /// Events sent from the server to the client to update the UI with timekeeping-related information.
 /// Direction: Server -> Client
 #[derive(ActixMessage)]
@@ -81,10 +209,62 @@ enum TimekeepingCommand {
 enum ServerEvent {
     /// Event to send the current heats open in Aquarius to the client
     AquariusHeats { heats: Vec<AquariusHeat> },
+    /// Event to send the state and statistics of the connection to Aquarius to the client
+    AquariusConnection {
+        /// The current state of the connection
+        state: ConnectionState,
+        /// The statistics of the connection
+        stats: ConnectionStats,
+    },
+    /// Event to send a time confirmed by Aquarius to the client
+    AquariusTimeConfirmed { time: EventTimeConfirmed },
+    /// Event to send an updated result of a boat to the client
+    AquariusResult { result: EventResultUpdated },
+    /// Event to send a time from the outbox rejected by Aquarius to the client, it is missing in Aquarius
+    AquariusTimeRejected {
+        /// The `TIME` command that was rejected
+        command: String,
+        /// The reason given by Aquarius
+        reason: String,
+    },
     /// Event to send the current timestrip data to the client
     TimeStrip { time_stamps: Vec<Timestamp> },
+    /// Event to send the result of a timestamp query to the client
+    TimestampQuery {
+        /// The matching timestamps, newest first
+        time_stamps: Vec<Timestamp>,
+        /// The number of matching timestamps skipped
+        offset: i32,
+    },
     /// Event to send a single timestamp update to the client
     Timestamp { timestamp: Timestamp },
+    /// Event to send the timestamps changed by a bulk operation to the client
+    Timestamps { time_stamps: Vec<Timestamp> },
+    /// Event to send the audit trail of a timestamp to the client
+    TimestampHistory {
+        /// The ID of the timestamp
+        id: i32,
+        /// The changes of the timestamp, newest first
+        history: Vec<AuditEntry>,
+    },
+    /// Event to send the timestamps inserted, updated or deleted by other stations to the client
+    TimeStripChanged { changes: Vec<TimeStripChange> },
+    /// Event to send the DB server time to the client, which answers it with `SetClockOffset`
+    ClockTime {
+        /// The time the client sent `SyncClock` at, by its clock
+        sent: DateTime<Utc>,
+        /// The current time of the DB server
+        db_time: DateTime<Utc>,
+    },
+    /// Event to send the station of the client with its measured clock offset to the client
+    ClockOffset { station: Station },
+    /// Event to send the provisional ranking of a heat to the client
+    Ranking { ranking: HeatRanking },
+    /// Event to send the deleted timestamps that can be restored to the client
+    DeletedTimestamps {
+        /// The deletions, newest first
+        deleted: Vec<AuditEntry>,
+    },
     /// Event to send the current heats ready to start to the client
     HeatsReadyToStart { heats: Vec<DbHeat> },
     /// Event to send an error message to the client
@@ -101,8 +281,10 @@ enum ServerEvent {
 struct AddTimestamp {
     /// The time of the timestamp to add (if None, the current time will be used)
     time: Option<DateTime<Utc>>,
-    /// The split number for the timestamp (0 for start, 64 for finish)
+    /// The split number for the timestamp (0 for start, 64 for finish, any other for an intermediate split)
     split: u8,
+    /// How the timestamp was taken
+    source: TimestampSource,
 }
 
 /// Message to trigger deleting a timestamp
@@ -110,25 +292,153 @@ struct AddTimestamp {
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
 struct DeleteTimestamp {
-    /// The time of the timestamp to delete
-    time: DateTime<Utc>,
+    /// The ID of the timestamp to delete
+    id: i32,
 }
 
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
 struct UpdateTimestamp {
-    /// The time of the timestamp to update
-    time: DateTime<Utc>,
-    /// The new heat number to set for the timestamp
+    /// The ID of the timestamp to update
+    id: i32,
+    /// The new heat number to set for the timestamp, if any
+    heat_nr: Option<i16>,
+    /// The new bib to set for the timestamp, if any
+    bib: Option<u8>,
+}
+
+/// A change of several timestamps, see [`TimekeepingCommand`]
+enum BulkChange {
+    /// Set the heat number
+    HeatNr(i16),
+    /// Assign the bibs in the order the boats finished
+    Bibs(Vec<u8>),
+    /// Shift the heat numbers by the given number
+    Shift(i16),
+}
+
+/// Message to trigger changing several timestamps in one transaction
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct UpdateTimestamps {
+    /// The IDs of the timestamps to update
+    ids: Vec<i32>,
+    /// The change to apply to the timestamps
+    change: BulkChange,
+}
+
+/// Message to trigger reverting the last change of a timestamp
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct UndoTimestamp {
+    /// The ID of the timestamp to revert
+    id: i32,
+}
+
+/// Message to trigger setting or clearing the note of a timestamp
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct SetTimestampNote {
+    /// The ID of the timestamp to update
+    id: i32,
+    /// The new note, or `None` to clear it
+    note: Option<String>,
+}
+
+/// Message to trigger voiding a timestamp or making it valid again
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct SetTimestampVoid {
+    /// The ID of the timestamp to update
+    id: i32,
+    /// Why the timestamp is void, or `None` to make it valid again
+    reason: Option<String>,
+}
+
+/// Message to trigger restoring a deleted timestamp and sending the timestrip back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct RestoreTimestamp {
+    /// The ID of the deleted timestamp
+    id: i32,
+}
+
+/// Message to trigger loading the audit trail of a timestamp and sending it back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct GetTimestampHistory {
+    /// The ID of the timestamp
+    id: i32,
+}
+
+/// Message to trigger loading the deleted timestamps and sending them back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct GetDeletedTimestamps;
+
+/// Message to trigger computing the provisional ranking of a heat and sending it back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct GetRanking {
+    /// The number of the heat
     heat_nr: i16,
 }
 
+/// Message to trigger querying the DB server time and sending it back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct SyncClock {
+    /// The time the client sent the command at, by its clock
+    time: DateTime<Utc>,
+}
+
+/// Message to trigger setting the clock offset of the client and sending it back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct SetClockOffset {
+    /// The time the client sent `SyncClock` at, by its clock
+    sent: DateTime<Utc>,
+    /// The DB server time the client received
+    db_time: DateTime<Utc>,
+    /// The time the client received the DB server time at, by its clock
+    received: DateTime<Utc>,
+}
+
 /// Message to trigger loading the current timestrip and sending it back to the client
 /// Direction: Server -> Server
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
 struct GetTimestrip;
 
+/// Message to trigger loading older timestamps into the timestrip and sending the timestrip back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct LoadMoreTimestamps;
+
+/// Message to trigger querying timestamps and sending them back to the client
+/// Direction: Server -> Server
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+struct QueryTimestamps {
+    /// The filter the timestamps have to match
+    filter: TimestampFilter,
+    /// The number of matching timestamps to skip
+    offset: i32,
+    /// The maximum number of timestamps to return
+    limit: i32,
+}
+
 /// Message to trigger loading the current heats ready to start from Aquarius and sending them back to the client
 /// Direction: Server -> Server
 #[derive(ActixMessage)]
@@ -137,35 +447,62 @@ struct GetHeatsReadyToStart;
 
 struct TimekeepingActor {
     heart_beat: Instant,
-    aquarius_client: Option<Arc<AquariusClient>>,
+    aquarius_client: Option<Arc<AsyncAquariusClient>>,
     aquarius_db: Data<Aquarius>,
-    heats: Arc<RwLock<Vec<AquariusHeat>>>,
-    event_receiver: Option<Receiver<AquariusEvent>>,
+    heats: Vec<AquariusHeat>,
+    aquarius_events: Option<AquariusEventStream>,
     time_strip: Arc<::tokio::sync::RwLock<TimeStrip>>,
 }
 
 impl TimekeepingActor {
-    async fn new(pool: Arc<TiberiusPool>, aquarius_db: Data<Aquarius>) -> Self {
-        let (event_sender, event_receiver) = mpsc::channel();
-        let client = AquariusClient::new(
-            &CONFIG.aquarius_host,
-            CONFIG.aquarius_port,
-            CONFIG.aquarius_timeout,
-            event_sender,
-        );
-        let aquarius_client = match client {
-            Ok(aquarius) => Some(Arc::new(aquarius)),
-            Err(_) => None,
+    /// Creates the actor of a timekeeping websocket and loads the time strip of the active regatta.
+    /// # Errors
+    /// If the time strip could not be loaded, e.g. because the DB is unreachable.
+    async fn new(pool: Arc<TiberiusPool>, aquarius_db: Data<Aquarius>, user: &str) -> Result<Self, DbError> {
+        let time_strip = TimeStrip::load(pool, user).await?;
+        let client =
+            AsyncAquariusClient::with_endpoints(CONFIG.aquarius_endpoints.clone(), CONFIG.aquarius_timeout).await;
+        let (aquarius_client, aquarius_events) = match client {
+            Ok((aquarius, events)) => {
+                aquarius.set_codec(CONFIG.aquarius_codec);
+                (Some(Arc::new(aquarius)), Some(events))
+            }
+            Err(_) => (None, None),
         };
 
-        Self {
+        Ok(Self {
             heart_beat: Instant::now(),
             aquarius_client,
-            heats: Arc::new(RwLock::new(Vec::new())),
-            event_receiver: Some(event_receiver),
-            time_strip: Arc::new(::tokio::sync::RwLock::new(TimeStrip::load(pool.clone()).await.unwrap())),
+            heats: Vec::new(),
+            aquarius_events,
+            time_strip: Arc::new(::tokio::sync::RwLock::new(time_strip)),
             aquarius_db,
-        }
+        })
+    }
+
+    /// Sends the heats currently open in Aquarius to the client.
+    fn send_aquarius_heats(&self, ctx: &mut <Self as Actor>::Context) {
+        ctx.address().do_send(ServerEvent::AquariusHeats {
+            heats: self.heats.clone(),
+        });
+    }
+
+    /// Periodically merges the changes of other stations into the timestrip and sends them to the client.
+    fn start_sync(&self, ctx: &mut <Self as Actor>::Context) {
+        ctx.run_interval(TIMESTRIP_SYNC_INTERVAL, |act, ctx| {
+            let time_strip = act.time_strip.clone();
+            ctx.spawn(
+                actix::fut::wrap_future(async move { time_strip.write().await.sync().await }).map(
+                    |result, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| match result {
+                        Ok(changes) if !changes.is_empty() => {
+                            ctx.address().do_send(ServerEvent::TimeStripChanged { changes });
+                        }
+                        Ok(_) => {}
+                        Err(err) => warn!(%err, "Failed to sync timestrip"),
+                    },
+                ),
+            );
+        });
     }
 
     fn start_heart_beat(&self, ctx: &mut <Self as Actor>::Context) {
@@ -195,13 +532,65 @@ impl StreamHandler<Result<Message, ProtocolError>> for TimekeepingActor {
             }
             Ok(Message::Text(text)) => match serde_json::from_str::<TimekeepingCommand>(&text) {
                 Ok(cmd_msg) => match cmd_msg {
-                    TimekeepingCommand::AddStart { time } => ctx.address().do_send(AddTimestamp { split: 0, time }),
-                    TimekeepingCommand::AddFinish { time } => ctx.address().do_send(AddTimestamp { split: 64, time }),
+                    TimekeepingCommand::AddStart { time, source } => ctx.address().do_send(AddTimestamp {
+                        split: 0,
+                        time,
+                        source: source.unwrap_or(TimestampSource::Websocket),
+                    }),
+                    TimekeepingCommand::AddIntermediate { split, time, source } => {
+                        ctx.address().do_send(AddTimestamp {
+                            split,
+                            time,
+                            source: source.unwrap_or(TimestampSource::Websocket),
+                        })
+                    }
+                    TimekeepingCommand::AddFinish { time, source } => ctx.address().do_send(AddTimestamp {
+                        split: 64,
+                        time,
+                        source: source.unwrap_or(TimestampSource::Websocket),
+                    }),
+                    TimekeepingCommand::SyncClock { time } => ctx.address().do_send(SyncClock { time }),
+                    TimekeepingCommand::SetClockOffset {
+                        sent,
+                        db_time,
+                        received,
+                    } => ctx.address().do_send(SetClockOffset {
+                        sent,
+                        db_time,
+                        received,
+                    }),
                     TimekeepingCommand::GetTimestrip => ctx.address().do_send(GetTimestrip),
-                    TimekeepingCommand::DeleteTimestamp { time } => ctx.address().do_send(DeleteTimestamp { time }),
-                    TimekeepingCommand::UpdateTimestamp { time, heat_nr } => {
-                        ctx.address().do_send(UpdateTimestamp { time, heat_nr })
+                    TimekeepingCommand::LoadMoreTimestamps => ctx.address().do_send(LoadMoreTimestamps),
+                    TimekeepingCommand::QueryTimestamps { filter, offset, limit } => {
+                        ctx.address().do_send(QueryTimestamps { filter, offset, limit })
                     }
+                    TimekeepingCommand::DeleteTimestamp { id } => ctx.address().do_send(DeleteTimestamp { id }),
+                    TimekeepingCommand::UpdateTimestamp { id, heat_nr, bib } => {
+                        ctx.address().do_send(UpdateTimestamp { id, heat_nr, bib })
+                    }
+                    TimekeepingCommand::UpdateTimestamps { ids, heat_nr } => ctx.address().do_send(UpdateTimestamps {
+                        ids,
+                        change: BulkChange::HeatNr(heat_nr),
+                    }),
+                    TimekeepingCommand::AssignBibs { ids, bibs } => ctx.address().do_send(UpdateTimestamps {
+                        ids,
+                        change: BulkChange::Bibs(bibs),
+                    }),
+                    TimekeepingCommand::ShiftHeatNumbers { ids, delta } => ctx.address().do_send(UpdateTimestamps {
+                        ids,
+                        change: BulkChange::Shift(delta),
+                    }),
+                    TimekeepingCommand::UndoTimestamp { id } => ctx.address().do_send(UndoTimestamp { id }),
+                    TimekeepingCommand::SetTimestampNote { id, note } => {
+                        ctx.address().do_send(SetTimestampNote { id, note })
+                    }
+                    TimekeepingCommand::SetTimestampVoid { id, reason } => {
+                        ctx.address().do_send(SetTimestampVoid { id, reason })
+                    }
+                    TimekeepingCommand::RestoreTimestamp { id } => ctx.address().do_send(RestoreTimestamp { id }),
+                    TimekeepingCommand::GetTimestampHistory { id } => ctx.address().do_send(GetTimestampHistory { id }),
+                    TimekeepingCommand::GetDeletedTimestamps => ctx.address().do_send(GetDeletedTimestamps),
+                    TimekeepingCommand::GetRanking { heat_nr } => ctx.address().do_send(GetRanking { heat_nr }),
                     TimekeepingCommand::GetHeatsReadyToStart => ctx.address().do_send(GetHeatsReadyToStart),
                 },
                 Err(err) => {
@@ -219,6 +608,78 @@ impl StreamHandler<Result<Message, ProtocolError>> for TimekeepingActor {
     }
 }
 
+/// Stream handler for the events emitted by the Aquarius client.
+/// Direction: Aquarius -> Server
+impl StreamHandler<AquariusEvent> for TimekeepingActor {
+    fn handle(&mut self, event: AquariusEvent, ctx: &mut Self::Context) {
+        match event {
+            AquariusEvent::HeatListChanged(event) => {
+                debug!("Received HeatListChanged event = {:?}", &event);
+                if event.opened {
+                    self.heats.push(event.heat);
+                } else {
+                    self.heats.retain(|heat| heat.id != event.heat.id);
+                }
+                self.send_aquarius_heats(ctx);
+            }
+            AquariusEvent::HeatStateChanged(event) => {
+                debug!("Received HeatStateChanged event = {:?}", &event);
+                if let Some(heat) = self.heats.iter_mut().find(|heat| heat.id == event.heat.id) {
+                    heat.set_state(event.heat.state);
+                    self.send_aquarius_heats(ctx);
+                }
+            }
+            AquariusEvent::TimeConfirmed(time) => {
+                debug!("Received TimeConfirmed event = {:?}", &time);
+                ctx.address().do_send(ServerEvent::AquariusTimeConfirmed { time });
+            }
+            AquariusEvent::ResultUpdated(result) => {
+                debug!("Received ResultUpdated event = {:?}", &result);
+                ctx.address().do_send(ServerEvent::AquariusResult { result });
+            }
+            AquariusEvent::Unknown(line) => debug!(line, "Ignoring unknown Aquarius event"),
+            AquariusEvent::QueuedTimeRejected { item, reason } => {
+                warn!(command = item.command, reason, "Aquarius rejected queued time");
+                ctx.address().do_send(ServerEvent::AquariusTimeRejected {
+                    command: item.command,
+                    reason,
+                });
+            }
+            AquariusEvent::Client(state) => {
+                if let Some(aquarius_client) = &self.aquarius_client {
+                    ctx.address().do_send(ServerEvent::AquariusConnection {
+                        state: state.clone(),
+                        stats: aquarius_client.stats(),
+                    });
+                }
+                if state.is_connected() {
+                    if let Some(aquarius_client) = self.aquarius_client.clone() {
+                        ctx.spawn(
+                            actix::fut::wrap_future(async move { aquarius_client.read_open_heats().await }).map(
+                                |result, actor: &mut Self, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                                    match result {
+                                        Ok(open_heats) => actor.heats = open_heats,
+                                        Err(err) => error!(%err, "Failed to read open heats from Aquarius client"),
+                                    }
+                                    actor.send_aquarius_heats(ctx);
+                                },
+                            ),
+                        );
+                    }
+                } else if matches!(state, ConnectionState::Disconnected { .. }) && !self.heats.is_empty() {
+                    self.heats.clear();
+                    self.send_aquarius_heats(ctx);
+                }
+            }
+        }
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {
+        // The Aquarius client has been shut down, the websocket stays open.
+        debug!("Aquarius event stream finished");
+    }
+}
+
 impl Handler<ServerEvent> for TimekeepingActor {
     type Result = ();
 
@@ -234,22 +695,19 @@ impl Handler<AddTimestamp> for TimekeepingActor {
     fn handle(&mut self, msg: AddTimestamp, ctx: &mut Self::Context) -> Self::Result {
         let time_strip = self.time_strip.clone();
         let split = msg.split;
+        let source = msg.source;
 
         ctx.wait(
             actix::fut::wrap_future(async move {
                 let mut time_strip = time_strip.write().await;
                 let timestamp = match split {
-                    0 => time_strip
-                        .add_start(msg.time)
+                    // taking a start or finish time never fails, it is persisted later if the DB is unreachable
+                    0 => time_strip.add_start(msg.time, source).await,
+                    64 => time_strip.add_finish(msg.time, source).await,
+                    _ => time_strip
+                        .add_intermediate(split, msg.time, source)
                         .await
-                        .map_err(|err| format!("Failed to add start timestamp: {err}"))?,
-                    64 => time_strip
-                        .add_finish(msg.time)
-                        .await
-                        .map_err(|err| format!("Failed to add finish timestamp: {err}"))?,
-                    _ => {
-                        return Err(format!("Invalid split number: {split}"));
-                    }
+                        .map_err(|err| format!("Failed to add intermediate timestamp: {err}"))?,
                 };
                 Ok(timestamp)
             })
@@ -276,7 +734,12 @@ impl Handler<DeleteTimestamp> for TimekeepingActor {
             actix::fut::wrap_future(async move {
                 let mut time_strip = time_strip.write().await;
                 let timestamp = time_strip
-                    .delete(&msg.time)
+                    .find_by_id(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to find timestamp: {err}"))?
+                    .ok_or_else(|| format!("Timestamp with ID {} not found", msg.id))?;
+                let timestamp = time_strip
+                    .delete(&timestamp)
                     .await
                     .map_err(|err| format!("Failed to delete timestamp: {err}"))?;
                 Ok(timestamp)
@@ -301,16 +764,29 @@ impl Handler<UpdateTimestamp> for TimekeepingActor {
         ctx.wait(
             actix::fut::wrap_future(async move {
                 let mut time_strip = time_strip.write().await;
-                let timestamp = time_strip.get_by_time(&msg.time).cloned();
-                if let Some(timestamp) = timestamp {
-                    let timestamp = time_strip
-                        .set_heat_nr(&timestamp, msg.heat_nr)
+                let timestamp = time_strip
+                    .find_by_id(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to find timestamp: {err}"))?;
+                let Some(mut timestamp) = timestamp else {
+                    return Err(format!("Timestamp with ID {} not found", msg.id));
+                };
+                if msg.heat_nr.is_none() && msg.bib.is_none() {
+                    return Err(format!("Neither heat number nor bib given for timestamp {}", msg.id));
+                }
+                if let Some(heat_nr) = msg.heat_nr {
+                    timestamp = time_strip
+                        .set_heat_nr(&timestamp, heat_nr)
                         .await
                         .map_err(|err| format!("Failed to update timestamp heat number: {err}"))?;
-                    Ok(timestamp)
-                } else {
-                    Err(format!("Timestamp with time {} not found", msg.time))
                 }
+                if let Some(bib) = msg.bib {
+                    timestamp = time_strip
+                        .set_bib(&timestamp, bib)
+                        .await
+                        .map_err(|err| format!("Failed to update timestamp bib: {err}"))?;
+                }
+                Ok(timestamp)
             })
             .map(
                 |result: Result<Timestamp, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
@@ -325,6 +801,292 @@ impl Handler<UpdateTimestamp> for TimekeepingActor {
     }
 }
 
+impl Handler<UpdateTimestamps> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateTimestamps, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                let time_stamps = msg
+                    .ids
+                    .iter()
+                    .map(|id| {
+                        time_strip
+                            .get_by_id(*id)
+                            .cloned()
+                            .ok_or_else(|| format!("Timestamp with ID {id} not found"))
+                    })
+                    .collect::<Result<Vec<Timestamp>, String>>()?;
+                let updated = match msg.change {
+                    BulkChange::HeatNr(heat_nr) => time_strip.set_heat_nrs(&time_stamps, heat_nr).await,
+                    BulkChange::Bibs(bibs) => time_strip.set_bibs(&time_stamps, &bibs).await,
+                    BulkChange::Shift(delta) => time_strip.shift_heat_nrs(&time_stamps, delta).await,
+                };
+                updated.map_err(|err| format!("Failed to update timestamps: {err}"))
+            })
+            .map(
+                |result: Result<Vec<Timestamp>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(time_stamps) => ServerEvent::Timestamps { time_stamps },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<UndoTimestamp> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UndoTimestamp, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                let timestamp = time_strip
+                    .get_by_id(msg.id)
+                    .cloned()
+                    .ok_or_else(|| format!("Timestamp with ID {} not found", msg.id))?;
+                time_strip
+                    .undo(&timestamp)
+                    .await
+                    .map_err(|err| format!("Failed to undo timestamp change: {err}"))
+            })
+            .map(
+                |result: Result<Timestamp, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(timestamp) => ServerEvent::Timestamp { timestamp },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<SetTimestampNote> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTimestampNote, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                let timestamp = time_strip
+                    .find_by_id(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to find timestamp: {err}"))?
+                    .ok_or_else(|| format!("Timestamp with ID {} not found", msg.id))?;
+                time_strip
+                    .set_note(&timestamp, msg.note.as_deref())
+                    .await
+                    .map_err(|err| format!("Failed to set timestamp note: {err}"))
+            })
+            .map(
+                |result: Result<Timestamp, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(timestamp) => ServerEvent::Timestamp { timestamp },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<SetTimestampVoid> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTimestampVoid, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                let timestamp = time_strip
+                    .find_by_id(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to find timestamp: {err}"))?
+                    .ok_or_else(|| format!("Timestamp with ID {} not found", msg.id))?;
+                time_strip
+                    .set_void(&timestamp, msg.reason.as_deref())
+                    .await
+                    .map_err(|err| format!("Failed to void timestamp: {err}"))
+            })
+            .map(
+                |result: Result<Timestamp, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(timestamp) => ServerEvent::Timestamp { timestamp },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<RestoreTimestamp> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RestoreTimestamp, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                time_strip
+                    .restore(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to restore timestamp: {err}"))?;
+                // the restored timestamp is inserted by its time, so the whole timestrip is sent
+                Ok(time_strip.to_vec())
+            })
+            .map(
+                |result: Result<Vec<Timestamp>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(time_stamps) => ServerEvent::TimeStrip { time_stamps },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<GetTimestampHistory> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: GetTimestampHistory, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        let id = msg.id;
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let time_strip = time_strip.read().await;
+                time_strip
+                    .history(msg.id)
+                    .await
+                    .map_err(|err| format!("Failed to read timestamp history: {err}"))
+            })
+            .map(
+                move |result: Result<Vec<AuditEntry>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(history) => ServerEvent::TimestampHistory { id, history },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<GetDeletedTimestamps> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: GetDeletedTimestamps, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let time_strip = time_strip.read().await;
+                time_strip
+                    .deleted()
+                    .await
+                    .map_err(|err| format!("Failed to read deleted timestamps: {err}"))
+            })
+            .map(
+                |result: Result<Vec<AuditEntry>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(deleted) => ServerEvent::DeletedTimestamps { deleted },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<GetRanking> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: GetRanking, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let time_strip = time_strip.read().await;
+                time_strip
+                    .ranking(msg.heat_nr)
+                    .await
+                    .map_err(|err| format!("Failed to compute ranking of heat {}: {err}", msg.heat_nr))
+            })
+            .map(
+                |result: Result<HeatRanking, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(ranking) => ServerEvent::Ranking { ranking },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<SyncClock> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SyncClock, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let time_strip = time_strip.read().await;
+                time_strip
+                    .query_db_time()
+                    .await
+                    .map_err(|err| format!("Failed to query DB time: {err}"))
+            })
+            .map(
+                move |result: Result<DateTime<Utc>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(db_time) => ServerEvent::ClockTime {
+                            sent: msg.time,
+                            db_time,
+                        },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<SetClockOffset> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetClockOffset, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                time_strip.set_clock_offset(msg.sent, msg.db_time, msg.received);
+                time_strip.station().clone()
+            })
+            .map(
+                |station: Station, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    ctx.address().do_send(ServerEvent::ClockOffset { station });
+                },
+            ),
+        );
+    }
+}
+
 impl Handler<GetTimestrip> for TimekeepingActor {
     type Result = ();
 
@@ -349,6 +1111,62 @@ impl Handler<GetTimestrip> for TimekeepingActor {
     }
 }
 
+impl Handler<LoadMoreTimestamps> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: LoadMoreTimestamps, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let mut time_strip = time_strip.write().await;
+                time_strip
+                    .load_more()
+                    .await
+                    .map_err(|err| format!("Failed to load older timestamps: {err}"))?;
+                Ok(time_strip.to_vec())
+            })
+            .map(
+                |result: Result<Vec<Timestamp>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(time_stamps) => ServerEvent::TimeStrip { time_stamps },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
+impl Handler<QueryTimestamps> for TimekeepingActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: QueryTimestamps, ctx: &mut Self::Context) -> Self::Result {
+        let time_strip = self.time_strip.clone();
+        let offset = msg.offset;
+
+        ctx.wait(
+            actix::fut::wrap_future(async move {
+                let time_strip = time_strip.read().await;
+                time_strip
+                    .query(&msg.filter, msg.offset, msg.limit)
+                    .await
+                    .map_err(|err| format!("Failed to query timestamps: {err}"))
+            })
+            .map(
+                move |result: Result<Vec<Timestamp>, String>, _actor, ctx: &mut WebsocketContext<TimekeepingActor>| {
+                    let event = match result {
+                        Ok(time_stamps) => ServerEvent::TimestampQuery { time_stamps, offset },
+                        Err(error) => ServerEvent::Error { error },
+                    };
+                    ctx.address().do_send(event);
+                },
+            ),
+        );
+    }
+}
+
 impl Handler<GetHeatsReadyToStart> for TimekeepingActor {
     type Result = ();
 
@@ -381,27 +1199,63 @@ impl Actor for TimekeepingActor {
     fn started(&mut self, ctx: &mut Self::Context) {
         trace!("Timekeeping websocket actor started");
         self.start_heart_beat(ctx);
+        self.start_sync(ctx);
 
-        if let Some(event_receiver) = self.event_receiver.take()
-            && let Some(aquarius_client) = self.aquarius_client.clone()
-        {
-            let heats = self.heats.clone();
-            let address = ctx.address();
-            thread::spawn(move || receive_aquarius_events(event_receiver, aquarius_client, heats, address));
+        if let Some(aquarius_events) = self.aquarius_events.take() {
+            ctx.add_stream(aquarius_events);
             ctx.address().do_send(GetTimestrip);
         } else {
-            error!("Failed to take event receiver for timekeeping websocket actor");
+            error!("Failed to take event stream for timekeeping websocket actor");
         }
     }
 
     fn stopped(&mut self, _ctx: &mut Self::Context) {
         trace!("Timekeeping websocket actor stopped");
         if let Some(aquarius_client) = self.aquarius_client.take() {
-            aquarius_client.shutdown();
+            actix::spawn(async move { aquarius_client.shutdown().await });
         }
     }
 }
 
+#[utoipa::path(
+    description = "Export the timekeeping protocol of a regatta day as printable HTML, CSV or JSON.",
+    context_path = PATH,
+    params(
+        ("regatta_id" = i32, Path, description = "The ID of the regatta"),
+        ("day" = String, Path, description = "The regatta day, e.g. 2026-06-01"),
+        ("format" = ProtocolFormat, Path, description = "The format of the protocol: html, csv or json")
+    ),
+    responses(
+        (status = 200, description = "Timekeeping protocol of <day>", body = TimekeepingProtocol),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Regatta not found"),
+        (status = 500, description = INTERNAL_SERVER_ERROR)
+    )
+)]
+#[get("/regattas/{regatta_id}/timekeeping_protocol/{day}/{format}")]
+async fn get_timekeeping_protocol(
+    path: Path<(i32, NaiveDate, ProtocolFormat)>,
+    identity: Identity,
+    aquarius: Data<Aquarius>,
+    user_pool_manager: Data<UserPoolManager>,
+) -> Result<HttpResponse, Error> {
+    let (regatta_id, day, format) = path.into_inner();
+    let user_pool = get_user_pool(&identity, &user_pool_manager).await?;
+    let protocol = aquarius
+        .get_timekeeping_protocol(regatta_id, day, &user_pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ErrorNotFound("Regatta not found"))?;
+    let body = protocol.render(format).map_err(ApiError::from)?;
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!("inline; filename=\"{}\"", protocol.file_name(format)),
+        ))
+        .body(body))
+}
+
 #[get("/timekeeping")]
 async fn get_timekeeping_ws(
     request: HttpRequest,
@@ -411,44 +1265,10 @@ async fn get_timekeeping_ws(
     user_pool_manager: Data<UserPoolManager>,
 ) -> Result<HttpResponse, Error> {
     let pool = get_user_pool(&identity, &user_pool_manager).await?;
-    let actor = TimekeepingActor::new(pool, aquarius_db.clone()).await;
+    // changes to the timestrip are recorded with the name of the user in the audit trail
+    let user = identity.id()?;
+    let actor = TimekeepingActor::new(pool, aquarius_db.clone(), &user)
+        .await
+        .map_err(ApiError::from)?;
     ws::start(actor, &request, stream)
 }
-
-fn receive_aquarius_events(
-    receiver: Receiver<AquariusEvent>,
-    aquarius_client: Arc<AquariusClient>,
-    heats: Arc<RwLock<Vec<AquariusHeat>>>,
-    addr: Addr<TimekeepingActor>,
-) {
-    while let Ok(event) = receiver.recv() {
-        match event {
-            AquariusEvent::HeatListChanged(event) => {
-                debug!("Received HeatListChanged event = {:?}", &event);
-                if event.opened {
-                    let mut heats_lock = heats.write().unwrap();
-                    heats_lock.push(event.heat);
-                } else {
-                    let mut heats_lock = heats.write().unwrap();
-                    heats_lock.retain(|heat| heat.id != event.heat.id);
-                }
-            }
-            AquariusEvent::Client(connected) => {
-                if connected {
-                    if let Ok(open_heats) = aquarius_client.read_open_heats() {
-                        let mut heats_lock = heats.write().unwrap();
-                        heats_lock.clear();
-                        heats_lock.extend(open_heats);
-                    } else {
-                        error!("Failed to read open heats from Aquarius client");
-                    }
-                } else {
-                    heats.write().unwrap().clear();
-                }
-            }
-        }
-        addr.do_send(ServerEvent::AquariusHeats {
-            heats: heats.read().unwrap().clone(),
-        });
-    }
-}
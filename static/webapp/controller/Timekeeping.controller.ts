@@ -82,17 +82,21 @@ export default class TimekeepingController extends BaseTableController {
     this.sendGetTimestripCommand();
   }
 
+  onLoadMoreButtonPress(event: Button$PressEvent): void {
+    this.sendCommand({ LoadMoreTimestamps: null });
+  }
+
   onDeleteTimestamp(event: Button$PressEvent): void {
     const bindingCtx: Context | null | undefined = event.getSource().getBindingContext(TimekeepingController.TIMESTRIP_MODEL);
     if (bindingCtx) {
       const timestamp: any = bindingCtx.getModel().getProperty(bindingCtx.getPath());
-      if (timestamp?.time) {
+      if (timestamp?.id) {
         MessageBox.confirm(super.i18n("timekeeping.deleteTimestamp.message"), {
           title: super.i18n("timekeeping.deleteTimestamp.title"),
           emphasizedAction: MessageBox.Action.CANCEL,
           onClose: (action: any) => {
             if (action === MessageBox.Action.OK) {
-              this.sendCommand({ DeleteTimestamp: { time: timestamp.time } });
+              this.sendCommand({ DeleteTimestamp: { id: timestamp.id } });
               this.deleteTimestamp(timestamp);
             }
           }
@@ -101,6 +105,19 @@ export default class TimekeepingController extends BaseTableController {
     }
   }
 
+  onUndoTimestamp(event: Button$PressEvent): void {
+    const bindingCtx: Context | null | undefined = event.getSource().getBindingContext(TimekeepingController.TIMESTRIP_MODEL);
+    const timestamp: any = bindingCtx?.getModel().getProperty(bindingCtx.getPath());
+    if (timestamp?.id) {
+      this.sendCommand({ UndoTimestamp: { id: timestamp.id } });
+    }
+  }
+
+  onRestoreButtonPress(event: Button$PressEvent): void {
+    // the deleted timestamps are sent back by the server, see confirmRestore
+    this.sendCommand({ GetDeletedTimestamps: null });
+  }
+
   onLoadHeats(event: ComboBoxBase$LoadItemsEvent): void {
     this.sendGetHeatsReadyToStartCommand();
     const binding: ListBinding | undefined = event.getSource().getBinding("items") as ListBinding;
@@ -122,7 +139,7 @@ export default class TimekeepingController extends BaseTableController {
       const timestamp: any = bindingCtx.getModel().getProperty(bindingCtx.getPath());
       if (timestamp) {
         timestamp.heat_nr = heatNr;
-        this.sendCommand({ UpdateTimestamp: { time: timestamp.time, heat_nr: Number.parseInt(heatNr, 10) } });
+        this.sendCommand({ UpdateTimestamp: { id: timestamp.id, heat_nr: Number.parseInt(heatNr, 10) } });
       }
     }
   }
@@ -133,8 +150,8 @@ export default class TimekeepingController extends BaseTableController {
     if (bindingCtx) {
       const timestamp: any = bindingCtx.getModel().getProperty(bindingCtx.getPath());
       if (timestamp) {
-        timestamp.bib_nr = bibNr;
-        this.sendCommand({ UpdateTimestamp: { time: timestamp.time, bib_nr: Number.parseInt(bibNr, 10) } });
+        timestamp.bib = bibNr;
+        this.sendCommand({ UpdateTimestamp: { id: timestamp.id, bib: Number.parseInt(bibNr, 10) } });
       }
     }
   }
@@ -156,6 +173,8 @@ export default class TimekeepingController extends BaseTableController {
     this.socket = new WebSocket(`${proto}://${location.host}/api/timekeeping`);
 
     this.socket.onopen = (_event: Event) => {
+      // the timestamps of this browser are corrected by the offset of its clock to the server
+      this.sendCommand({ SyncClock: { time: new Date().toISOString() } });
       this.sendGetTimestripCommand();
       this.statusButton?.setIcon('sap-icon://connected');
       console.debug('Timekeeping WebSocket Connected');
@@ -175,9 +194,54 @@ export default class TimekeepingController extends BaseTableController {
         } else if (data.Timestamp) {
           this.updateTimestamp(data.Timestamp.timestamp);
           super.showInfoMessageToast("Timestamp added successfully");
+        } else if (data.Timestamps) {
+          // timestamps changed by a bulk operation
+          for (const timestamp of data.Timestamps.time_stamps) {
+            this.updateTimestamp(timestamp);
+          }
         } else if (data.TimeStrip) {
           super.getViewJSONModel(TimekeepingController.TIMESTRIP_MODEL).setData(data.TimeStrip.time_stamps);
           super.showInfoMessageToast("Timestrip retrieved successfully");
+        } else if (data.TimestampQuery) {
+          console.debug(`Received ${data.TimestampQuery.time_stamps.length} timestamps from offset ${data.TimestampQuery.offset}`);
+        } else if (data.TimeStripChanged) {
+          // timestamps taken or changed by other stations
+          for (const change of data.TimeStripChanged.changes) {
+            if (change.Inserted) {
+              this.updateTimestamp(change.Inserted);
+            } else if (change.Updated) {
+              this.updateTimestamp(change.Updated);
+            } else if (change.Deleted) {
+              this.deleteTimestamp({ id: change.Deleted });
+            } else if (change.Persisted) {
+              this.persistTimestamp(change.Persisted);
+            }
+          }
+        } else if (data.ClockTime) {
+          // the offset is measured by the round trip, the DB time is estimated to be taken in its middle
+          const clockTime = data.ClockTime;
+          this.sendCommand({
+            SetClockOffset: { sent: clockTime.sent, db_time: clockTime.db_time, received: new Date().toISOString() }
+          });
+        } else if (data.ClockOffset) {
+          const station = data.ClockOffset.station;
+          console.debug(`Clock of station ${station.name} is corrected by ${station.clock_offset_ms} ms`);
+        } else if (data.Ranking) {
+          const ranking = data.Ranking.ranking;
+          console.debug(`Received ranking of heat ${ranking.heat_nr} with ${ranking.problems.length} problems`);
+        } else if (data.DeletedTimestamps) {
+          this.confirmRestore(data.DeletedTimestamps.deleted);
+        } else if (data.TimestampHistory) {
+          console.debug(`Received ${data.TimestampHistory.history.length} changes of timestamp ${data.TimestampHistory.id}`);
+        } else if (data.AquariusTimeConfirmed) {
+          const time = data.AquariusTimeConfirmed.time;
+          console.debug(`Aquarius confirmed time ${time.time} of heat ${time.heatNr}`);
+        } else if (data.AquariusTimeRejected) {
+          const rejected = data.AquariusTimeRejected;
+          super.showErrorMessageToast(`Aquarius rejected queued time "${rejected.command}": ${rejected.reason}`);
+        } else if (data.AquariusResult) {
+          const result = data.AquariusResult.result;
+          console.debug(`Aquarius updated result of bib ${result.bib} in heat ${result.heatId}: ${result.result}`);
         } else if (data.HeatsReadyToStart) {
           super.getViewJSONModel(TimekeepingController.HEATS_MODEL).setData(data.HeatsReadyToStart.heats);
           super.showInfoMessageToast("Heats ready to start retrieved successfully");
@@ -187,10 +251,27 @@ export default class TimekeepingController extends BaseTableController {
     }
   }
 
+  private confirmRestore(deleted: any[]) {
+    const last: any = deleted[0];
+    if (!last) {
+      super.showInfoMessageToast(super.i18n("timekeeping.restoreTimestamp.none"));
+      return;
+    }
+    MessageBox.confirm(super.i18n("timekeeping.restoreTimestamp.message", [Formatter.timestamp(last.time), last.split]), {
+      title: super.i18n("timekeeping.restoreTimestamp.title"),
+      onClose: (action: any) => {
+        if (action === MessageBox.Action.OK) {
+          this.sendCommand({ RestoreTimestamp: { id: last.timestamp_id } });
+        }
+      }
+    });
+  }
+
   private updateTimestamp(timestamp: any) {
     const timestripModel: JSONModel = super.getViewJSONModel(TimekeepingController.TIMESTRIP_MODEL);
     const existingTimestamps: any[] = timestripModel.getData() || [];
-    const timestampIndex: number = existingTimestamps.findIndex((t: any) => t.time === timestamp.time);
+    // timestamps not persisted yet have no ID, they are replaced once persisted, see persistTimestamp
+    const timestampIndex: number = timestamp.id == null ? -1 : existingTimestamps.findIndex((t: any) => t.id === timestamp.id);
     if (timestampIndex >= 0) {
       existingTimestamps[timestampIndex] = timestamp;
     } else {
@@ -199,10 +280,26 @@ export default class TimekeepingController extends BaseTableController {
     timestripModel.setData(existingTimestamps);
   }
 
+  private persistTimestamp(timestamp: any) {
+    // the timestamp was sent without ID before, it is identified by the time it was taken
+    const timestripModel: JSONModel = super.getViewJSONModel(TimekeepingController.TIMESTRIP_MODEL);
+    const existingTimestamps: any[] = timestripModel.getData() || [];
+    const timestampIndex: number = existingTimestamps.findIndex((t: any) => t.id == null
+      && t.time === timestamp.time
+      && JSON.stringify(t.split) === JSON.stringify(timestamp.split)
+      && t.station === timestamp.station);
+    if (timestampIndex >= 0) {
+      existingTimestamps[timestampIndex] = timestamp;
+      timestripModel.setData(existingTimestamps);
+    } else {
+      this.updateTimestamp(timestamp);
+    }
+  }
+
   private deleteTimestamp(timestamp: any) {
     const timestripModel: JSONModel = super.getViewJSONModel(TimekeepingController.TIMESTRIP_MODEL);
     const existingTimestamps: any[] = timestripModel.getData() || [];
-    const timestampIndex: number = existingTimestamps.findIndex((t: any) => t.time === timestamp.time);
+    const timestampIndex: number = existingTimestamps.findIndex((t: any) => t.id === timestamp.id);
     if (timestampIndex >= 0) {
       existingTimestamps.splice(timestampIndex, 1);
     }
@@ -8,6 +8,7 @@ mod utils;
 use self::heats_tab::HeatsTab;
 use self::logs_tab::LogsTab;
 use crate::error::TimekeeperErr;
+use crate::trigger::{Impulse, read_impulses};
 use crate::{
     app::{selected_tab::SelectedTab, timestrip_popup::TimeStripTabPopup, timestrip_tab::TimeStripTab},
     args::Args,
@@ -16,11 +17,16 @@ use ::aquarius::client::AquariusClient;
 use ::aquarius::error::AquariusErr;
 use ::aquarius::event::AquariusEvent;
 use ::aquarius::messages::EventHeatChanged;
+use ::aquarius::messages::EventHeatStateChanged;
 use ::aquarius::messages::Heat;
+use ::aquarius::outbox::Outbox;
+use ::aquarius::recorder::Recorder;
+use ::aquarius::status::ConnectionState;
+use ::chrono::{DateTime, Local, TimeDelta, Utc};
 use ::clap::Parser;
 use ::db::tiberius::TiberiusPool;
 use ::db::tiberius_client::{AuthMethod, Config, EncryptionLevel};
-use ::db::timekeeper::TimeStrip;
+use ::db::timekeeper::{HeatSuggester, Journal, ProtocolFormat, Split, Station, TimeStrip, TimestampSource};
 use ::ratatui::{
     DefaultTerminal,
     crossterm::event::{self, Event, KeyCode, KeyEventKind},
@@ -32,20 +38,38 @@ use ::ratatui::{
     text::Line,
     widgets::{Clear, Tabs},
 };
+use ::std::fs;
+use ::std::path::PathBuf;
 use ::std::sync::Arc;
+use ::std::time::Duration;
 use ::std::{
     cell::RefCell,
+    collections::HashMap,
     rc::Rc,
     sync::mpsc::{self, Receiver, Sender},
     thread,
 };
 use ::strum::IntoEnumIterator;
-use ::tracing::{debug, warn};
+use ::tokio::time::timeout;
+use ::tracing::{debug, error, info, warn};
+
+/// How often the time strip is synced with the changes of other stations.
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long reloading the heats the heat numbers are proposed from may take, the last ones are kept on timeout.
+const SUGGESTER_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct App<'a> {
     // application state
     state: AppState,
     selected_tab: SelectedTab,
+    connection_state: ConnectionState,
+    intermediate_split: u8,
+    export_dir: PathBuf,
+    // whether the heats the heat numbers are proposed from have to be reloaded with the next sync
+    suggester_outdated: bool,
+    // whether the clock offset of the station is known, it is measured with the next sync otherwise
+    clock_offset_measured: bool,
 
     // event receiver
     app_event_receiver: Receiver<AppEvent>,
@@ -60,6 +84,7 @@ pub struct App<'a> {
     client: Rc<RefCell<AquariusClient>>,
     heats: Rc<RefCell<Vec<Heat>>>,
     time_strip: Rc<RefCell<TimeStrip>>,
+    suggester: Rc<RefCell<HeatSuggester>>,
     show_time_strip_popup: Rc<RefCell<bool>>,
 }
 
@@ -69,40 +94,90 @@ impl App<'_> {
 
         let db_config = Self::get_db_config(&args);
         let pool = Arc::new(TiberiusPool::new(db_config, 1, 1).await);
-        let timestrip = TimeStrip::load(pool.clone()).await?;
+        // open the journal first, so times can be taken even if the DB is unreachable
+        let journal = Journal::open(&args.journal)?;
+        let mut timestrip = match TimeStrip::load(pool.clone(), &args.station).await {
+            Ok(timestrip) => timestrip,
+            Err(err) => {
+                warn!(%err, "Error loading time strip, it is loaded with the next sync:");
+                TimeStrip::offline(pool.clone(), &args.station)
+            }
+        };
+        timestrip.set_journal(journal);
+        let clock_offset_measured = match args.clock_offset {
+            Some(offset) => {
+                timestrip.set_station(Station::new(&args.station, TimeDelta::milliseconds(offset)));
+                true
+            }
+            None => Self::measure_clock_offset(&mut timestrip).await,
+        };
+        // persist the time stamps left in the journal, e.g. by a crash while the DB was unreachable
+        if let Err(err) = timestrip.persist_pending().await {
+            warn!(%err, "Error persisting the time stamps of the journal:");
+        }
 
         let (aquarius_event_sender, aquarius_event_receiver) = mpsc::channel();
         let (app_event_sender, app_event_receiver) = mpsc::channel();
         let app_event_sender_clone = app_event_sender.clone();
+        let sync_event_sender = app_event_sender.clone();
 
         let client: AquariusClient =
-            AquariusClient::new(&args.host, args.port, args.timeout, aquarius_event_sender.clone())?;
+            AquariusClient::with_endpoints(args.endpoints()?, args.timeout, aquarius_event_sender.clone())?;
+        client.set_codec(args.codec);
+        if let Some(path) = &args.record {
+            client.set_recorder(Some(Recorder::create(path)?));
+        }
+        client.set_outbox(Some(Outbox::open(&args.outbox)?));
         thread::spawn(move || input_thread(app_event_sender_clone));
+        thread::spawn(move || sync_thread(sync_event_sender));
+        if let Some(source) = args.trigger.clone() {
+            let channels = args.trigger_channels.clone();
+            let clock_offset = TimeDelta::milliseconds(args.trigger_clock_offset);
+            let (impulse_sender, impulse_receiver) = mpsc::channel();
+            let trigger_event_sender = app_event_sender.clone();
+            thread::spawn(move || read_impulses(&source, &channels, clock_offset, &impulse_sender));
+            thread::spawn(move || receive_impulses(impulse_receiver, trigger_event_sender));
+        }
         thread::spawn(move || receive_aquarius_events(aquarius_event_receiver, app_event_sender));
 
         // shared context
         let client_rc = Rc::new(RefCell::new(client));
         let heats = Rc::new(RefCell::new(Vec::new()));
         let time_strip = Rc::new(RefCell::new(timestrip));
+        let suggester = Rc::new(RefCell::new(HeatSuggester::default()));
         let selected_time_stamp = Rc::new(RefCell::new(None));
+        let selected_range = Rc::new(RefCell::new(Vec::new()));
         let show_time_strip_popup = Rc::new(RefCell::new(false));
+        let sent_times = Rc::new(RefCell::new(HashMap::new()));
 
         Ok(Self {
             state: AppState::Running,
             selected_tab: SelectedTab::Heats,
+            connection_state: ConnectionState::default(),
+            intermediate_split: args.intermediate_split,
+            export_dir: args.export_dir.clone(),
+            suggester_outdated: true,
+            clock_offset_measured,
             // tabs
             heats_tab: HeatsTab::new(heats.clone()),
             time_strip_tab: TimeStripTab::new(
+                client_rc.clone(),
+                heats.clone(),
                 time_strip.clone(),
+                suggester.clone(),
                 selected_time_stamp.clone(),
+                selected_range.clone(),
                 show_time_strip_popup.clone(),
+                sent_times.clone(),
             ),
             time_strip_popup: TimeStripTabPopup::new(
                 client_rc.clone(),
                 heats.clone(),
                 time_strip.clone(),
                 selected_time_stamp.clone(),
+                selected_range,
                 show_time_strip_popup.clone(),
+                sent_times,
             ),
             logs_tab: LogsTab::default(),
             // shared context
@@ -110,18 +185,59 @@ impl App<'_> {
             app_event_receiver,
             heats,
             time_strip,
+            suggester,
             show_time_strip_popup,
         })
     }
 
     pub(crate) async fn start(mut self, terminal: &mut DefaultTerminal) -> Result<(), AquariusErr> {
         // main loop, runs until the user quits the application by pressing 'q'
+        self.refresh_suggester().await;
+        // the heats the heat numbers are proposed from are reloaded with the next sync, so events are not delayed by
+        // the DB
         while self.state == AppState::Running {
             let event = self.app_event_receiver.recv()?;
             match event {
-                AppEvent::UI(event) => self.handle_ui_event(event).await,
-                AppEvent::Aquarius(AquariusEvent::HeatListChanged(event)) => self.handle_aquarius_event(event),
-                AppEvent::Aquarius(AquariusEvent::Client(connected)) => self.handle_client_event(connected),
+                AppEvent::UI(event, time) => {
+                    self.handle_ui_event(event, time).await;
+                    // time stamps may have been added or assigned
+                    self.suggester_outdated = true;
+                }
+                AppEvent::Aquarius(AquariusEvent::HeatListChanged(event)) => {
+                    self.handle_aquarius_event(event);
+                    self.suggester_outdated = true;
+                }
+                AppEvent::Aquarius(AquariusEvent::HeatStateChanged(event)) => {
+                    self.handle_heat_state_event(event);
+                    self.suggester_outdated = true;
+                }
+                AppEvent::Aquarius(AquariusEvent::TimeConfirmed(event)) => {
+                    info!(time = %event.time, heat = event.heat_nr, split = %event.split, bib = ?event.bib, "Time confirmed:");
+                }
+                AppEvent::Aquarius(AquariusEvent::ResultUpdated(event)) => {
+                    info!(heat_id = event.heat_id, bib = event.bib, rank = ?event.rank, result = event.result, "Result updated:");
+                }
+                AppEvent::Aquarius(AquariusEvent::Unknown(line)) => debug!(line, "Unknown Aquarius event:"),
+                AppEvent::Aquarius(AquariusEvent::Client(state)) => self.handle_client_event(state),
+                AppEvent::Aquarius(AquariusEvent::QueuedTimeRejected { item, reason }) => {
+                    error!(
+                        command = item.command,
+                        reason, "Aquarius rejected queued time, enter it manually:"
+                    );
+                }
+                AppEvent::Trigger(impulse) => {
+                    self.handle_impulse(impulse).await;
+                    self.suggester_outdated = true;
+                }
+                AppEvent::Sync => {
+                    if !self.clock_offset_measured {
+                        self.remeasure_clock_offset().await;
+                    }
+                    self.sync_time_strip().await;
+                    if self.suggester_outdated {
+                        self.refresh_suggester().await;
+                    }
+                }
             }
             self.draw(terminal)?;
         }
@@ -129,16 +245,34 @@ impl App<'_> {
     }
 
     fn draw(&mut self, terminal: &mut DefaultTerminal) -> Result<(), AquariusErr> {
+        let queued = self.client.borrow().outbox_items().len();
+        let active_endpoint = self.client.borrow().stats().active_endpoint;
         terminal.draw(|frame| {
             // vertical layout: header, inner area, footer
             let [header_area, inner_area, footer_area] =
                 Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
                     .areas(frame.area());
-            // horizontal header layout: tabs, title
-            let [tabs_area, title_area] = Layout::horizontal([Min(0), Length(20)]).areas(header_area);
+            // horizontal header layout: tabs, connection state, title
+            let [tabs_area, connection_area, title_area] =
+                Layout::horizontal([Min(0), Length(50), Length(20)]).areas(header_area);
 
-            // render tabs header and title
+            // render tabs header, connection state and title
             frame.render_widget("Aquarius Zeitmessung".bold(), title_area);
+            let connection = match (&active_endpoint, self.connection_state.is_connected()) {
+                (Some(endpoint), true) => format!("{} to {endpoint}", self.connection_state),
+                _ => self.connection_state.to_string(),
+            };
+            let connection = match queued {
+                0 => connection,
+                queued => format!("{connection} ({queued} queued)"),
+            };
+            let connection = Line::raw(connection).right_aligned();
+            let connection = if self.connection_state.is_connected() {
+                connection.green()
+            } else {
+                connection.red()
+            };
+            frame.render_widget(connection, connection_area);
             let titles = SelectedTab::iter().map(SelectedTab::title);
 
             // render the selected tab
@@ -158,23 +292,130 @@ impl App<'_> {
 
             // render footer
             frame.render_widget(
-                Line::raw("◄ ► / tab to change tab | + to start | space to finish | q to quit").centered(),
+                Line::raw("◄ ► / tab to change tab | + to start | space to finish | l to load older | v to select range | b to assign bibs | < > to shift heats | a to accept heat | u to undo | R to restore | x to export | q to quit")
+                    .centered(),
                 footer_area,
             );
         })?;
         Ok(())
     }
 
-    fn handle_client_event(&mut self, connected: bool) {
-        if !connected {
-            self.heats.borrow_mut().clear();
-        } else {
-            self.read_open_heats();
+    /// Adds a time stamp for an impulse of the timing device, with the time taken by the device. Unlike the times taken
+    /// with the keyboard, it is not corrected by the clock offset of the station, but by the one of the device.
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn handle_impulse(&mut self, impulse: Impulse) {
+        debug!(channel = impulse.channel, split = %impulse.split, time = %impulse.time, "Impulse of timing device:");
+        let time = Some(impulse.time);
+        let mut time_strip = self.time_strip.borrow_mut();
+        match impulse.split {
+            Split::Start => {
+                time_strip.add_start(time, TimestampSource::Hardware).await;
+            }
+            Split::Finish => {
+                time_strip.add_finish(time, TimestampSource::Hardware).await;
+            }
+            Split::Intermediate(split_nr) => {
+                let added = time_strip
+                    .add_intermediate(split_nr, time, TimestampSource::Hardware)
+                    .await;
+                if let Err(err) = added {
+                    warn!(%err, split_nr, "Error adding intermediate time stamp:");
+                }
+            }
+        }
+    }
+
+    /// Measures the clock offset of the station against the DB server.
+    /// # Returns
+    /// Whether the clock offset was measured, the times are taken without correction otherwise.
+    async fn measure_clock_offset(time_strip: &mut TimeStrip) -> bool {
+        match time_strip.measure_clock_offset().await {
+            Ok(_) => true,
+            Err(err) => {
+                warn!(%err, "Error measuring clock offset, times are not corrected until it is measured:");
+                false
+            }
+        }
+    }
+
+    /// Measures the clock offset of the station that could not be measured on start, e.g. because the DB was
+    /// unreachable.
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn remeasure_clock_offset(&mut self) {
+        self.clock_offset_measured = Self::measure_clock_offset(&mut self.time_strip.borrow_mut()).await;
+    }
+
+    /// Merges the time stamps taken or changed by other stations, e.g. the start tower, into the time strip.
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn sync_time_strip(&mut self) {
+        let changes = self.time_strip.borrow_mut().sync().await;
+        match changes {
+            Ok(changes) if !changes.is_empty() => {
+                debug!(count = changes.len(), "Synced time stamps of other stations:");
+                self.time_strip_tab.refresh();
+                self.suggester_outdated = true;
+            }
+            Ok(_) => {}
+            Err(err) => warn!(%err, "Error syncing time strip:"),
+        }
+    }
+
+    /// Reloads the heats the heat numbers of unassigned time stamps are proposed from and proposes the heat numbers
+    /// again. On error or timeout the last heats are kept and reloaded with the next sync.
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn refresh_suggester(&mut self) {
+        let suggester = timeout(SUGGESTER_TIMEOUT, self.time_strip.borrow().heat_suggester()).await;
+        match suggester {
+            // the open heats are only known while connected to Aquarius
+            Ok(Ok(suggester)) if self.connection_state.is_connected() => {
+                let open_heats = self.heats.borrow().iter().map(|heat| heat.number).collect();
+                *self.suggester.borrow_mut() = suggester.with_open_heats(open_heats);
+                self.suggester_outdated = false;
+            }
+            Ok(Ok(suggester)) => {
+                *self.suggester.borrow_mut() = suggester;
+                self.suggester_outdated = false;
+            }
+            Ok(Err(err)) => warn!(%err, "Error loading heats for suggestions:"),
+            Err(_) => warn!("Loading heats for suggestions timed out"),
         }
+        // the time strip may have changed anyway
+        self.time_strip_tab.refresh_suggestions();
     }
 
+    /// Exports the timekeeping protocol of today as HTML, CSV and JSON files into the export directory.
     #[allow(clippy::await_holding_refcell_ref)]
-    async fn handle_ui_event(&mut self, event: Event) {
+    async fn export_protocol(&mut self) {
+        let protocol = self.time_strip.borrow().protocol(Local::now().date_naive()).await;
+        match protocol {
+            Ok(protocol) => {
+                for format in [ProtocolFormat::Html, ProtocolFormat::Csv, ProtocolFormat::Json] {
+                    let path = self.export_dir.join(protocol.file_name(format));
+                    let written = protocol
+                        .render(format)
+                        .map_err(|err| err.to_string())
+                        .and_then(|content| fs::write(&path, content).map_err(|err| err.to_string()));
+                    match written {
+                        Ok(()) => info!(path = %path.display(), "Exported timekeeping protocol:"),
+                        Err(err) => warn!(%err, path = %path.display(), "Error exporting timekeeping protocol:"),
+                    }
+                }
+            }
+            Err(err) => warn!(%err, "Error compiling timekeeping protocol:"),
+        }
+    }
+
+    fn handle_client_event(&mut self, state: ConnectionState) {
+        match state {
+            ConnectionState::Connected => self.read_open_heats(),
+            ConnectionState::Disconnected { .. } => self.heats.borrow_mut().clear(),
+            _ => {}
+        }
+        self.connection_state = state;
+    }
+
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn handle_ui_event(&mut self, event: Event, time: DateTime<Utc>) {
         match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => match key_event.code {
                 KeyCode::Tab => self.selected_tab = self.selected_tab.next(),
@@ -182,12 +423,30 @@ impl App<'_> {
                 KeyCode::Left => self.selected_tab = self.selected_tab.previous(),
                 KeyCode::Char('q') => self.state = AppState::Quitting,
                 KeyCode::Char('+') => {
-                    self.time_strip.borrow_mut().add_start(None).await.unwrap();
+                    self.time_strip
+                        .borrow_mut()
+                        .add_start(Some(time), TimestampSource::Keyboard)
+                        .await;
+                }
+                KeyCode::Char('i') => {
+                    let split_nr = self.intermediate_split;
+                    let added = self
+                        .time_strip
+                        .borrow_mut()
+                        .add_intermediate(split_nr, Some(time), TimestampSource::Keyboard)
+                        .await;
+                    if let Err(err) = added {
+                        warn!(%err, split_nr, "Error adding intermediate time stamp:");
+                    }
                 }
                 KeyCode::Char(' ') => {
-                    self.time_strip.borrow_mut().add_finish(None).await.unwrap();
+                    self.time_strip
+                        .borrow_mut()
+                        .add_finish(Some(time), TimestampSource::Keyboard)
+                        .await;
                 }
                 KeyCode::Char('r') => self.read_open_heats(),
+                KeyCode::Char('x') => self.export_protocol().await,
                 _ => match self.selected_tab {
                     SelectedTab::Heats => self.heats_tab.handle_key_event(key_event),
                     SelectedTab::TimeStrip => {
@@ -223,6 +482,13 @@ impl App<'_> {
         }
     }
 
+    fn handle_heat_state_event(&mut self, event: EventHeatStateChanged) {
+        let mut heats = self.heats.borrow_mut();
+        if let Some(heat) = heats.iter_mut().find(|heat| heat.id == event.heat.id) {
+            heat.set_state(event.heat.state);
+        }
+    }
+
     fn read_open_heats(&mut self) {
         match self.client.borrow_mut().read_open_heats() {
             Ok(open_heats) => {
@@ -261,7 +527,25 @@ fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
 
 fn input_thread(sender: Sender<AppEvent>) {
     while let Ok(event) = event::read() {
-        sender.send(AppEvent::UI(event)).unwrap();
+        // the time is taken when the key is pressed, not when the event is handled
+        sender.send(AppEvent::UI(event, Utc::now())).unwrap();
+    }
+}
+
+fn sync_thread(sender: Sender<AppEvent>) {
+    loop {
+        thread::sleep(SYNC_INTERVAL);
+        if sender.send(AppEvent::Sync).is_err() {
+            break;
+        }
+    }
+}
+
+fn receive_impulses(receiver: Receiver<Impulse>, sender: Sender<AppEvent>) {
+    while let Ok(impulse) = receiver.recv() {
+        if sender.send(AppEvent::Trigger(impulse)).is_err() {
+            break;
+        }
     }
 }
 
@@ -283,8 +567,14 @@ enum AppState {
 }
 
 pub(crate) enum AppEvent {
-    /// An UI event
-    UI(Event),
+    /// An UI event and the time it was read
+    UI(Event, DateTime<Utc>),
 
     Aquarius(AquariusEvent),
+
+    /// An impulse of the timing device
+    Trigger(Impulse),
+
+    /// Time to sync the time strip with the changes of other stations
+    Sync,
 }
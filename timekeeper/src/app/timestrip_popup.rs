@@ -1,3 +1,4 @@
+use crate::app::timestrip_tab::{SendState, assign_heat, assign_heat_to_range};
 use ::aquarius::{client::AquariusClient, messages::Heat};
 use ::db::timekeeper::{TimeStrip, Timestamp};
 use ::ratatui::{
@@ -8,7 +9,7 @@ use ::ratatui::{
     widgets::{Block, BorderType, Padding, Paragraph, Widget},
 };
 use ::ratatui_textarea::{Input, TextArea};
-use ::std::{cell::RefCell, rc::Rc};
+use ::std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 pub(crate) struct TimeStripTabPopup<'a> {
     input: TextArea<'a>,
@@ -19,17 +20,21 @@ pub(crate) struct TimeStripTabPopup<'a> {
     heats: Rc<RefCell<Vec<Heat>>>,
     time_strip: Rc<RefCell<TimeStrip>>,
     selected_time_stamp: Rc<RefCell<Option<Timestamp>>>,
+    selected_range: Rc<RefCell<Vec<Timestamp>>>,
     show_time_strip_popup: Rc<RefCell<bool>>,
+    sent_times: Rc<RefCell<HashMap<i32, SendState>>>,
 }
 
 impl Widget for &mut TimeStripTabPopup<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let binding = self.selected_time_stamp.borrow_mut();
-        let ts_split = binding.as_ref().unwrap().split();
+        let title = match self.selected_range.borrow().len() {
+            0 | 1 => self.selected_time_stamp.borrow().as_ref().unwrap().split().to_string(),
+            count => format!("{count} Zeiten"),
+        };
         let block = Block::bordered()
             .border_type(BorderType::Rounded)
             .padding(Padding::horizontal(1))
-            .title(format!(" {ts_split} "));
+            .title(format!(" {title} "));
 
         // inner popup area
         let inner_area = block.inner(area);
@@ -53,7 +58,9 @@ impl TimeStripTabPopup<'_> {
         heats: Rc<RefCell<Vec<Heat>>>,
         time_strip: Rc<RefCell<TimeStrip>>,
         selected_time_stamp: Rc<RefCell<Option<Timestamp>>>,
+        selected_range: Rc<RefCell<Vec<Timestamp>>>,
         show_time_strip_popup: Rc<RefCell<bool>>,
+        sent_times: Rc<RefCell<HashMap<i32, SendState>>>,
     ) -> Self {
         Self {
             input: TextArea::default(),
@@ -62,7 +69,9 @@ impl TimeStripTabPopup<'_> {
             heats,
             time_strip,
             selected_time_stamp,
+            selected_range,
             show_time_strip_popup,
+            sent_times,
         }
     }
 
@@ -80,11 +89,17 @@ impl TimeStripTabPopup<'_> {
                 if self.is_valid {
                     let heat_nr = self.input.lines()[0].parse::<i16>().unwrap();
                     self.input.delete_line_by_head();
-                    if let Some(timestamp) = self.selected_time_stamp.borrow().as_ref()
-                        && let Ok(timestamp) = self.time_strip.borrow_mut().set_heat_nr(timestamp, heat_nr).await
-                    {
+                    // a selected range is assigned at once
+                    let range = self.selected_range.borrow().clone();
+                    let assigned = if range.len() > 1 {
+                        assign_heat_to_range(&self.time_strip, &self.client, &self.sent_times, &range, heat_nr).await
+                    } else if let Some(timestamp) = self.selected_time_stamp.borrow().as_ref() {
+                        assign_heat(&self.time_strip, &self.client, &self.sent_times, timestamp, heat_nr).await
+                    } else {
+                        false
+                    };
+                    if assigned {
                         *self.show_time_strip_popup.borrow_mut() = false;
-                        self.client.borrow_mut().send_time(&timestamp, None).unwrap();
                     }
                     self.is_valid = false;
                 }
@@ -2,33 +2,71 @@ use crate::app::{
     TimeStrip,
     utils::{HIGHLIGHT_SYMBOL, block},
 };
-use ::db::timekeeper::Timestamp;
+use ::aquarius::{client::AquariusClient, messages::Heat, outbox::Delivery};
+use ::db::aquarius::model::BoatState;
+use ::db::timekeeper::{HeatSuggester, HeatSuggestion, Timestamp};
 use ::ratatui::{
     buffer::Buffer,
     crossterm::event::{KeyCode, KeyEvent},
     layout::Rect,
+    style::{Color, Style, Stylize},
     widgets::{HighlightSpacing, List, ListItem, ListState, StatefulWidget, Widget},
 };
-use ::std::{cell::RefCell, rc::Rc};
+use ::std::{cell::RefCell, collections::HashMap, ops::RangeInclusive, rc::Rc};
+use ::tracing::{info, warn};
 
 const DATE_FORMAT_STR: &str = "%H:%M:%S.%3f";
 
+/// The result of sending a time stamp to Aquarius.
+#[derive(Clone, Copy)]
+pub(crate) enum SendState {
+    /// Aquarius accepted the time stamp.
+    Accepted,
+    /// The time stamp is queued in the outbox until Aquarius is connected again.
+    Queued,
+    /// Aquarius rejected the time stamp or did not answer.
+    Failed,
+}
+
 pub(crate) struct TimeStripTab {
     state: ListState,
+    // the list index a range of time stamps is selected from, up to the selected one
+    range_start: Option<usize>,
 
     // shared context
+    client: Rc<RefCell<AquariusClient>>,
+    heats: Rc<RefCell<Vec<Heat>>>,
     time_strip: Rc<RefCell<TimeStrip>>,
+    suggester: Rc<RefCell<HeatSuggester>>,
     selected_time_stamp: Rc<RefCell<Option<Timestamp>>>,
+    selected_range: Rc<RefCell<Vec<Timestamp>>>,
     show_time_strip_popup: Rc<RefCell<bool>>,
+    // the results of sending the time stamps to Aquarius, by the ID of the time stamp
+    sent_times: Rc<RefCell<HashMap<i32, SendState>>>,
+    // the heat numbers proposed for the unassigned time stamps, by the ID of the time stamp
+    suggestions: HashMap<i32, HeatSuggestion>,
 }
 
 impl Widget for &mut TimeStripTab {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let time_strip = self.time_strip.borrow();
+        let sent_times = self.sent_times.borrow();
+        let range = self.range();
         let items: Vec<ListItem> = time_strip
             .iter()
             .rev()
-            .map(|ts| ListItem::from(MyTimeStamp(ts)))
+            .enumerate()
+            .map(|(index, ts)| {
+                let item = ListItem::from(MyTimeStamp(
+                    ts,
+                    ts.id().and_then(|id| sent_times.get(&id)).copied(),
+                    ts.id().and_then(|id| self.suggestions.get(&id)).cloned(),
+                ));
+                match range.as_ref().is_some_and(|range| range.contains(&index)) {
+                    true => item.bg(Color::DarkGray),
+                    false => item,
+                }
+            })
             .collect();
 
         // Create a List from all list items and highlight the currently selected one
@@ -44,16 +82,29 @@ impl Widget for &mut TimeStripTab {
 }
 
 impl TimeStripTab {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
+        client: Rc<RefCell<AquariusClient>>,
+        heats: Rc<RefCell<Vec<Heat>>>,
         time_strip: Rc<RefCell<TimeStrip>>,
+        suggester: Rc<RefCell<HeatSuggester>>,
         selected_time_stamp: Rc<RefCell<Option<Timestamp>>>,
+        selected_range: Rc<RefCell<Vec<Timestamp>>>,
         show_time_strip_popup: Rc<RefCell<bool>>,
+        sent_times: Rc<RefCell<HashMap<i32, SendState>>>,
     ) -> Self {
         Self {
             state: ListState::default(),
+            range_start: None,
+            client,
+            heats,
             time_strip,
+            suggester,
             selected_time_stamp,
+            selected_range,
             show_time_strip_popup,
+            sent_times,
+            suggestions: HashMap::new(),
         }
     }
 
@@ -64,16 +115,114 @@ impl TimeStripTab {
             KeyCode::Down => self.state.select_next(),
             KeyCode::Home => self.state.select_first(),
             KeyCode::End => self.state.select_last(),
-            KeyCode::Char('h') => self.state.select(None),
+            KeyCode::Char('h') => {
+                self.state.select(None);
+                self.range_start = None;
+            }
+            KeyCode::Char('v') => {
+                // select a range of time stamps from the selected one, or stop selecting it
+                self.range_start = match self.range_start {
+                    Some(_) => None,
+                    None => self.state.selected(),
+                };
+            }
+            KeyCode::Esc => self.range_start = None,
+            KeyCode::Char('b') => {
+                // assign the bibs of the start list to the selected range in the order the boats finished
+                let range = self.selected_range.borrow().clone();
+                if let Some(bibs) = self.start_list(&range) {
+                    let updated = self.time_strip.borrow_mut().set_bibs(&range, &bibs).await;
+                    match updated {
+                        Ok(updated) => send_times(&self.client, &self.sent_times, &updated, true),
+                        Err(err) => warn!(%err, "Error assigning bibs to time stamps:"),
+                    }
+                }
+            }
+            KeyCode::Char(key @ ('<' | '>')) => {
+                // shift the heat numbers of the selected range to the previous or next heats
+                let range = self.selected_range.borrow().clone();
+                let delta = if key == '>' { 1 } else { -1 };
+                let updated = self.time_strip.borrow_mut().shift_heat_nrs(&range, delta).await;
+                match updated {
+                    Ok(updated) => send_times(&self.client, &self.sent_times, &updated, true),
+                    Err(err) => warn!(%err, delta, "Error shifting heat numbers of time stamps:"),
+                }
+            }
             KeyCode::Enter
                 // open popup if a time stamp is selected
                 if self.state.selected().is_some() => {
                     *self.show_time_strip_popup.borrow_mut() = true;
                 }
+            KeyCode::Char('l') => {
+                // load older time stamps, they are shown on top of the list
+                let loaded = self.time_strip.borrow_mut().load_more().await;
+                match loaded {
+                    Ok(count) => {
+                        if let Some(selected) = self.state.selected() {
+                            self.state.select(Some(selected + count));
+                        }
+                        info!(count, "Loaded older time stamps:");
+                    }
+                    Err(err) => warn!(%err, "Error loading older time stamps:"),
+                }
+            }
+            KeyCode::Char('a') => {
+                // accept the heat number proposed for the selected time stamp
+                let selected = self.selected_time_stamp.borrow().clone();
+                if let Some(timestamp) = selected {
+                    let suggestion = self
+                        .time_strip
+                        .borrow()
+                        .suggest_heat(&self.suggester.borrow(), &timestamp);
+                    if let Some(suggestion) = suggestion {
+                        assign_heat(
+                            &self.time_strip,
+                            &self.client,
+                            &self.sent_times,
+                            &timestamp,
+                            suggestion.heat_nr,
+                        )
+                        .await;
+                    }
+                }
+            }
+            KeyCode::Char('u') => {
+                // revert the last change of the heat number or bib of the selected time stamp
+                let selected = self.selected_time_stamp.borrow().clone();
+                if let Some(timestamp) = selected {
+                    let reverted = self.time_strip.borrow_mut().undo(&timestamp).await;
+                    match reverted {
+                        // Aquarius gets the reverted heat number and bib
+                        Ok(reverted) if reverted.heat_nr().is_some() => {
+                            send_times(&self.client, &self.sent_times, &[reverted], true)
+                        }
+                        Ok(_) => {}
+                        Err(err) => warn!(%err, "Error undoing change of time stamp:"),
+                    }
+                }
+            }
+            KeyCode::Char('R') => {
+                // restore the time stamp deleted last, `r` reads the open heats
+                let deleted = self.time_strip.borrow().deleted().await;
+                match deleted {
+                    Ok(deleted) => {
+                        if let Some(entry) = deleted.first() {
+                            let restored = self.time_strip.borrow_mut().restore(entry.timestamp_id).await;
+                            if let Err(err) = restored {
+                                warn!(%err, "Error restoring time stamp:");
+                            }
+                        }
+                    }
+                    Err(err) => warn!(%err, "Error reading deleted time stamps:"),
+                }
+            }
             KeyCode::Delete => {
                 // delete the selected time stamp
-                if let Some(timestamp) = self.selected_time_stamp.borrow_mut().take() {
-                    self.time_strip.borrow_mut().delete(&timestamp.time).await.unwrap();
+                let selected = self.selected_time_stamp.borrow_mut().take();
+                if let Some(timestamp) = selected
+                    && let Err(err) = self.time_strip.borrow_mut().delete(&timestamp).await
+                {
+                    warn!(%err, "Error deleting time stamp:");
                 }
             }
             _ => {}
@@ -81,6 +230,60 @@ impl TimeStripTab {
         self.update_selected_time_stamp();
     }
 
+    /// Returns the bibs of the start list of the heat the given time stamps are assigned to, without the boats that
+    /// did not start.
+    fn start_list(&self, time_stamps: &[Timestamp]) -> Option<Vec<u8>> {
+        let heat_nr = time_stamps.first()?.heat_nr();
+        let Some(heat_nr) = heat_nr.filter(|_| time_stamps.iter().all(|ts| ts.heat_nr() == heat_nr)) else {
+            warn!("Time stamps have to be assigned to the same heat to assign bibs:");
+            return None;
+        };
+        let heats = self.heats.borrow();
+        let Some(boats) = heats
+            .iter()
+            .find(|heat| heat.number == heat_nr)
+            .and_then(|heat| heat.boats.as_ref())
+        else {
+            warn!(heat_nr, "Start list of heat is not known:");
+            return None;
+        };
+        Some(
+            boats
+                .iter()
+                .filter(|boat| boat.state != BoatState::DidNotStart)
+                .map(|boat| boat.bib)
+                .collect(),
+        )
+    }
+
+    /// Returns the list indices of the selected range, if a range is selected.
+    fn range(&self) -> Option<RangeInclusive<usize>> {
+        let (start, end) = (self.range_start?, self.state.selected()?);
+        Some(start.min(end)..=start.max(end))
+    }
+
+    /// Updates the selected time stamp after the time strip was changed by another station.
+    pub(crate) fn refresh(&mut self) {
+        // the selected time stamp may have been deleted
+        if let Some(selected) = self.state.selected()
+            && selected >= self.time_strip.borrow().len()
+        {
+            self.state.select_last();
+        }
+        self.update_selected_time_stamp();
+    }
+
+    /// Proposes the heat numbers of the unassigned time stamps again, after the time strip or the heats changed. The
+    /// proposals are kept, since each one is found by scanning the whole time strip.
+    pub(crate) fn refresh_suggestions(&mut self) {
+        let time_strip = self.time_strip.borrow();
+        let suggester = self.suggester.borrow();
+        self.suggestions = time_strip
+            .iter()
+            .filter_map(|ts| Some((ts.id()?, time_strip.suggest_heat(&suggester, ts)?)))
+            .collect();
+    }
+
     fn update_selected_time_stamp(&mut self) {
         let time_strip = self.time_strip.borrow();
 
@@ -97,14 +300,105 @@ impl TimeStripTab {
         } else {
             *self.selected_time_stamp.borrow_mut() = None;
         }
+
+        // the range is selected in the reversed list, too
+        *self.selected_range.borrow_mut() = match self.range() {
+            Some(range) => range
+                .filter_map(|index| time_strip.get(time_strip.len().checked_sub(index + 1)?))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+}
+
+/// Assigns a heat number to a time stamp and sends the time to Aquarius.
+/// # Returns
+/// `true` if the heat number was assigned.
+#[allow(clippy::await_holding_refcell_ref)]
+pub(crate) async fn assign_heat(
+    time_strip: &Rc<RefCell<TimeStrip>>,
+    client: &Rc<RefCell<AquariusClient>>,
+    sent_times: &Rc<RefCell<HashMap<i32, SendState>>>,
+    timestamp: &Timestamp,
+    heat_nr: i16,
+) -> bool {
+    match time_strip.borrow_mut().set_heat_nr(timestamp, heat_nr).await {
+        Ok(timestamp) => {
+            send_times(client, sent_times, &[timestamp], false);
+            true
+        }
+        Err(err) => {
+            warn!(%err, heat_nr, "Error assigning heat to time stamp:");
+            false
+        }
+    }
+}
+
+/// Assigns a heat number to the selected range of time stamps and sends the times to Aquarius.
+/// # Returns
+/// `true` if the heat number was assigned.
+#[allow(clippy::await_holding_refcell_ref)]
+pub(crate) async fn assign_heat_to_range(
+    time_strip: &Rc<RefCell<TimeStrip>>,
+    client: &Rc<RefCell<AquariusClient>>,
+    sent_times: &Rc<RefCell<HashMap<i32, SendState>>>,
+    time_stamps: &[Timestamp],
+    heat_nr: i16,
+) -> bool {
+    match time_strip.borrow_mut().set_heat_nrs(time_stamps, heat_nr).await {
+        Ok(updated) => {
+            send_times(client, sent_times, &updated, false);
+            true
+        }
+        Err(err) => {
+            warn!(%err, heat_nr, count = time_stamps.len(), "Error assigning heat to time stamps:");
+            false
+        }
+    }
+}
+
+/// Sends the times of the given time stamps to Aquarius and records whether they were accepted.
+/// # Arguments
+/// * `with_bib` - Whether the bibs of the time stamps are sent, too.
+fn send_times(
+    client: &Rc<RefCell<AquariusClient>>,
+    sent_times: &Rc<RefCell<HashMap<i32, SendState>>>,
+    time_stamps: &[Timestamp],
+    with_bib: bool,
+) {
+    for timestamp in time_stamps {
+        let bib = timestamp.bib().filter(|_| with_bib);
+        let state = match client.borrow_mut().send_time(timestamp, bib) {
+            Ok(Delivery::Accepted) => SendState::Accepted,
+            Ok(Delivery::Queued { .. }) => SendState::Queued,
+            Err(err) => {
+                warn!(%err, heat_nr = timestamp.heat_nr(), "Error sending time to Aquarius:");
+                SendState::Failed
+            }
+        };
+        // only persisted time stamps are assigned to heats
+        if let Some(id) = timestamp.id() {
+            sent_times.borrow_mut().insert(id, state);
+        }
     }
 }
 
 impl<'a> From<MyTimeStamp<'a>> for ListItem<'a> {
     fn from(value: MyTimeStamp<'a>) -> Self {
         let prefix: String = (value.0.split()).into();
-        ListItem::new(format!(
-            "{:5}  {}  {:3}  {:2}  {}",
+        // void time stamps stay in the list, but are neither ranked nor paired
+        let void = value.0.is_voided().then(|| match value.0.void_reason() {
+            Some(reason) => format!("ungültig: {reason}"),
+            None => "ungültig".to_owned(),
+        });
+        let remark = [void.as_deref(), value.0.note()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let item = ListItem::new(format!(
+            "{:5}  {}  {:3}  {:2}  {}  {}  {}  {}",
             prefix,
             value.0.time.format(DATE_FORMAT_STR),
             value.0.heat_nr().unwrap_or_default(),
@@ -112,9 +406,26 @@ impl<'a> From<MyTimeStamp<'a>> for ListItem<'a> {
             match value.0.is_persisted() {
                 true => "\u{1F506}",
                 false => "\u{1F329}",
-            }
-        ))
+            },
+            // whether the time was accepted by Aquarius
+            match value.1 {
+                Some(SendState::Accepted) => "\u{2714}",
+                Some(SendState::Queued) => "\u{23F3}",
+                Some(SendState::Failed) => "\u{2718}",
+                None => " ",
+            },
+            // the heat number proposed for an unassigned time stamp
+            match value.2 {
+                Some(suggestion) => format!("Lauf {}? ({:.0} %)", suggestion.heat_nr, suggestion.confidence * 100.0),
+                None => String::new(),
+            },
+            remark
+        ));
+        match value.0.is_voided() {
+            true => item.style(Style::new().dim()),
+            false => item,
+        }
     }
 }
 
-struct MyTimeStamp<'a>(&'a Timestamp);
+struct MyTimeStamp<'a>(&'a Timestamp, Option<SendState>, Option<HeatSuggestion>);
@@ -1,4 +1,9 @@
+use crate::trigger::{ChannelMap, TriggerSource};
+use aquarius::codec::Codec;
+use aquarius::endpoint::Endpoint;
+use aquarius::error::AquariusErr;
 use clap::Parser;
+use std::path::PathBuf;
 
 pub mod built_info {
     // The file has been placed there by the build script.
@@ -10,11 +15,12 @@ pub mod built_info {
 #[command(version = built_info::PKG_VERSION)]
 #[command(about = built_info::PKG_DESCRIPTION, long_about = None)]
 pub(crate) struct Args {
-    /// The host to connect to
+    /// The hosts to connect to, separated by commas and starting with the primary one, e.g.
+    /// `aquarius,aquarius-backup:2049`. Hosts without a port use the port given by `--port`
     #[arg(long, default_value = "aquarius")]
     pub(crate) host: String,
 
-    /// The port to connect to
+    /// The default port to connect to
     #[arg(long, default_value = "2048")]
     pub(crate) port: u16,
 
@@ -22,6 +28,56 @@ pub(crate) struct Args {
     #[arg(long, default_value = "500")]
     pub(crate) timeout: u16,
 
+    /// The split number of the intermediate times taken with the `i` key, e.g. 1 for a 500 m tower
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u8).range(1..64))]
+    pub(crate) intermediate_split: u8,
+
+    /// The name of this timekeeper station, recorded in the audit trail of the time stamps, e.g. `finish`
+    #[arg(long, default_value = "timekeeper")]
+    pub(crate) station: String,
+
+    /// The offset in milliseconds added to the clock of this station, e.g. `-250` if it is 250 ms ahead. If not
+    /// given, the offset is measured against the clock of the database server
+    #[arg(long, allow_negative_numbers = true)]
+    pub(crate) clock_offset: Option<i64>,
+
+    /// The journal file every time stamp is written to before it is stored in the database. Time stamps that could not
+    /// be stored, e.g. while the database was unreachable, are stored from the journal on the next start
+    #[arg(long, default_value = "timekeeper-journal.jsonl")]
+    pub(crate) journal: PathBuf,
+
+    /// The timing device to take times from, e.g. `tcp://timing:7000` for a device or serial-to-network adapter
+    /// connected by TCP, or `/dev/ttyUSB0` for a serial line. The device sends a line with the channel and the time of
+    /// day for each impulse, e.g. `C1 10:15:30.1234`
+    #[arg(long, value_parser = TriggerSource::parse)]
+    pub(crate) trigger: Option<TriggerSource>,
+
+    /// The splits of the channels of the timing device, e.g. `0=start,1=finish,2=1` with the split number of an
+    /// intermediate split. Impulses of other channels are ignored
+    #[arg(long, default_value = "0=start,1=finish", value_parser = ChannelMap::parse)]
+    pub(crate) trigger_channels: ChannelMap,
+
+    /// The offset in milliseconds added to the clock of the timing device, e.g. `-250` if it is 250 ms ahead. The times
+    /// of the device are not corrected by the clock offset of this station, as the device has a clock of its own
+    #[arg(long, default_value = "0", allow_negative_numbers = true)]
+    pub(crate) trigger_clock_offset: i64,
+
+    /// The file to queue the times in that could not be sent to Aquarius
+    #[arg(long, default_value = "timekeeper-outbox.json")]
+    pub(crate) outbox: PathBuf,
+
+    /// The directory the timekeeping protocol of the day is exported to with the `x` key
+    #[arg(long, default_value = ".")]
+    pub(crate) export_dir: PathBuf,
+
+    /// Record all lines sent to and received from Aquarius to the given JSONL file
+    #[arg(long)]
+    pub(crate) record: Option<PathBuf>,
+
+    /// The text encoding of the link to Aquarius
+    #[arg(long, value_enum, default_value_t = Codec::Windows1252)]
+    pub(crate) codec: Codec,
+
     /// The database host
     #[arg(long, default_value = "data")]
     pub(crate) db_host: String,
@@ -43,6 +99,15 @@ pub(crate) struct Args {
     pub(crate) db_password: String,
 }
 
+impl Args {
+    /// Returns the Aquarius endpoints given by `--host` and `--port`.
+    /// # Errors
+    /// If the endpoints could not be parsed.
+    pub(crate) fn endpoints(&self) -> Result<Vec<Endpoint>, AquariusErr> {
+        Endpoint::parse_list(&self.host, self.port)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -52,7 +117,19 @@ mod tests {
         let args = Args::parse();
         assert_eq!(args.host, "aquarius");
         assert_eq!(args.port, 2048);
+        assert_eq!(args.endpoints().unwrap(), vec![Endpoint::new("aquarius", 2048)]);
         assert_eq!(args.timeout, 500);
+        assert_eq!(args.codec, Codec::Windows1252);
+        assert_eq!(args.intermediate_split, 1);
+        assert_eq!(args.station, "timekeeper");
+        assert!(args.clock_offset.is_none());
+        assert_eq!(args.journal, PathBuf::from("timekeeper-journal.jsonl"));
+        assert!(args.trigger.is_none());
+        assert_eq!(args.trigger_channels, ChannelMap::parse("0=start,1=finish").unwrap());
+        assert_eq!(args.trigger_clock_offset, 0);
+        assert_eq!(args.outbox, PathBuf::from("timekeeper-outbox.json"));
+        assert_eq!(args.export_dir, PathBuf::from("."));
+        assert!(args.record.is_none());
         assert_eq!(args.db_name, "Regatta_Test");
         assert_eq!(args.db_host, "data");
         assert_eq!(args.db_port, 1433);
@@ -1,6 +1,7 @@
 mod app;
 mod args;
 mod error;
+mod trigger;
 
 use ::tui_logger::{init_logger, set_default_level};
 use app::App;
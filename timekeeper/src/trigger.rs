@@ -0,0 +1,338 @@
+use ::chrono::{DateTime, Local, NaiveTime, TimeDelta, Utc};
+use ::db::timekeeper::Split;
+use ::std::collections::BTreeMap;
+use ::std::fmt::{Display, Formatter, Result as FmtResult};
+use ::std::fs::File;
+use ::std::io::{BufRead, BufReader, Result as IoResult};
+use ::std::net::TcpStream;
+use ::std::path::PathBuf;
+use ::std::sync::mpsc::Sender;
+use ::std::thread;
+use ::std::time::Duration;
+use ::tracing::{debug, info, warn};
+
+/// How long to wait before the timing device is opened again after the connection was lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How far the clock of a timing device may be ahead of the local clock. An impulse further ahead was taken the day
+/// before, e.g. just before midnight.
+const MAX_DEVICE_AHEAD: TimeDelta = TimeDelta::hours(1);
+
+/// The prefix of timing devices connected by TCP, e.g. `tcp://timing:7000`.
+const TCP_PREFIX: &str = "tcp://";
+
+/// Where the impulses of a timing device are read from, e.g. a light barrier at the finish line or a start pistol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TriggerSource {
+    /// A timing device or a serial-to-network adapter listening on the given address, e.g. `timing:7000`. The
+    /// connection is opened again if it is lost.
+    Tcp(String),
+
+    /// A serial device or any other line stream, e.g. `/dev/ttyUSB0`. The serial line has to be set up before, e.g.
+    /// with `stty -F /dev/ttyUSB0 9600 raw`. Reading stops at the end of the stream, so a recorded file can be replayed.
+    Device(PathBuf),
+}
+
+impl TriggerSource {
+    /// Parses a trigger source. Sources starting with `tcp://` are connected by TCP, any other is opened as a file.
+    /// # Arguments
+    /// * `source` - The source to parse, e.g. `tcp://timing:7000` or `/dev/ttyUSB0`.
+    /// # Errors
+    /// If the source or its address is empty.
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        match source.trim().strip_prefix(TCP_PREFIX) {
+            Some("") => Err(format!("Missing address of timing device: {source}")),
+            Some(address) => Ok(TriggerSource::Tcp(address.to_owned())),
+            None if source.trim().is_empty() => Err("Missing timing device".to_owned()),
+            None => Ok(TriggerSource::Device(PathBuf::from(source.trim()))),
+        }
+    }
+
+    fn open(&self) -> IoResult<Box<dyn BufRead>> {
+        match self {
+            TriggerSource::Tcp(address) => Ok(Box::new(BufReader::new(TcpStream::connect(address)?))),
+            TriggerSource::Device(path) => Ok(Box::new(BufReader::new(File::open(path)?))),
+        }
+    }
+}
+
+impl Display for TriggerSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            TriggerSource::Tcp(address) => write!(f, "{TCP_PREFIX}{address}"),
+            TriggerSource::Device(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Maps the channels of a timing device to the splits of the time stamps, e.g. channel 0 to the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChannelMap(BTreeMap<u8, Split>);
+
+impl ChannelMap {
+    /// Parses a comma separated list of channels and their splits, e.g. `0=start,1=finish,2=1`. Intermediate splits
+    /// are given by their split number.
+    /// # Arguments
+    /// * `list` - The channels to parse.
+    /// # Errors
+    /// If a channel or split is invalid, a channel is mapped twice or the list contains no channel.
+    pub(crate) fn parse(list: &str) -> Result<Self, String> {
+        let mut channels = BTreeMap::new();
+        for mapping in list.split(',').map(str::trim).filter(|mapping| !mapping.is_empty()) {
+            let (channel, split) = mapping
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid channel mapping, expected `channel=split`: {mapping}"))?;
+            let channel: u8 = channel
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid channel {channel}: {err}"))?;
+            let split = match split.trim() {
+                "start" => Split::Start,
+                "finish" => Split::Finish,
+                split_nr => match split_nr.parse::<u8>() {
+                    Ok(split_nr @ 1..=63) => Split::Intermediate(split_nr),
+                    _ => return Err(format!("Invalid split {split_nr}, expected start, finish or 1 to 63")),
+                },
+            };
+            if channels.insert(channel, split).is_some() {
+                return Err(format!("Channel {channel} is mapped twice"));
+            }
+        }
+        if channels.is_empty() {
+            return Err("No channel mapped".to_owned());
+        }
+        Ok(ChannelMap(channels))
+    }
+
+    /// Returns the split the given channel is mapped to, if any.
+    pub(crate) fn split(&self, channel: u8) -> Option<&Split> {
+        self.0.get(&channel)
+    }
+}
+
+/// An impulse of a timing device, e.g. a boat crossing the light barrier at the finish line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Impulse {
+    /// The channel of the device the impulse was received on.
+    pub(crate) channel: u8,
+
+    /// The split the channel is mapped to.
+    pub(crate) split: Split,
+
+    /// The time taken by the device, corrected by the clock offset of the device.
+    pub(crate) time: DateTime<Utc>,
+}
+
+/// Reads the impulses of a timing device and sends those of mapped channels to the given sender. A lost TCP connection
+/// is opened again, this returns at the end of a device stream or when the receiver is dropped.
+/// # Arguments
+/// * `source` - The timing device to read from.
+/// * `channels` - The splits of the channels, impulses of other channels are ignored.
+/// * `clock_offset` - The offset added to the clock of the device.
+/// * `sender` - The sender to send the impulses to.
+pub(crate) fn read_impulses(
+    source: &TriggerSource,
+    channels: &ChannelMap,
+    clock_offset: TimeDelta,
+    sender: &Sender<Impulse>,
+) {
+    loop {
+        match source.open() {
+            Ok(reader) => {
+                info!(%source, "Reading impulses of timing device:");
+                let mut failed = false;
+                for line in reader.lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(err) => {
+                            warn!(%err, %source, "Error reading from timing device:");
+                            failed = true;
+                            break;
+                        }
+                    };
+                    let Some((channel, time)) = parse_line(&line) else {
+                        debug!(line, "Skipping line of timing device:");
+                        continue;
+                    };
+                    let Some(time) = device_time(time, Local::now()) else {
+                        debug!(line, "Skipping impulse at a time that does not exist locally:");
+                        continue;
+                    };
+                    let time = time + clock_offset;
+                    let Some(split) = channels.split(channel) else {
+                        debug!(channel, %time, "Skipping impulse of unmapped channel:");
+                        continue;
+                    };
+                    let impulse = Impulse {
+                        channel,
+                        split: split.clone(),
+                        time,
+                    };
+                    if sender.send(impulse).is_err() {
+                        return;
+                    }
+                }
+                if !failed && matches!(source, TriggerSource::Device(_)) {
+                    info!(%source, "End of timing device stream:");
+                    return;
+                }
+                warn!(%source, "Lost connection to timing device:");
+            }
+            Err(err) => warn!(%err, %source, "Error opening timing device:"),
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// Parses a line of a timing device with a channel and the time of day, e.g. `C1 10:15:30.1234` or the
+/// ALGE/TAG-Heuer style `0012 C1M 10:15:30.1234 00` with an impulse number before and a flag after the channel. The
+/// channel is the token in front of the time.
+/// # Arguments
+/// * `line` - The line to parse.
+/// # Returns
+/// The channel and the time of day of the impulse, or `None` if the line holds no impulse.
+fn parse_line(line: &str) -> Option<(u8, NaiveTime)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let pos = tokens.iter().position(|token| token.contains(':'))?;
+    let channel = tokens
+        .get(pos.checked_sub(1)?)?
+        .trim_start_matches(['C', 'c'])
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let channel = channel.parse().ok()?;
+    let time = NaiveTime::parse_from_str(tokens[pos], "%H:%M:%S%.f").ok()?;
+    Some((channel, time))
+}
+
+/// Returns the time of an impulse received at the given local time. The impulse belongs to the day it was received on,
+/// unless its time of day is far ahead, then it was taken the day before and received after midnight.
+/// # Arguments
+/// * `time` - The time of day taken by the device.
+/// * `now` - The local time the impulse was received at.
+/// # Returns
+/// The time of the impulse, or `None` if the time of day does not exist on that day, e.g. when clocks go forward.
+fn device_time(time: NaiveTime, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let mut day = now.date_naive();
+    if day.and_time(time) - now.naive_local() > MAX_DEVICE_AHEAD {
+        day = day.pred_opt()?;
+    }
+    Some(day.and_time(time).and_local_timezone(Local).earliest()?.to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::chrono::NaiveDate;
+    use ::std::io::Write;
+    use ::std::net::TcpListener;
+    use ::std::sync::mpsc;
+
+    fn time(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M:%S%.f").unwrap()
+    }
+
+    fn local(time: &str) -> DateTime<Local> {
+        NaiveDate::from_ymd_opt(2026, 6, 1)
+            .unwrap()
+            .and_time(self::time(time))
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("C1 10:15:30.1234"), Some((1, time("10:15:30.1234"))));
+        assert_eq!(parse_line("0012 c0M 09:00:00.5 00"), Some((0, time("09:00:00.5"))));
+        assert_eq!(parse_line("3 23:59:59"), Some((3, time("23:59:59"))));
+        assert_eq!(parse_line("10:15:30.1234"), None);
+        assert_eq!(parse_line("C1 25:15:30"), None);
+        assert_eq!(parse_line("ALGE TIMY3"), None);
+    }
+
+    #[test]
+    fn test_device_time() {
+        let now = local("10:00:00");
+        assert_eq!(device_time(time("09:59:59.5"), now), Some(local("09:59:59.5").to_utc()));
+        // the clock of the device may be a little ahead
+        assert_eq!(device_time(time("10:00:00.5"), now), Some(local("10:00:00.5").to_utc()));
+        assert_eq!(device_time(time("10:59:00"), now), Some(local("10:59:00").to_utc()));
+
+        // an impulse taken just before midnight and received after it
+        let now = local("00:00:00.2");
+        let before_midnight = local("23:59:59.9") - TimeDelta::days(1);
+        assert_eq!(device_time(time("23:59:59.9"), now), Some(before_midnight.to_utc()));
+    }
+
+    #[test]
+    fn test_parse_channels() {
+        let channels = ChannelMap::parse("0=start, 1=finish,2=1").unwrap();
+        assert_eq!(channels.split(0), Some(&Split::Start));
+        assert_eq!(channels.split(1), Some(&Split::Finish));
+        assert_eq!(channels.split(2), Some(&Split::Intermediate(1)));
+        assert_eq!(channels.split(3), None);
+        assert!(ChannelMap::parse("0=start,0=finish").is_err());
+        assert!(ChannelMap::parse("0=64").is_err());
+        assert!(ChannelMap::parse("start").is_err());
+        assert!(ChannelMap::parse("").is_err());
+
+        assert_eq!(
+            TriggerSource::parse("tcp://timing:7000"),
+            Ok(TriggerSource::Tcp("timing:7000".to_owned()))
+        );
+        assert_eq!(
+            TriggerSource::parse("/dev/ttyUSB0"),
+            Ok(TriggerSource::Device(PathBuf::from("/dev/ttyUSB0")))
+        );
+        assert!(TriggerSource::parse("tcp://").is_err());
+    }
+
+    #[test]
+    fn test_read_impulses_from_fake_device() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let source = TriggerSource::parse(&format!("tcp://{}", listener.local_addr().unwrap())).unwrap();
+        let (dropped_sender, dropped_receiver) = mpsc::channel::<()>();
+        let device = thread::spawn(move || {
+            // the first connection is lost after some impulses
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(b"ALGE TIMY3\r\n0001 C0 10:00:00.0000 00\r\n0002 C5 10:00:01.0000 00\r\n")
+                .unwrap();
+            stream.write_all(b"0003 C1 10:07:00.2500 00\r\n").unwrap();
+            drop(stream);
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"0004 C1 10:07:01.0000 00\r\n").unwrap();
+            // the impulses after the receiver is dropped stop the reader
+            dropped_receiver.recv().unwrap();
+            let _ = stream.write_all(b"0005 C1 10:07:02.0000 00\r\n");
+        });
+
+        let channels = ChannelMap::parse("0=start,1=finish").unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let offset = TimeDelta::milliseconds(-250);
+        let reader = thread::spawn(move || read_impulses(&source, &channels, offset, &sender));
+
+        let timeout = Duration::from_secs(5);
+        let impulses: Vec<Impulse> = (0..3).map(|_| receiver.recv_timeout(timeout).unwrap()).collect();
+        // the times are corrected by the clock offset of the device
+        let received = |time: &str| device_time(self::time(time), Local::now()).unwrap() + offset;
+        assert_eq!(
+            impulses[0],
+            Impulse {
+                channel: 0,
+                split: Split::Start,
+                time: received("10:00:00"),
+            }
+        );
+        assert_eq!(
+            (impulses[1].split.clone(), impulses[1].time),
+            (Split::Finish, received("10:07:00.25"))
+        );
+        // the reader connected again
+        assert_eq!(impulses[2].time, received("10:07:01"));
+
+        drop(receiver);
+        dropped_sender.send(()).unwrap();
+        device.join().unwrap();
+        reader.join().unwrap();
+    }
+}